@@ -1,11 +1,11 @@
 use soulog::*;
-use crate::{archive::Archive, unwrap_opt};
+use crate::{archive::Archive, Home, Result};
 
 macro_rules! log_attr {
     ([$entry:ident, $logger:ident] $($name:ident$(($multi:expr))?),* $(,)?) => {$(
         let _multi = true;
         $(let _multi = $multi;)?
-        let attr = $entry.$name($logger.hollow());
+        let attr = $entry.$name()?;
         if _multi {
             log!(($logger.vital) $name("{attr:#?}\n") as Result);
         } else {
@@ -14,8 +14,8 @@ macro_rules! log_attr {
     )*}
 }
 
-pub fn about(is_moc: bool, uid: String, logger: impl Logger) {
-    let archive = Archive::load(logger.hollow());
+pub fn about(is_moc: bool, uid: String, home: &Home, logger: impl Logger) -> Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
 
     if is_moc {
         about_moc(archive, uid, logger)
@@ -24,10 +24,8 @@ pub fn about(is_moc: bool, uid: String, logger: impl Logger) {
     }
 }
 
-fn about_entry(archive: Archive, uid: String, mut logger: impl Logger) {
-    let error_msg = format!("Entry of uid '{uid}' not found in archive");
-    let mut entry = unwrap_opt!((archive.get_entry(uid, logger.hollow())) with logger, format: About("{error_msg}"));
-    std::mem::drop(error_msg);
+fn about_entry(archive: Archive, uid: String, mut logger: impl Logger) -> Result<()> {
+    let mut entry = archive.get_entry(uid)?;
 
     // Print the stuff
     log!((logger) About(""));
@@ -39,13 +37,14 @@ fn about_entry(archive: Archive, uid: String, mut logger: impl Logger) {
         description(false),
         notes,
         tags,
+        private(false),
     }
+
+    Ok(())
 }
 
-fn about_moc(archive: Archive, uid: String, mut logger: impl Logger) {
-    let error_msg = format!("MOC of uid '{uid}' not found in archive");
-    let mut moc = unwrap_opt!((archive.get_moc(uid, logger.hollow())) with logger, format: About("{error_msg}"));
-    std::mem::drop(error_msg);
+fn about_moc(archive: Archive, uid: String, mut logger: impl Logger) -> Result<()> {
+    let mut moc = archive.get_moc(uid)?;
 
     // Print the stuff
     log!((logger) About(""));
@@ -56,5 +55,8 @@ fn about_moc(archive: Archive, uid: String, mut logger: impl Logger) {
         title(false),
         description(false),
         notes,
+        private(false),
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
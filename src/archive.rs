@@ -1,158 +1,292 @@
 use lazy_db::*;
-use crate::home_dir;
+use crate::Home;
+use crate::backups;
+use crate::crypt;
+use crate::history;
 use crate::list;
+use crate::index;
+use crate::lock::{self, Lock, LockKind};
+use crate::migrate::{self, FORMAT_VERSION};
+use crate::transaction;
 use crate::unwrap_opt;
+use crate::{Context, Error};
 use soulog::*;
 use std::fs;
 use std::path::PathBuf;
 use std::path::Path;
+use std::rc::Rc;
 use crate::entry::Entry;
 use crate::moc::MOC;
 
 pub struct Archive {
+    home: Home,
     database: LazyDB,
     uid: u64,
-    pub itver: u16,
+    pub itver: u32,
+    /// The version of the archive's layout, which `migrate` upgrades older archives from
+    pub format_version: u16,
+    /// The lock on the home's archive (`None` for previews, backups and archives within a transaction, which only this process sees)
+    _lock: Option<Lock>,
+    /// The journal of the transaction this is being modified within (see `transaction::run`)
+    journal: Option<Rc<transaction::Journal>>,
 }
 
 impl Archive {
-    /// Initialises a new archive, will throw error if one already exists
-    pub fn init(mut logger: impl Logger) -> Self {
-        let path = home_dir().join("archive");
+    /// Initialises a new archive, will return an error if one already exists
+    pub fn init(home: &Home, mut logger: impl Logger) -> crate::Result<Self> {
+        let lock = lock::acquire(home, LockKind::Exclusive, logger.hollow())?;
+        let path = home.archive();
         let path_string = path.to_string_lossy();
         // Check if archive already exists
         if path.exists() {
-            log!((logger.error) Init("Archive '{path_string}' already exists, try wiping it before initialising again") as Fatal);
-            return logger.crash()
+            return Err(Error::AlreadyExists(format!("Archive '{path_string}' already exists, try wiping it before initialising again")));
         }
 
         log!((logger) Init("Initialising a new archive at '{path_string}'..."));
-        let database = if_err!((logger) [Init, err => ("While initialising database: {err:?}")] retry LazyDB::init(&path));
+        let database = LazyDB::init(&path).context("While initialising database")?;
+        crypt::forget(&path); // in case this process had unlocked an archive that was here before
         
         let uid = {
             use std::collections::hash_map::RandomState;
             use std::hash::{BuildHasher, Hasher};
             RandomState::new().build_hasher().finish()
         };
-        let itver = 0u16;
+        let itver = 0u32;
 
         log!((logger) Init("Writing uid and itver to archive..."));
-        if_err!((logger) [Init, err => ("While writing uid: {err:?}")] retry write_database!((&database) uid = new_u64(uid)));
-        if_err!((logger) [Init, err => ("While writing itver: {err:?}")] retry write_database!((&database) itver = new_u16(itver)));
+        write_database!((&database) uid = new_u64(uid)).context("While writing uid")?;
+        write_database!((&database) itver = new_u32(itver)).context("While writing itver")?;
+        write_database!((&database) format_version = new_u16(FORMAT_VERSION)).context("While writing format version")?;
 
         log!((logger) Init("Initialising sorted and unsorted entry containers..."));
-        if_err!((logger) [Init, err => ("While writing stack length: {err:?}")] retry write_database!((&database) /order/sorted::length = new_u16(0)));
-        if_err!((logger) [Init, err => ("While writing stack length: {err:?}")] retry write_database!((&database) /order/unsorted::length = new_u16(0)));
+        write_database!((&database) /order/sorted::length = new_u32(0)).context("While writing stack length")?;
+        write_database!((&database) /order/unsorted::length = new_u32(0)).context("While writing stack length")?;
+
+        log!((logger) Init("Initialising index containers..."));
+        for kind in ["entries", "mocs"] {
+            search_database!((&database) /index/(kind)/tags).context("While initialising index")?;
+            search_database!((&database) /index/(kind)/words).context("While initialising index")?;
+        }
 
         log!((logger.vital) Init("Successfully initialised archive '{path_string}'") as Log);
-        Self {
+        Ok(Self {
+            home: home.clone(),
             database,
             uid,
             itver,
+            format_version: FORMAT_VERSION,
+            _lock: Some(lock),
+            journal: None,
+        })
+    }
+
+    /// Loads the archive of the home's diary to modify it, initialising a new one if it doesn't exist (named diaries must already exist)
+    ///
+    /// The archive stays locked until it is dropped, so other processes can neither read nor modify it in the meantime
+    ///
+    /// Archives of older format versions are migrated first (after backing them up)
+    pub fn load(home: &Home, logger: impl Logger) -> crate::Result<Self> {
+        Self::load_locked(home, LockKind::Exclusive, true, logger)
+    }
+
+    /// Loads the archive of the home's diary only to read it, which other readers may do at the same time
+    pub fn load_shared(home: &Home, logger: impl Logger) -> crate::Result<Self> {
+        // Initialising a missing archive or recovering from an interrupted transaction are modifications
+        if !home.archive().is_dir() || home.staging().exists() || home.journal().exists() {
+            return Self::load(home, logger);
+        }
+        let archive = Self::load_locked(home, LockKind::Shared, false, logger.hollow())?;
+
+        // So is migrating it or building its missing index
+        if migrate::outdated(&archive)? || !archive.database_exists("index") {
+            drop(archive);
+            return Self::load(home, logger);
         }
+        Ok(archive)
     }
 
-    /// Loads an archive at the cli's home
-    #[inline]
-    pub fn load(logger: impl Logger) -> Self {
-        let path = home_dir().join("archive");
-        Self::load_dir(path, logger)
+    /// Loads the archive of the home's diary to modify it, leaving it at the format version it's at
+    pub(crate) fn load_unmigrated(home: &Home, logger: impl Logger) -> crate::Result<Self> {
+        Self::load_locked(home, LockKind::Exclusive, false, logger)
+    }
+
+    fn load_locked(home: &Home, kind: LockKind, migrate: bool, logger: impl Logger) -> crate::Result<Self> {
+        // Named diaries are only ever created explicitly so typos don't spawn new ones
+        if !home.is_default() && !home.path().is_dir() {
+            return Err(Error::NotFound(format!("Diary '{0}' doesn't exist; run `diary-cli diaries new {0}` to create it", home.diary())));
+        }
+        let lock = lock::acquire(home, kind, logger.hollow())?;
+        if kind == LockKind::Exclusive {
+            transaction::recover(home, logger.hollow())?;
+        }
+        let mut archive = Self::load_dir(home, home.archive(), logger.hollow())?;
+        if migrate && migrate::upgrade(&archive, logger.hollow())? {
+            archive = Self::load_dir(home, home.archive(), logger.hollow())?; // for the new format version
+        }
+        if migrate {
+            index::ensure(&archive, logger)?;
+        }
+        archive._lock = Some(lock);
+        Ok(archive)
     }
 
     /// Loads an archive at a specified path
-    pub fn load_dir(path: PathBuf, mut logger: impl Logger) -> Self {
+    pub fn load_dir(home: &Home, path: PathBuf, mut logger: impl Logger) -> crate::Result<Self> {
         let path_string = path.to_string_lossy();
         log!((logger) Archive("Loading archive '{path_string}'..."));
 
         // Checks if path exists or not
         if !path.is_dir() {
             log!((logger.vital) Archive("Archive '{path_string}' not found; initialising a new one...") as Inconvenience);
-            return Self::init(logger)
+            return Self::init(home, logger)
         };
 
-        let database = if_err!((logger) [Archive, err => ("While loading archive '{path_string}': {err:?}")] retry LazyDB::load_dir(&path));
+        let database = LazyDB::load_dir(&path).with_context(|| format!("While loading archive '{path_string}'"))?;
+        crypt::unlock(&path, logger.hollow())?;
         log!((logger) Archive("Loading uid and itver of archive..."));
-        let uid = if_err!((logger) [Archive, err => ("While loading archive uid: {err:?}")] retry (|| search_database!((&database) uid)?.collect_u64())());
-        let itver = if_err!((logger) [Archive, err => ("While loading archive itver: {err:?}")] retry (|| search_database!((&database) itver)?.collect_u16())());
+        let uid = search_database!((&database) uid).and_then(|x| x.collect_u64()).context("While loading archive uid")?;
+        let format_version = match path.join("format_version").is_file() {
+            true => search_database!((&database) format_version).and_then(|x| x.collect_u16()).context("While loading archive format version")?,
+            false => 0, // from before archives had format versions
+        };
+        let itver = match format_version {
+            0 | 1 => search_database!((&database) itver).and_then(|x| x.collect_u16()).map(|x| x as u32), // until it's migrated
+            _ => search_database!((&database) itver).and_then(|x| x.collect_u32()),
+        }.context("While loading archive itver")?;
 
         log!((logger.verbose) Archive("Successfully loaded archive at '{path_string}'") as Log);
         log!((logger) Archive(""));
 
-        Self {
+        Ok(Self {
+            home: home.clone(),
             database,
             uid,
             itver,
+            format_version,
+            _lock: None,
+            journal: None,
+        })
+    }
+
+    /// Loads the archive again (eg for its new itver after a modification), staying within the transaction it's in
+    pub fn reload(&self, logger: impl Logger) -> crate::Result<Self> {
+        let mut archive = Self::load_dir(&self.home, self.database.path().to_path_buf(), logger)?;
+        archive.journal = self.journal.clone();
+        Ok(archive)
+    }
+
+    /// The archive as modified within a transaction, journaling the paths it's about to change
+    pub(crate) fn journaled(&self, journal: Rc<transaction::Journal>, logger: impl Logger) -> crate::Result<Self> {
+        let mut archive = Self::load_dir(&self.home, self.database.path().to_path_buf(), logger)?;
+        archive.journal = Some(journal);
+        Ok(archive)
+    }
+
+    /// Records a path within the archive (like `entries/<uid>`) in the transaction's journal before it's changed, so it can be undone
+    ///
+    /// Does nothing outside of a transaction
+    pub fn touch(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        match &self.journal {
+            Some(journal) => journal.record(path.as_ref()),
+            None => Ok(()),
         }
     }
 
-    /// Rolls back to last backup
-    pub fn rollback(force: bool, mut logger: impl Logger) {
-        log!((logger) RollBack("Rolling back to last backup..."));
-        log!((logger.vital) RollBack("Rollback cannot revert successful commits; only unsuccessful ones that corrupt the archive.") as Warning);
-        let path = home_dir().join("backup.ldb");
-        if !path.is_file() {
-            log!((logger.error) RollBack("No recent backups made; cannot rollback") as Fatal);
-            return logger.crash();
-        } Self::load_backup(path, force, logger.hollow());
-        log!((logger.vital) RollBack("Successfully rolled back to last backup") as Log);
+    /// Rolls back to the last backup, or to a specific backup (by id or itver) of the backup history
+    pub fn rollback(home: &Home, to: Option<String>, force: bool, mut logger: impl Logger) -> crate::Result<()> {
+        let _lock = lock::acquire(home, LockKind::Exclusive, logger.hollow())?;
+        let target = match to {
+            Some(x) => x,
+            None => {
+                log!((logger) RollBack("Rolling back to last backup..."));
+                log!((logger.vital) RollBack("Rollback cannot revert successful commits; only unsuccessful ones that corrupt the archive (use `--to` to go back further).") as Warning);
+                let path = match backups::list(home)?.pop() {
+                    Some(x) => x.path(home),
+                    None => home.backup(), // the single backup older versions made
+                };
+                if !path.is_file() {
+                    return Err(Error::NotFound("No recent backups made; cannot rollback".into()));
+                } Self::load_backup(home, path, force, logger.hollow())?;
+                log!((logger.vital) RollBack("Successfully rolled back to last backup") as Log);
+                return Ok(());
+            },
+        };
+
+        let backup = backups::find(home, &target)?;
+        log!((logger) RollBack("Rolling back to backup '{}' (itver {})...", backup.id, backup.itver));
+
+        if home.archive().is_dir() {
+            let archive = Self::load(home, logger.hollow())?;
+            if archive.uid != backup.uid && !force {
+                return Err(Error::Conflict(format!("Cannot rollback to backup '{}' as it is a backup of a different archive (uids don't match); use `-f` to force it", backup.id)));
+            }
+
+            // Back up the current archive so the rollback itself can be undone
+            backups::snapshot(&archive, &format!("rollback to '{}'", backup.id), logger.hollow())?;
+        }
+
+        // Going back is the point, so the backup being older is expected
+        Self::load_backup(home, backup.path(home), true, logger.hollow())?;
+        backups::prune(home, backups::keep()?, logger.hollow())?;
+        log!((logger.vital) RollBack("Successfully rolled back to backup '{}' (itver {})", backup.id, backup.itver) as Log);
+        Ok(())
     }
 
     /// Backs up home archive to specified path
-    pub fn backup(out_path: impl AsRef<Path>, mut logger: impl Logger) {
+    pub fn backup(home: &Home, out_path: impl AsRef<Path>, mut logger: impl Logger) -> crate::Result<()> {
         let out_path = out_path.as_ref();
-        let path = home_dir().join("archive");
+        let path = home.archive();
         let path_string = path.to_string_lossy();
         let out_string = out_path.to_string_lossy();
         
         log!((logger) Backup("Backing up archive '{path_string}' as '{out_string}'..."));
 
         if !path.is_dir() {
-            log!((logger.error) Backup("Archive does not exist, run `diary-cli init` to create a new one before you can back it up.") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound("Archive does not exist, run `diary-cli init` to create a new one before you can back it up.".into()));
         }
+        let _lock = lock::acquire(home, LockKind::Shared, logger.hollow())?;
 
-        let database = if_err!((logger) [Backup, err => ("While backing up archive: {err:?}")] retry LazyDB::load_dir(&path));
-        if_err!((logger) [Backup, err => ("While backing up archive: {err:?}")] retry database.compile(out_path));
+        let database = LazyDB::load_dir(&path).context("While backing up archive")?;
+        database.compile(out_path).context("While backing up archive")?;
         log!((logger.vital) Backup("Successfully backed up archive '{path_string}' as '{out_string}'") as Log);
         log!((logger) Backup(""));
+        Ok(())
     }
 
-    /// Loads a backup if that backup is the same as the active archive and or newer than the active archive, otherwise errors will be thrown
-    pub fn load_backup(path: impl AsRef<Path>, force: bool, mut logger: impl Logger) {
+    /// Loads a backup if that backup is the same as the active archive and or newer than the active archive, otherwise an error is returned
+    pub fn load_backup(home: &Home, path: impl AsRef<Path>, force: bool, mut logger: impl Logger) -> crate::Result<()> {
         let path = path.as_ref();
-        let archive = home_dir().join("archive");
+        let archive = home.archive();
         let archive_string = archive.to_string_lossy();
         let path_string = path.to_string_lossy();
 
         log!((logger) Backup("Loading archive backup '{path_string}'..."));
+        let _lock = lock::acquire(home, LockKind::Exclusive, logger.hollow())?;
 
         // Check if backup exists
         if !path.is_file() {
-            log!((logger.error) Backup("Backup file '{path_string}' does not exist") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound(format!("Backup file '{path_string}' does not exist")));
         }
 
         // Check if archive already exists
         if archive.is_dir() {
             log!((logger.vital) Backup("Detected that there is already a loaded archive at '{archive_string}'") as Inconvenience);
-            let old = Archive::load(logger.hollow()); // Loads old archive
+            let old = Archive::load(home, logger.hollow())?; // Loads old archive
 
             if force {
                 log!((logger.vital) Backup("Forcefully loading backup; this may result in archive data loss") as Warning);
             }
 
             // Load new archive
-            let new = home_dir().join("new");
-            if_err!((logger) [Backup, err => ("While decompiling backup '{path_string}': {err:?}")] retry LazyDB::decompile(path, &new));
-            let new = Archive::load_dir(new, logger.hollow());
-
-            let _ = std::fs::remove_dir_all(new.database.path()); // cleanup
+            let new = home.scratch();
+            LazyDB::decompile(path, &new).with_context(|| format!("While decompiling backup '{path_string}'"))?;
+            let new = Archive::load_dir(home, new, logger.hollow());
+            let _ = std::fs::remove_dir_all(home.scratch()); // cleanup
+            let new = new?;
 
             // Check if uid is the same and that the itver is higher
             if new.uid != old.uid && !force {
-                log!((logger.error) Backup("Cannot load backup as it is a backup of a different archive (uids don't match)") as Fatal);
-                log!((logger.vital) Backup("If you still want to load it (deleting your current archive in the process) then run the same command but with `-f` to force it.") as Warning);
-                return logger.crash();
+                return Err(Error::Conflict("Cannot load backup as it is a backup of a different archive (uids don't match); use `-f` to force it (deleting your current archive in the process)".into()));
             }
 
             if old.itver == new.itver && !force {
@@ -160,103 +294,199 @@ impl Archive {
             }
 
             if old.itver > new.itver && !force {
-                log!((logger.error) Backup("Cannot load backup as it is older than the currently loaded archive (itver is less)") as Fatal);
-                log!((logger.vital) Backup("If you still want to load it (losing un-backed changes in the process) then run the same command but with `-f` to force it.") as Warning);
-                return logger.crash();
+                return Err(Error::Conflict("Cannot load backup as it is older than the currently loaded archive (itver is less); use `-f` to force it (losing un-backed changes in the process)".into()));
             }
             
             let _ = std::fs::remove_dir_all(&archive); // cleanup
         }
 
-        if_err!((logger) [Backup, err => ("While decompiling backup '{path_string}': {err:?}")] retry LazyDB::decompile(path, &archive));
+        LazyDB::decompile(path, &archive).with_context(|| format!("While decompiling backup '{path_string}'"))?;
         log!((logger.vital) Backup("Successfully loaded backup '{path_string}'") as Log);
+        Ok(())
     }
 
     /// Wipes the specified archive and asks the user for confirmation
-    pub fn wipe(self, mut logger: impl Logger) {
+    pub fn wipe(self, mut logger: impl Logger) -> crate::Result<()> {
         // Confirm with the user about the action
         let expected = "I, as the user, confirm that I fully understand that I am wiping my ENTIRE archive and that this action is permanent and irreversible";
         log!((logger.vital) Wipe("To confirm with wiping your ENTIRE archive PERMANENTLY enter the phrase below (without quotes):") as Log);
-        if_err!((logger) [Wipe, err => ("Entered phrase incorrect, please retry")] retry {
+        let mut confirmed = false;
+        for _ in 0..3 {
             log!((logger.vital) Wipe("\"{expected}\"") as Log);
             let input = logger.ask("Wipe", "Enter the phrase");
-            if &input[0..input.len() - 1] != expected { Err(()) }
-            else { Ok(()) }
-        });
+            if input.trim_end_matches(['\n', '\r']) == expected {
+                confirmed = true;
+                break;
+            }
+            log!((logger.vital) Wipe("Entered phrase incorrect, please retry") as Warning);
+        } if !confirmed {
+            return Err(Error::Aborted("Entered phrase incorrect; not wiping archive".into()));
+        }
 
         log!((logger) Wipe("Wiping archive..."));
 
-        let path = home_dir().join("archive");
+        let path = self.home.archive();
         // Check if path exists
         if !path.exists() {
             log!((logger.vital) Wipe("Archive '{}' doesn't exist; doing nothing", path.to_string_lossy()) as Inconvenience);
-            return;
+            return Ok(());
         }
 
         // Wipe archive
-        if_err!((logger) [Wipe, err => ("While wiping archive: {err:?}")] retry std::fs::remove_dir_all(&path));
+        std::fs::remove_dir_all(&path).context("While wiping archive")?;
         log!((logger.vital) Wipe("Successfully wiped archive! Run `diary-cli init` to init a new archive\n") as Log);
+        Ok(())
     }
 
-    pub fn commit(&self, config: impl AsRef<Path>, mut logger: impl Logger) {
+    pub fn commit(&self, config: impl AsRef<Path>, mut logger: impl Logger) -> crate::Result<()> {
         let config = config.as_ref();
-        let path = home_dir().join("archive");
+        let path = self.home.archive();
         let path_string = path.to_string_lossy();
 
         // Checks if path exists or not
         if !path.is_dir() {
-            log!((logger.error) Commit("Archive '{path_string}' doesn't exist! Run `diary-cli init` before you can commit") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound(format!("Archive '{path_string}' doesn't exist! Run `diary-cli init` before you can commit")));
         }
 
         // Check if entry path exists or not
         let config_string = config.to_string_lossy();
         if !config.is_file() {
-            log!((logger.error) Commit("Entry config file '{config_string}' doesn't exist") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound(format!("Entry config file '{config_string}' doesn't exist")));
         }
         
-        // Backup archive before modification
-        let _ = std::fs::remove_file(home_dir().join("backup.ldb")); // Clean up
-        Self::backup(home_dir().join("backup.ldb"), logger.hollow());
-
         // Parse toml
         log!((logger) Commit("Parsing toml at '{}'", config.to_string_lossy()));
-        let entry = if_err!((logger) [Commit, err => ("While reading the entry config file: {err:?}")] retry std::fs::read_to_string(config));
-        let entry = if_err!((logger) [Commit, err => ("While parsing entry config toml: {err:?}")] {entry.parse::<toml::Table>()} crash {
-            log!((logger.error) Commit("{err:#?}") as Fatal);
-            logger.crash()
-        });
+        let entry = std::fs::read_to_string(config).context("While reading the entry config file")?;
+        let entry = entry.parse::<toml::Table>()
+            .map_err(|err| Error::Parse { path: config_string.to_string(), message: err.to_string() })?;
 
-        
+        self.commit_table(entry, &config_string, &format!("commit '{config_string}'"), logger)
+    }
+
+    /// Commits an already parsed entry or moc config (`config_string` naming where it came from) and records it as a new revision
+    pub fn commit_table(&self, entry: toml::Table, config_string: &str, operation: &str, mut logger: impl Logger) -> crate::Result<()> {
+        // Backup archive before modification
+        backups::create(self, operation, logger.hollow())?;
+
+        // Stage the commit so a failure part-way through doesn't leave a half-written entry behind
+        transaction::run(self, logger.hollow(), |archive| archive.write_table(entry, config_string, logger.hollow()))?;
+
+        log!((logger.vital) Commit("Successfully commited config to archive") as Log);
+        Ok(())
+    }
+
+    /// Writes an entry or moc config into the archive as is, without backing it up or journaling it (see `transaction::run`)
+    pub fn write_table(&self, entry: toml::Table, config_string: &str, mut logger: impl Logger) -> crate::Result<()> {
         // Checks if it is a moc
-        let is_moc = entry.get("is-moc")
-            .map(|x| unwrap_opt!((x.as_bool()) with logger, format: Commit("`is-moc` attribute of config file '{config_string}' must be boolean")))
-            .unwrap_or(false);
+        let is_moc = Self::is_moc(&entry, config_string)?;
         
+        // Remove the old version of the item from the index (if it's being re-committed)
+        let old_uid = entry.get(if is_moc { "moc" } else { "entry" })
+            .and_then(|x| x.get("uid"))
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string());
+        if let Some(uid) = old_uid {
+            self.touch(format!("{}/{uid}", if is_moc { "mocs" } else { "entries" }))?;
+            if is_moc && self.database_exists(format!("mocs/{uid}")) {
+                let mut moc = self.get_moc(uid.clone())?;
+                // Items committed before revisions were kept get their current version recorded first
+                if history::count(self, &uid, true)? == 0 {
+                    history::record(self, &uid, true, self.itver, moc.pull()?)?;
+                }
+                index::remove(self, &mut moc, true, logger.hollow())?;
+            } else if !is_moc && self.database_exists(format!("entries/{uid}")) {
+                let mut entry = self.get_entry(uid.clone())?;
+                if history::count(self, &uid, false)? == 0 {
+                    history::record(self, &uid, false, self.itver, entry.pull(Path::new("."), true)?)?;
+                }
+                index::remove(self, &mut entry, false, logger.hollow())?;
+            }
+        }
+
         if is_moc {
-            let container = if_err!((logger) [Commit, err => ("While loading archive as container: {err:?}")] retry search_database!((self.database) /mocs/));
+            let container = search_database!((self.database) /mocs/).context("While loading archive as container")?;
             log!((logger) Commit("Detected that config file '{config_string}' is an moc (map of contents)"));
-            MOC::new(entry, &config_string, container, logger.hollow());
+            let mut moc = MOC::new(entry, config_string, container, logger.hollow())?;
+            index::insert(self, &mut moc, true, logger.hollow())?;
+            log!((logger) Commit("Recording moc's revision..."));
+            let revision = moc.pull()?;
+            history::record(self, &moc.uid, true, self.next_itver()?, revision)?;
         } else {
-            let container = if_err!((logger) [Commit, err => ("While loading archive as container: {err:?}")] retry search_database!((self.database) /entries/));
+            let container = search_database!((self.database) /entries/).context("While loading archive as container")?;
             log!((logger) Commit("Detected that config file '{config_string}' is an entry"));
             
             // Add to unsorted list
-            let entry = Entry::new(entry, &config_string, container, logger.hollow());
+            let mut entry = Entry::new(entry, config_string, container, logger.hollow())?;
+            index::insert(self, &mut entry, false, logger.hollow())?;
+            log!((logger) Commit("Recording entry's revision..."));
+            let revision = entry.pull(Path::new("."), true)?;
+            history::record(self, &entry.uid, false, self.next_itver()?, revision)?;
             log!((logger) Commit("Adding entry to unsorted stack..."));
-            list::push(
-                |file| LazyData::new_string(file, &entry.uid),
-                &if_err!((logger) [Commit, err => ("While loaded unsorted stack: {err:?}")] retry search_database!((self.database) /order/unsorted)),
-                logger.hollow(),
-            );
+            self.touch("order/unsorted")?;
+            list::push_string(
+                &entry.uid,
+                &search_database!((self.database) /order/unsorted).context("While loading unsorted stack")?,
+            )?;
         }
 
         // Update itver
         log!((logger) Commit("Updating archive itver..."));
-        if_err!((logger) [Commit, err => ("While update archive itver: {err:?}")] retry write_database!((self.database) itver = new_u16(self.itver + 1)));
+        let itver = self.next_itver()?;
+        self.touch("itver")?;
+        write_database!((self.database) itver = new_u32(itver)).context("While updating archive itver")?;
+        Ok(())
+    }
 
-        log!((logger.vital) Commit("Successfully commited config to archive") as Log);
+    /// Checks if an entry or moc config is valid by parsing it into a scratch database (leaving the archive untouched); returns if it is a moc
+    pub fn validate(config: toml::Table, config_string: &str, logger: impl Logger) -> crate::Result<bool> {
+        let is_moc = Self::is_moc(&config, config_string)?;
+        let scratch = std::env::temp_dir().join(format!("diary-cli-validate-{}-{}", std::process::id(), {
+            use std::collections::hash_map::RandomState;
+            use std::hash::{BuildHasher, Hasher};
+            RandomState::new().build_hasher().finish()
+        }));
+
+        let result = LazyDB::init(&scratch)
+            .context("While initialising scratch database")
+            .and_then(|database| {
+                let container = database.as_container().context("While loading scratch database")?;
+                if is_moc {
+                    MOC::new(config, config_string, container, logger).map(|_| ())
+                } else {
+                    Entry::new(config, config_string, container, logger).map(|_| ())
+                }
+            });
+        let _ = fs::remove_dir_all(&scratch); // cleanup
+        result.map(|_| is_moc)
+    }
+
+    /// Returns if a config is of a moc, along with its uid (if it has one)
+    pub fn uid_of(config: &toml::Table) -> Option<(bool, String)> {
+        let is_moc = config.get("is-moc").and_then(|x| x.as_bool()).unwrap_or(false);
+        let uid = config.get(if is_moc { "moc" } else { "entry" })?.get("uid")?.as_str()?;
+        Some((is_moc, uid.to_string()))
+    }
+
+    fn is_moc(config: &toml::Table, config_string: &str) -> crate::Result<bool> {
+        Ok(match config.get("is-moc") {
+            Some(x) => unwrap_opt!((x.as_bool()) else Validation("`is-moc` attribute of config file '{config_string}' must be boolean")),
+            None => false,
+        })
+    }
+
+    /// The itver the archive will have after its next modification
+    pub fn next_itver(&self) -> crate::Result<u32> {
+        self.itver.checked_add(1).ok_or_else(|| Error::Overflow(format!("The archive's itver can't go past {}; back it up, then wipe it and load its entries into a new one", u32::MAX)))
+    }
+
+    #[inline]
+    pub fn uid(&self) -> u64 {
+        self.uid
+    }
+
+    #[inline]
+    pub fn home(&self) -> &Home {
+        &self.home
     }
 
     #[inline]
@@ -269,75 +499,62 @@ impl Archive {
         self.database().path().join(path).exists()
     }
 
-    pub fn get_entry(&self, uid: String, mut logger: impl Logger) -> Option<Entry> {
+    pub fn get_entry(&self, uid: String) -> crate::Result<Entry> {
         if !self.database_exists(format!("entries/{uid}")) {
-            log!((logger.error) Archive("Entry of uid `{uid}` does not exist") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound(format!("Entry of uid `{uid}` does not exist")));
         }
 
-        match search_database!((self.database) /entries/(&uid)) {
-            Ok(x) => Some(Entry::load_lazy(uid, x)),
-            Err(err) => match err {
-                LDBError::DirNotFound(..) => None,
-                _ => {
-                    log!((logger.error) Archive("While getting entry '{uid}': {err:?}") as Fatal);
-                    logger.crash()
-                }
-            }
-        }
+        let container = search_database!((self.database) /entries/(&uid)).with_context(|| format!("While getting entry '{uid}'"))?;
+        Ok(Entry::load_lazy(uid, container))
     }
 
-    pub fn get_moc(&self, uid: String, mut logger: impl Logger) -> Option<MOC> {
+    pub fn get_moc(&self, uid: String) -> crate::Result<MOC> {
         if !self.database_exists(format!("mocs/{uid}")) {
-            log!((logger.error) Archive("Moc of uid `{uid}` does not exist") as Fatal);
-            return logger.crash();
+            return Err(Error::NotFound(format!("Moc of uid `{uid}` does not exist")));
         }
 
-        match search_database!((self.database) /mocs/(&uid)) {
-            Ok(x) => Some(MOC::load_lazy(uid, x)),
-            Err(err) => match err {
-                LDBError::DirNotFound(..) => None,
-                _ => {
-                    log!((logger.error) Archive("While getting moc '{uid}': {err:?}") as Fatal);
-                    logger.crash()
-                }
-            }
-        }
+        let container = search_database!((self.database) /mocs/(&uid)).with_context(|| format!("While getting moc '{uid}'"))?;
+        Ok(MOC::load_lazy(uid, container))
     }
 
-    pub fn list_entries(&self, mut logger: impl Logger) -> Vec<Entry> {
+    pub fn list_entries(&self, mut logger: impl Logger) -> crate::Result<Vec<Entry>> {
         let path = self.database.path().join("entries");
 
         if !path.is_dir() {
             log!((logger.vital) Entries("Path '{}' does not exist; doing nothing", path.to_string_lossy()) as Inconvenience);
-            return Vec::with_capacity(0);
+            return Ok(Vec::with_capacity(0));
         }
 
-        let mut logger1 = logger.hollow();
-        let logger2 = logger.hollow();
-        let dir = if_err!((logger) [Entries, err => ("While reading directory {}'s contents: {err:?}", path.to_string_lossy())] retry fs::read_dir(&path));
-        dir.into_iter()
-            .map(|x| if_err!((logger) [Entries, err => ("While reading dir element: {err:?}")] {x} crash logger.crash()))
-            .filter(|x| if_err!((logger1) [Entries, err => ("While reading dir element: {err:?}")] {x.file_type()} crash logger1.crash()).is_dir())
-            .map(|x| self.get_entry(x.file_name().to_string_lossy().to_string(), logger2.hollow()).unwrap())
+        list_dirs(&path)?
+            .into_iter()
+            .map(|x| self.get_entry(x))
             .collect()
     }
 
-    pub fn list_mocs(&self, mut logger: impl Logger) -> Vec<MOC> {
+    pub fn list_mocs(&self, mut logger: impl Logger) -> crate::Result<Vec<MOC>> {
         let path = self.database.path().join("mocs");
 
         if !path.is_dir() {
             log!((logger.vital) MOCs("Path '{}' does not exist; doing nothing", path.to_string_lossy()) as Inconvenience);
-            return Vec::with_capacity(0);
+            return Ok(Vec::with_capacity(0));
         }
 
-        let mut logger1 = logger.hollow();
-        let logger2 = logger.hollow();
-        let dir = if_err!((logger) [MOCs, err => ("While reading directory {}'s contents: {err:?}", path.to_string_lossy())] retry fs::read_dir(&path));
-        dir.into_iter()
-            .map(|x| if_err!((logger) [MOCs, err => ("While reading dir element: {err:?}")] {x} crash logger.crash()))
-            .filter(|x| if_err!((logger1) [MOCs, err => ("While reading dir element: {err:?}")] {x.file_type()} crash logger1.crash()).is_dir())
-            .map(|x| self.get_moc(x.file_name().to_string_lossy().to_string(), logger2.hollow()).unwrap())
+        list_dirs(&path)?
+            .into_iter()
+            .map(|x| self.get_moc(x))
             .collect()
     }
-}
\ No newline at end of file
+}
+
+/// Returns the names of the directories within a directory
+pub(crate) fn list_dirs(path: &Path) -> crate::Result<Vec<String>> {
+    let dir = fs::read_dir(path).with_context(|| format!("While reading directory {}'s contents", path.to_string_lossy()))?;
+    let mut names = Vec::new();
+    for x in dir {
+        let x = x.context("While reading dir element")?;
+        if x.file_type().context("While reading dir element")?.is_dir() {
+            names.push(x.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, FixedOffset, Local};
+use soulog::*;
+use toml::Table;
+use crate::{archive::Archive, lock::{self, LockKind}, Context, Error, Home, Result};
+
+/// The environment variable that sets how many backups are kept (`0` keeps all of them)
+pub const KEEP_VAR: &str = "DIARY_CLI_BACKUPS";
+/// How many backups are kept if `$DIARY_CLI_BACKUPS` isn't set
+pub const DEFAULT_KEEP: usize = 20;
+
+/// A timestamped backup of an archive, made before each modification of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub id: String,
+    /// The itver of the archive when it was backed up
+    pub itver: u32,
+    /// The uid of the archive it is a backup of
+    pub uid: u64,
+    pub time: DateTime<FixedOffset>,
+    /// The operation that triggered the backup, eg `commit 'entry.toml'`
+    pub operation: String,
+}
+
+impl Backup {
+    /// The compiled archive of the backup
+    #[inline]
+    pub fn path(&self, home: &Home) -> PathBuf {
+        home.backups().join(&self.id).with_extension("ldb")
+    }
+
+    #[inline]
+    fn meta_path(&self, home: &Home) -> PathBuf {
+        home.backups().join(&self.id).with_extension("toml")
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let path_string = path.to_string_lossy();
+        let id = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let table = fs::read_to_string(path).with_context(|| format!("While reading backup metadata '{path_string}'"))?
+            .parse::<Table>()
+            .map_err(|err| Error::Parse { path: path_string.to_string(), message: err.to_string() })?;
+
+        let invalid = |key: &str| Error::Validation(format!("Backup metadata '{path_string}' must have a valid '{key}' attribute"));
+        let itver = table.get("itver").and_then(|x| x.as_integer()).and_then(|x| u32::try_from(x).ok()).ok_or_else(|| invalid("itver"))?;
+        let uid = table.get("uid").and_then(|x| x.as_str()).and_then(|x| x.parse().ok()).ok_or_else(|| invalid("uid"))?;
+        let time = table.get("time").and_then(|x| x.as_str()).and_then(|x| DateTime::parse_from_rfc3339(x).ok()).ok_or_else(|| invalid("time"))?;
+        let operation = table.get("operation").and_then(|x| x.as_str()).ok_or_else(|| invalid("operation"))?.to_string();
+
+        Ok(Self { id, itver, uid, time, operation })
+    }
+
+    fn write(&self, home: &Home) -> Result<()> {
+        let mut table = Table::new();
+        table.insert("itver".into(), (self.itver as i64).into());
+        table.insert("uid".into(), self.uid.to_string().into()); // toml integers are only 63 bits
+        table.insert("time".into(), self.time.to_rfc3339().into());
+        table.insert("operation".into(), self.operation.clone().into());
+
+        let contents = toml::to_string_pretty(&table)
+            .map_err(|err| Error::Validation(format!("While encoding backup metadata: {err}")))?;
+        fs::write(self.meta_path(home), contents).context("While writing backup metadata")
+    }
+
+    fn remove(&self, home: &Home) -> Result<()> {
+        fs::remove_file(self.path(home)).with_context(|| format!("While removing backup '{}'", self.id))?;
+        fs::remove_file(self.meta_path(home)).with_context(|| format!("While removing backup '{}'s metadata", self.id))
+    }
+}
+
+/// How many backups to keep, from `$DIARY_CLI_BACKUPS`
+pub fn keep() -> Result<usize> {
+    match std::env::var(KEEP_VAR) {
+        Ok(x) if !x.is_empty() => x.parse()
+            .map_err(|_| Error::Validation(format!("`${KEEP_VAR}` must be a number of backups to keep, not '{x}'"))),
+        _ => Ok(DEFAULT_KEEP),
+    }
+}
+
+/// Backs up the archive into its diary's backup history and prunes the history down to `keep()`
+pub fn create(archive: &Archive, operation: &str, logger: impl Logger) -> Result<Backup> {
+    let keep = keep()?;
+    let backup = snapshot(archive, operation, logger.hollow())?;
+    prune(archive.home(), keep, logger)?;
+    Ok(backup)
+}
+
+/// Backs up the archive into its diary's backup history without pruning it
+pub fn snapshot(archive: &Archive, operation: &str, mut logger: impl Logger) -> Result<Backup> {
+    let home = archive.home();
+    fs::create_dir_all(home.backups()).context("While creating the backups directory")?;
+
+    // Ids are timestamps, with a suffix for backups made within the same second
+    let time = Local::now().fixed_offset();
+    let base = time.format("%Y%m%d-%H%M%S").to_string();
+    let mut id = base.clone();
+    let mut i = 1;
+    while home.backups().join(&id).with_extension("toml").exists() {
+        id = format!("{base}-{i}");
+        i += 1;
+    }
+
+    let backup = Backup {
+        id,
+        itver: archive.itver,
+        uid: archive.uid(),
+        time,
+        operation: operation.to_string(),
+    };
+
+    log!((logger) Backup("Backing up archive as backup '{}' before {operation}...", backup.id));
+    Archive::backup(home, backup.path(home), logger.hollow())?;
+    backup.write(home)?;
+    Ok(backup)
+}
+
+/// Lists the backups of the home's diary from oldest to newest
+pub fn list(home: &Home) -> Result<Vec<Backup>> {
+    let path = home.backups();
+    if !path.is_dir() { return Ok(Vec::with_capacity(0)); }
+
+    let mut backups = Vec::new();
+    for x in fs::read_dir(&path).context("While reading the backups directory")? {
+        let x = x.context("While reading the backups directory")?.path();
+        if x.extension().is_some_and(|x| x == "toml") {
+            backups.push(Backup::read(&x)?);
+        }
+    }
+
+    backups.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.id.len().cmp(&b.id.len())).then_with(|| a.id.cmp(&b.id)));
+    Ok(backups)
+}
+
+/// Finds a backup by its id, or the newest backup made at an itver
+pub fn find(home: &Home, target: &str) -> Result<Backup> {
+    let backups = list(home)?;
+    if let Some(x) = backups.iter().find(|x| x.id == target) {
+        return Ok(x.clone());
+    }
+
+    if let Ok(itver) = target.parse::<u32>() {
+        if let Some(x) = backups.into_iter().rev().find(|x| x.itver == itver) {
+            return Ok(x);
+        }
+    }
+
+    Err(Error::NotFound(format!("No backup of id or itver '{target}' found; run `diary-cli backups list` to see the available backups")))
+}
+
+/// Removes all but the newest `keep` backups (`0` keeps all of them), returning how many were removed
+pub fn prune(home: &Home, keep: usize, mut logger: impl Logger) -> Result<usize> {
+    let backups = list(home)?;
+    if keep == 0 || backups.len() <= keep { return Ok(0); }
+
+    let removed = backups.len() - keep;
+    for backup in &backups[..removed] {
+        log!((logger) Backups("Pruning backup '{}'...", backup.id));
+        backup.remove(home)?;
+    }
+    Ok(removed)
+}
+
+pub fn list_command(home: &Home, mut logger: impl Logger) -> Result<()> {
+    let backups = list(home)?;
+    if backups.is_empty() {
+        log!((logger.vital) Backups("No backups have been made yet") as Log);
+        return Ok(());
+    }
+
+    for x in backups.iter() {
+        log!((logger.vital) Backups("{}", colour_format![
+            cyan(&x.id), blue(" | "), green("itver "), none(&x.itver.to_string()), blue(" | "),
+            none(&x.time.format("%Y-%m-%d %H:%M:%S").to_string()), blue(" | "), none(&x.operation)
+        ]) as Log);
+    }
+    Ok(())
+}
+
+pub fn prune_command(home: &Home, keep: usize, mut logger: impl Logger) -> Result<()> {
+    let _lock = lock::acquire(home, LockKind::Exclusive, logger.hollow())?; // writers back up as they go
+    let removed = prune(home, keep, logger.hollow())?;
+    log!((logger.vital) Backups("Pruned {removed} backup(s)") as Log);
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use soulog::*;
+use toml::{Table, Value};
+use crate::{archive::Archive, index::{self, Group}, Error, Home, Result};
+
+/// A line and column (both starting at 1) in a config file
+pub type Location = Option<(usize, usize)>;
+/// A moc collection's index, location and included tags
+type Include = (usize, Location, Vec<String>);
+
+/// Something wrong with a config file, found by `check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub path: String,
+    /// Where the problem is, if it is anywhere in particular
+    pub location: Location,
+    pub message: String,
+    /// Warnings don't stop a config from being committed
+    pub warning: bool,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Finds where tables and their keys are in a config's source, as toml tables don't keep their spans
+///
+/// Tables are named like `entry` or `section.0` (the first `[[section]]`) and the root table is `""`
+#[derive(Debug, Default)]
+struct Locator {
+    keys: HashMap<(String, String), (usize, usize)>,
+}
+
+impl Locator {
+    fn new(source: &str) -> Self {
+        let mut this = Self::default();
+        let mut table = String::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut multiline: Option<&str> = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let column = line.len() - trimmed.len() + 1;
+
+            // Skip the insides of multi-line strings
+            if let Some(quotes) = multiline {
+                if line.matches(quotes).count() % 2 == 1 { multiline = None; }
+                continue;
+            }
+
+            if let Some(name) = trimmed.strip_prefix("[[").and_then(|x| x.split("]]").next()) {
+                let name = name.trim().to_string();
+                let count = counts.entry(name.clone()).or_insert(0);
+                table = format!("{name}.{count}");
+                *count += 1;
+                this.keys.entry((table.clone(), String::new())).or_insert((i + 1, column));
+            } else if let Some(name) = trimmed.strip_prefix('[').and_then(|x| x.split(']').next()) {
+                table = name.trim().to_string();
+                this.keys.entry((table.clone(), String::new())).or_insert((i + 1, column));
+            } else if let Some((key, value)) = trimmed.split_once('=') {
+                let key = key.trim().trim_matches(|x| x == '"' || x == '\'').to_string();
+                this.keys.entry((table.clone(), key)).or_insert((i + 1, column));
+                multiline = ["\"\"\"", "'''"].into_iter().find(|x| value.matches(x).count() % 2 == 1);
+            }
+        }
+
+        this
+    }
+
+    /// The location of a key, falling back to its table's header
+    fn find(&self, table: &str, key: &str) -> Location {
+        self.keys.get(&(table.to_string(), key.to_string()))
+            .or_else(|| self.keys.get(&(table.to_string(), String::new())))
+            .copied()
+    }
+}
+
+/// Turns a byte offset into a line and column (both starting at 1)
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+/// What cross-file checks need to know about a checked entry or moc
+#[derive(Debug, Clone)]
+struct Item {
+    uid: String,
+    is_moc: bool,
+    location: Location,
+    tags: Vec<String>,
+    /// The tags included by each collection (if it's a moc), along with its location
+    includes: Vec<Include>,
+}
+
+/// Checks a single config, collecting every problem instead of stopping at the first
+struct Checker<'a> {
+    path: &'a str,
+    locator: Locator,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn problem(&mut self, table: &str, key: &str, message: String, warning: bool) {
+        let location = self.locator.find(table, key);
+        self.problems.push(Problem { path: self.path.to_string(), location, message, warning });
+    }
+
+    #[inline]
+    fn error(&mut self, table: &str, key: &str, message: String) {
+        self.problem(table, key, message, false)
+    }
+
+    fn get<'t>(&mut self, name: &str, table_name: &str, table: &'t Table, key: &str, required: bool) -> Option<&'t Value> {
+        let value = table.get(key);
+        if value.is_none() && required {
+            self.error(table_name, "", format!("{name} must have a '{key}' attribute"));
+        } value
+    }
+
+    fn string<'t>(&mut self, name: &str, table_name: &str, table: &'t Table, key: &str, required: bool) -> Option<&'t str> {
+        let value = self.get(name, table_name, table, key, required)?;
+        let string = value.as_str();
+        if string.is_none() {
+            self.error(table_name, key, format!("{name}'s '{key}' attribute must be a string, not {}", value.type_str()));
+        } string
+    }
+
+    fn boolean(&mut self, name: &str, table_name: &str, table: &Table, key: &str) -> Option<bool> {
+        let value = self.get(name, table_name, table, key, false)?;
+        let boolean = value.as_bool();
+        if boolean.is_none() {
+            self.error(table_name, key, format!("{name}'s '{key}' attribute must be a boolean, not {}", value.type_str()));
+        } boolean
+    }
+
+    fn strings(&mut self, name: &str, table_name: &str, table: &Table, key: &str, required: bool) -> Option<Vec<String>> {
+        let value = self.get(name, table_name, table, key, required)?;
+        let Some(array) = value.as_array() else {
+            self.error(table_name, key, format!("{name}'s '{key}' attribute must be an array of strings, not {}", value.type_str()));
+            return None;
+        };
+
+        let strings: Vec<String> = array.iter().filter_map(|x| x.as_str()).map(|x| x.to_string()).collect();
+        if strings.len() != array.len() {
+            self.error(table_name, key, format!("All of {name}'s '{key}' must be strings"));
+            return None;
+        } Some(strings)
+    }
+
+    fn tables<'t>(&mut self, name: &str, table: &'t Table, key: &str) -> Vec<(usize, &'t Table)> {
+        let Some(value) = self.get(name, "", table, key, true) else { return Vec::new() };
+        let Some(array) = value.as_array() else {
+            self.error("", key, format!("{name}'s '{key}' attribute must be an array of tables (`[[{key}]]`), not {}", value.type_str()));
+            return Vec::new();
+        };
+
+        let mut tables = Vec::with_capacity(array.len());
+        for (i, x) in array.iter().enumerate() {
+            match x.as_table() {
+                Some(x) => tables.push((i, x)),
+                None => self.error(&format!("{key}.{i}"), "", format!("{name}'s {key} {i} must be a table")),
+            }
+        } tables
+    }
+
+    fn check(&mut self, config: &Table) -> Option<Item> {
+        let is_moc = match config.get("is-moc") {
+            Some(x) => match x.as_bool() {
+                Some(x) => x,
+                None => { self.error("", "is-moc", "`is-moc` attribute must be boolean".into()); return None },
+            },
+            None => false,
+        };
+        let key = if is_moc { "moc" } else { "entry" };
+
+        let item = match self.get(key, "", config, key, true) {
+            Some(Value::Table(x)) => Some(x),
+            Some(x) => { self.error("", key, format!("'{key}' attribute must be a table (`[{key}]`), not {}", x.type_str())); None },
+            None => None,
+        };
+
+        let mut result = None;
+        if let Some(item) = item {
+            let uid = self.string(key, key, item, "uid", true);
+            if uid.is_some_and(|x| x.is_empty() || x.contains(['/', '\\'])) {
+                self.error(key, "uid", format!("{key}'s uid '{}' must not be empty or contain slashes", uid.unwrap_or_default()));
+            }
+            self.string(key, key, item, "title", true);
+            self.string(key, key, item, "description", true);
+            self.strings(key, key, item, "notes", false);
+            let tags = self.strings(key, key, item, "tags", true);
+            self.boolean(key, key, item, "private");
+            if !is_moc { self.date(item); }
+
+            result = uid.map(|uid| Item {
+                uid: uid.to_string(),
+                is_moc,
+                location: self.locator.find(key, "uid"),
+                tags: tags.unwrap_or_default(),
+                includes: Vec::new(),
+            });
+        }
+
+        if is_moc {
+            let includes = self.collections(config);
+            if let Some(x) = &mut result { x.includes = includes; }
+        } else {
+            self.sections(config);
+        }
+
+        result
+    }
+
+    fn date(&mut self, entry: &Table) {
+        let Some(value) = self.get("entry", "entry", entry, "date", true) else { return };
+        let Some(datetime) = value.as_datetime() else {
+            self.error("entry", "date", format!("entry's 'date' attribute must be a date, not {}", value.type_str()));
+            return;
+        };
+
+        match datetime.date {
+            Some(x) if NaiveDate::from_ymd_opt(x.year as i32, x.month as u32, x.day as u32).is_none() =>
+                self.error("entry", "date", format!("entry's date '{x}' isn't a valid date")),
+            Some(_) => (),
+            None => self.error("entry", "date", format!("entry's date '{datetime}' must contain a date")),
+        }
+    }
+
+    fn sections(&mut self, config: &Table) {
+        for (i, section) in self.tables("entry", config, "section") {
+            let table_name = format!("section.{i}");
+            let name = format!("section {i}");
+            self.string(&name, &table_name, section, "title", true);
+            self.strings(&name, &table_name, section, "notes", false);
+            self.boolean(&name, &table_name, section, "private");
+
+            match section.get("path") {
+                Some(_) => if let Some(path) = self.string(&name, &table_name, section, "path", true) {
+                    if !Path::new(path).is_file() {
+                        self.error(&table_name, "path", format!("{name}'s path '{path}' does not exist"));
+                    }
+                },
+                None => { self.string(&name, &table_name, section, "contents", true); },
+            }
+        }
+    }
+
+    fn collections(&mut self, config: &Table) -> Vec<Include> {
+        let mut includes = Vec::new();
+        for (i, collection) in self.tables("moc", config, "collection") {
+            let table_name = format!("collection.{i}");
+            let name = format!("collection {i}");
+            self.string(&name, &table_name, collection, "title", true);
+            self.strings(&name, &table_name, collection, "notes", false);
+            let include = self.strings(&name, &table_name, collection, "include", false);
+
+            let query = self.string(&name, &table_name, collection, "query", false);
+            if let Some(Err(err)) = query.map(crate::query::parse) {
+                self.error(&table_name, "query", format!("{name}'s query: {err}"));
+            }
+            if let Some(Err(err)) = self.string(&name, &table_name, collection, "diary", false).map(crate::diaries::validate_name) {
+                self.error(&table_name, "diary", format!("{name}'s diary: {err}"));
+            }
+
+            match include {
+                Some(x) if !x.is_empty() => includes.push((i, self.locator.find(&table_name, "include"), x)),
+                _ if collection.contains_key("query") => (),
+                Some(_) | None => self.error(&table_name, "", format!("{name} must have an 'include' or 'query' attribute")),
+            }
+        }
+        includes
+    }
+}
+
+/// Checks a single config file's source, returning its problems and (if it has a uid) what cross-file checks need
+fn check_source(path: &str, source: &str) -> (Vec<Problem>, Option<Item>) {
+    let config = match source.parse::<Table>() {
+        Ok(x) => x,
+        Err(err) => return (vec![Problem {
+            path: path.to_string(),
+            location: err.span().map(|x| line_column(source, x.start)),
+            message: err.message().trim().replace('\n', "; "),
+            warning: false,
+        }], None),
+    };
+
+    let mut checker = Checker { path, locator: Locator::new(source), problems: Vec::new() };
+    let item = checker.check(&config);
+    (checker.problems, item)
+}
+
+/// Checks config files without touching the archive, returning every problem found in them
+///
+/// The archive (if there is one) is only read, to see if collections include anything
+pub fn check(paths: &[String], archive: Option<&Archive>, logger: impl Logger) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let mut items: Vec<(String, Item)> = Vec::new();
+
+    for path in paths {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                let (x, item) = check_source(path, &source);
+                problems.extend(x);
+                if let Some(item) = item { items.push((path.clone(), item)); }
+            },
+            Err(err) => problems.push(Problem { path: path.clone(), location: None, message: format!("While reading config: {err}"), warning: false }),
+        }
+    }
+
+    // Duplicate uids (entries and mocs don't share uids)
+    let mut seen: HashMap<(bool, &str), (&str, Location)> = HashMap::new();
+    for (path, item) in items.iter() {
+        match seen.get(&(item.is_moc, item.uid.as_str())) {
+            Some((first, location)) => problems.push(Problem {
+                path: path.clone(),
+                location: item.location,
+                message: match location {
+                    Some((line, column)) => format!("uid '{}' is already used by '{first}:{line}:{column}'", item.uid),
+                    None => format!("uid '{}' is already used by '{first}'", item.uid),
+                },
+                warning: false,
+            }),
+            None => { seen.insert((item.is_moc, &item.uid), (path, item.location)); },
+        }
+    }
+
+    // Collection includes that match nothing in the checked configs or the archive
+    let indexed = archive.filter(|x| x.database_exists("index"));
+    for (path, item) in items.iter() {
+        for (i, location, include) in item.includes.iter() {
+            if items.iter().any(|(_, x)| include.iter().all(|tag| x.tags.contains(tag))) { continue }
+            if let Some(archive) = indexed {
+                if !index::search(archive, Group::Tags, include, true, false, logger.hollow())?.is_empty()
+                || !index::search(archive, Group::Tags, include, true, true, logger.hollow())?.is_empty() { continue }
+            }
+
+            problems.push(Problem {
+                path: path.clone(),
+                location: *location,
+                message: format!("collection {i}'s include {include:?} matches no entries or mocs"),
+                warning: true,
+            });
+        }
+    }
+
+    Ok(problems)
+}
+
+pub fn check_command(paths: Vec<String>, home: &Home, mut logger: impl Logger) -> Result<()> {
+    // Only read the archive if it exists, as loading a missing one initialises it
+    let archive = match home.archive().is_dir() {
+        true => Some(Archive::load_shared(home, logger.hollow())?),
+        false => None,
+    };
+
+    let problems = check(&paths, archive.as_ref(), logger.hollow())?;
+    for x in problems.iter() {
+        if x.warning {
+            log!((logger.vital) Check("{x}") as Warning);
+        } else {
+            log!((logger.error) Check("{x}") as Failure);
+        }
+    }
+
+    let errors = problems.iter().filter(|x| !x.warning).count();
+    if errors > 0 {
+        return Err(Error::Validation(format!("Found {errors} problem(s) in {} config file(s)", paths.len())));
+    }
+    log!((logger.vital) Check("All {} config file(s) are valid", paths.len()) as Log);
+    Ok(())
+}
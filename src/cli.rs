@@ -4,12 +4,19 @@ use crate::*;
 use soulog::*;
 
 pub static mut VERBOSE: bool = false;
+pub static mut WAIT: bool = false;
 
 #[derive(Parser)]
 #[command(author, version, about)]
 pub struct Cli {
     #[arg(short, long, help="Specifies if you want it to log everything it does")]
     pub verbose: bool,
+    #[arg(long, global=true, value_name="dir", help="The directory the archive and its backups are kept in (defaults to `$DIARY_CLI_HOME` or `~/.diary-cli`)")]
+    pub archive: Option<String>,
+    #[arg(short='D', long, global=true, value_name="name", help="The named diary to use (defaults to the `default` diary)")]
+    pub diary: Option<String>,
+    #[arg(long, global=true, help="Waits for other processes using the archive to finish instead of failing")]
+    pub wait: bool,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -19,13 +26,50 @@ pub enum Commands {
     #[command(about="A mere test command")]
     Test,
     #[command(about="Initialises a new archive")]
-    Init,
+    Init {
+        #[arg(short, long, help="Encrypts the archive (and its backups) with a passphrase; uids and the operations backups were made for (like the config paths committed) aren't encrypted")]
+        encrypted: bool,
+    },
     #[command(about="Wipes the archive")]
     Wipe,
-    #[command(about="Commit an entry into the archive")]
+    #[command(about="Commit entries and mocs into the archive (checking them all first, then backing up once)")]
     Commit {
-        #[arg(index=1, required=true, help="The path to the entry config toml file to commit.")]
-        file_path: String,
+        #[arg(index=1, required=true, num_args=1.., help="The paths (or glob patterns) of the entry and moc config toml files to commit.")]
+        file_paths: Vec<String>,
+        #[arg(short, long, help="Commits the config toml files within any folders given (and their subfolders), leaving out hidden ones like `.git`")]
+        recursive: bool,
+    },
+    #[command(about="Watches a folder of entry and moc configs, re-committing the ones that change (or whose section files do) as they're saved")]
+    Watch {
+        #[arg(index=1, required=true, help="The folder of config toml files to watch (including its subfolders)")]
+        dir: String,
+        #[arg(short, long, default_value_t=500, help="How long (in milliseconds) to wait for saves to settle before committing them")]
+        debounce: u64,
+    },
+    #[command(about="Checks entry and moc configs for problems without committing them")]
+    Check {
+        #[arg(index=1, required=true, num_args=1.., help="The paths to the entry and moc config toml files to check.")]
+        file_paths: Vec<String>,
+    },
+    #[command(about="Creates a new entry config from a template (and optionally commits it after editing it)")]
+    New {
+        #[arg(short, long, help="The name of the template to use from the archive's `templates` directory (defaults to `default`)")]
+        template: Option<String>,
+        #[arg(short='T', long, help="The title of the new entry (used for the `{title}` and `{slug}` placeholders)")]
+        title: Option<String>,
+        #[arg(short='u', long, value_name="pattern", help="The pattern of the new uid, eg `{yyyy}-{mm}-{dd}-{slug}`")]
+        pattern: Option<String>,
+        #[arg(short, long, default_value=".", help="Specifies the path of the containing folder of the new config file")]
+        path: String,
+        #[arg(short, long, help="Opens the config in `$EDITOR` and commits it once it's saved")]
+        edit: bool,
+    },
+    #[command(about="Edits an entry or moc in `$EDITOR` and commits it back if it changed")]
+    Edit {
+        #[arg(short='m', long, help="Determines if it is a moc or not")]
+        is_moc: bool,
+        #[arg(index=1, required=true, help="The uid of the entry or moc")]
+        uid: String,
     },
     #[command(about="Backs up the archive")]
     Backup {
@@ -43,6 +87,13 @@ pub enum Commands {
     Rollback {
         #[arg(short, long, help="Force loads a backup even if you may lose archive data.")]
         force: bool,
+        #[arg(long, value_name="id|itver", help="Rolls back to a specific backup (by id or the archive's itver) instead of the last one")]
+        to: Option<String>,
+    },
+    #[command(about="Lists and prunes the archive's backup history")]
+    Backups {
+        #[command(subcommand)]
+        command: Option<BackupsCommands>,
     },
     #[command(about="Returns the days since 2020 from a specified date")]
     Since {
@@ -70,22 +121,76 @@ pub enum Commands {
         tags: Option<Vec<String>>,
         #[arg(short, long, requires="tags", help="Sets if the search is strict or not (if the item must implement all tags)")]
         strict: bool,
+        #[arg(short, long, help="Filters out the list according to a query, eg `(travel or work) and not draft and date>=2023-01-01`")]
+        query: Option<String>,
         #[arg(short='e', long, help="Sets if you want to show entries")]
         show_entries: bool,
         #[arg(short='m', long, help="Sets if you want to show mocs")]
         show_mocs: bool,
     },
+    #[command(visible_alias="grep", about="Searches the text of entries and mocs for a pattern.")]
+    Find {
+        #[arg(index=1, required=true, help="The text (or regex with `-r`) to search for")]
+        pattern: String,
+        #[arg(short='r', long, help="Treats the pattern as a regular expression")]
+        regex: bool,
+        #[arg(short='w', long, help="Only matches whole words (uses the archive's word index)")]
+        word: bool,
+        #[arg(short='i', long, help="Matches the pattern case-insensitively")]
+        ignore_case: bool,
+        #[arg(short='e', long, help="Only searches entries")]
+        show_entries: bool,
+        #[arg(short='m', long, help="Only searches mocs")]
+        show_mocs: bool,
+    },
     #[command(about="Sorts the unsorted, committed, entries.")]
     Sort,
-    #[command(about="Exports the archive as an `Obsidian.md` vault.")]
+    #[command(about="Rebuilds the archive's tag and word index from scratch.")]
+    Reindex,
+    #[command(about="Checks the archive for missing attributes and inconsistent lists (and optionally repairs them).")]
+    Fsck {
+        #[arg(short, long, help="Repair the problems that are found (backing up the archive first).")]
+        repair: bool,
+    },
+    #[command(about="Migrates the archive to the current format version (which also happens the next time it's used).")]
+    Migrate {
+        #[arg(short='n', long, help="Shows what migrating would change without changing anything.")]
+        dry_run: bool,
+    },
+    #[command(about="Encrypts the archive and its backups with a passphrase (except uids and the operations backups were made for).")]
+    Encrypt,
+    #[command(about="Decrypts the archive and its backups so they no longer need a passphrase.")]
+    Decrypt,
+    #[command(about="Changes the passphrase the archive and its backups are encrypted with.")]
+    ChangePassphrase,
+    #[command(about="Exports the archive as an `Obsidian.md` vault, a static website or json.")]
     Export {
+        #[arg(short, long, value_enum, default_value_t=export::Format::Md, help="What to export the archive as")]
+        format: export::Format,
         #[arg(short, long, num_args=1.., help="Filters out entries and mocs that don't have all these tags")]
         tags: Option<Vec<String>>,
         #[arg(short, long, requires="tags", help="Determines if the tags filter strictly or not")]
         strict: bool,
-        #[arg(index=1, required=true, help="The path the `Obsidian.md` vault is going to be placed")]
+        #[arg(short, long, help="Filters out entries and mocs that don't match a query, eg `(travel or work) and not draft`")]
+        query: Option<String>,
+        #[arg(long, conflicts_with="omit", help="Replaces private entries, mocs and sections with a placeholder")]
+        redact: bool,
+        #[arg(long, help="Leaves private entries, mocs and sections out entirely")]
+        omit: bool,
+        #[arg(index=1, required=true, help="The path the `Obsidian.md` vault (or website, or json file) is going to be placed")]
         path: String,
     },
+    #[command(subcommand_negates_reqs=true, about="Imports the entries and mocs of a json or ndjson export (or notes from other apps) into the archive.")]
+    Import {
+        #[command(subcommand)]
+        command: Option<ImportCommands>,
+        #[arg(index=1, required=true, help="The path to the json or ndjson export")]
+        path: Option<String>,
+        #[arg(long, global=true, help="Reports what would be imported without importing it")]
+        dry_run: bool,
+        #[arg(long, global=true, value_enum, default_value_t=import::Collision::Overwrite, help="What to do with items of the same uid as one already in the archive")]
+        on_collision: import::Collision,
+    },
     #[command(about="Lists the attributes about an entry or moc.")]
     About {
         #[arg(short='m', long, help="Determines if it is a moc or not")]
@@ -93,6 +198,13 @@ pub enum Commands {
         #[arg(index=1, required=true, help="The uid of the entry or moc")]
         uid: String,
     },
+    #[command(about="Shows an entry (or moc) in full in the terminal, through `$PAGER` if it's interactive")]
+    Show {
+        #[arg(short='m', long, help="Determines if it is a moc or not")]
+        is_moc: bool,
+        #[arg(index=1, required=true, help="The uid of the entry or moc")]
+        uid: String,
+    },
     #[command(about="Removes an entry or moc from the archive.")]
     Remove {
         #[arg(short='m', long, help="Determines if it is a moc or not")]
@@ -100,38 +212,186 @@ pub enum Commands {
         #[arg(index=1)]
         uid: String,
     },
+    #[command(about="Lists the committed revisions of an entry or moc.")]
+    History {
+        #[arg(short='m', long, help="Determines if it is a moc or not")]
+        is_moc: bool,
+        #[arg(index=1, required=true, help="The uid of the entry or moc")]
+        uid: String,
+    },
+    #[command(about="Shows what changed between two revisions of an entry or moc (defaults to the latest change).")]
+    Diff {
+        #[arg(short='m', long, help="Determines if it is a moc or not")]
+        is_moc: bool,
+        #[arg(index=1, required=true, help="The uid of the entry or moc")]
+        uid: String,
+        #[arg(index=2, help="The older revision (defaults to the one before the latest)")]
+        rev1: Option<usize>,
+        #[arg(index=3, help="The newer revision (defaults to the latest)")]
+        rev2: Option<usize>,
+    },
+    #[command(about="Restores an entry or moc to an older revision (committing it as a new revision).")]
+    Restore {
+        #[arg(short='m', long, help="Determines if it is a moc or not")]
+        is_moc: bool,
+        #[arg(index=1, required=true, help="The uid of the entry or moc")]
+        uid: String,
+        #[arg(index=2, required=true, help="The revision to restore")]
+        rev: usize,
+    },
+    #[command(about="Creates, lists, renames and deletes named diaries.")]
+    Diaries {
+        #[command(subcommand)]
+        command: Option<DiariesCommands>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupsCommands {
+    #[command(about="Lists the backups from oldest to newest")]
+    List,
+    #[command(about="Removes all but the newest backups")]
+    Prune {
+        #[arg(short, long, required=true, help="How many of the newest backups to keep")]
+        keep: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ImportCommands {
+    #[command(about="Imports the dated notes of an `Obsidian.md` vault as entries (and the mocs of ones exported by `export`)")]
+    Obsidian {
+        #[arg(index=1, required=true, help="The path to the vault")]
+        vault: String,
+    },
+    #[command(about="Imports the entries of a Day One json export")]
+    DayOne {
+        #[arg(index=1, required=true, help="The path to the export's `Journal.json`")]
+        path: String,
+    },
+    #[command(about="Imports the entries of a jrnl journal (its plain text file or a json export)")]
+    Jrnl {
+        #[arg(index=1, required=true, help="The path to the journal")]
+        path: String,
+    },
+    #[command(about="Imports a folder of `yyyy-mm-dd*.md` notes as entries")]
+    Markdown {
+        #[arg(index=1, required=true, help="The path to the folder")]
+        folder: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DiariesCommands {
+    #[command(about="Lists all the diaries")]
+    List,
+    #[command(about="Creates a new named diary")]
+    New {
+        #[arg(index=1, required=true, help="The name of the new diary")]
+        name: String,
+    },
+    #[command(about="Renames a named diary")]
+    Rename {
+        #[arg(index=1, required=true, help="The current name of the diary")]
+        old: String,
+        #[arg(index=2, required=true, help="The new name of the diary")]
+        new: String,
+    },
+    #[command(about="Deletes a named diary and its backups")]
+    Delete {
+        #[arg(index=1, required=true, help="The name of the diary to delete")]
+        name: String,
+    },
 }
 
 impl Commands {
-    pub fn execute(self) {
+    pub fn execute(self, home: &Home) -> Result<()> {
         use Commands::*;
         let logger = DynamicLogger::new();
         match self {
             Test => println!("Hello, world!"),
-            Init => {Archive::init(logger);},
-            Wipe => Archive::load(logger.hollow()).wipe(logger),
-            Commit { file_path } => Archive::load(logger.hollow()).commit(file_path, logger),
-            Load { file_path, force } => Archive::load_backup(file_path, force, logger),
-            Rollback { force } => Archive::rollback(force, logger),
+            Init { encrypted: false } => {Archive::init(home, logger)?;},
+            Init { encrypted: true } => crypt::init_command(home, logger)?,
+            New { template, title, pattern, path, edit } => scaffold::new_command(template, title, pattern, path, edit, home, logger)?,
+            Edit { is_moc, uid } => edit::edit_command(uid, is_moc, home, logger)?,
+            Wipe => Archive::load(home, logger.hollow())?.wipe(logger)?,
+            Commit { file_paths, recursive } => commit::commit_command(file_paths, recursive, home, logger)?,
+            Watch { dir, debounce } => watch::watch_command(dir, std::time::Duration::from_millis(debounce), home, logger)?,
+            Check { file_paths } => check::check_command(file_paths, home, logger)?,
+            Load { file_path, force } => Archive::load_backup(home, file_path, force, logger)?,
+            Rollback { force, to } => Archive::rollback(home, to, force, logger)?,
             Backup { out_path } => {
                 match out_path {
-                    Some(path) => Archive::backup(path, logger),
-                    None => Archive::backup(home_dir().join("backup.ldb"), logger),
+                    Some(path) => Archive::backup(home, path, logger)?,
+                    None => {backups::create(&Archive::load(home, logger.hollow())?, "backup", logger)?;},
                 }
             },
-            Since { date, today: _ } => since::since_2023(date, logger),
-            Pull { is_moc, one_file, uid, path, file_name } => pull::pull(std::path::PathBuf::from(path), file_name, is_moc, uid, one_file, logger),
-            List { strict, tags, show_entries, show_mocs } => search::list_command(strict, show_mocs, show_entries, tags, logger),
-            Sort => sort::sort(logger),
-            Export { strict, tags, path } => export::export_md(strict, tags, path, logger.hollow()),
-            About { is_moc, uid } => about::about(is_moc, uid, logger),
-            Remove { is_moc, uid } => uncommit::uncommmit(uid, is_moc, logger),
+            Backups { command } => match command.unwrap_or(BackupsCommands::List) {
+                BackupsCommands::List => backups::list_command(home, logger)?,
+                BackupsCommands::Prune { keep } => backups::prune_command(home, keep, logger)?,
+            },
+            Since { date, today: _ } => since::since_2023(date, logger)?,
+            Pull { is_moc, one_file, uid, path, file_name } => pull::pull(std::path::PathBuf::from(path), file_name, is_moc, uid, one_file, home, logger)?,
+            List { strict, tags, query, show_entries, show_mocs } => search::list_command(strict, show_mocs, show_entries, tags, query, home, logger)?,
+            Find { pattern, regex, word, ignore_case, show_entries, show_mocs } => find::find_command(pattern, regex, word, ignore_case, show_entries, show_mocs, home, logger)?,
+            Sort => sort::sort(&Archive::load(home, logger.hollow())?, logger)?,
+            Reindex => index::reindex(home, logger)?,
+            Fsck { repair } => fsck::fsck_command(repair, home, logger)?,
+            Migrate { dry_run } => migrate::migrate_command(dry_run, home, logger)?,
+            Encrypt => crypt::encrypt_command(home, logger)?,
+            Decrypt => crypt::decrypt_command(home, logger)?,
+            ChangePassphrase => crypt::change_passphrase_command(home, logger)?,
+            Export { format, strict, tags, query, redact, omit, path } => {
+                let privacy = match (redact, omit) {
+                    (true, _) => export::Privacy::Redact,
+                    (_, true) => export::Privacy::Omit,
+                    _ => export::Privacy::Show,
+                };
+                export::export(format, strict, tags, query, privacy, path, home, logger.hollow())?
+            },
+            Import { command, path, dry_run, on_collision } => match command {
+                Some(ImportCommands::Obsidian { vault }) => import::import(&import::obsidian::Obsidian, vault, dry_run, on_collision, home, logger)?,
+                Some(ImportCommands::DayOne { path }) => import::import(&import::day_one::DayOne, path, dry_run, on_collision, home, logger)?,
+                Some(ImportCommands::Jrnl { path }) => import::import(&import::jrnl::Jrnl, path, dry_run, on_collision, home, logger)?,
+                Some(ImportCommands::Markdown { folder }) => import::import(&import::markdown::Markdown, folder, dry_run, on_collision, home, logger)?,
+                None => import::import(&import::DiaryJson, path.unwrap_or_default(), dry_run, on_collision, home, logger)?,
+            },
+            About { is_moc, uid } => about::about(is_moc, uid, home, logger)?,
+            Show { is_moc, uid } => show::show_command(uid, is_moc, home, logger)?,
+            Remove { is_moc, uid } => uncommit::uncommmit(uid, is_moc, home, logger)?,
+            History { is_moc, uid } => history::history_command(uid, is_moc, home, logger)?,
+            Diff { is_moc, uid, rev1, rev2 } => history::diff_command(uid, is_moc, rev1, rev2, home, logger)?,
+            Restore { is_moc, uid, rev } => history::restore_command(uid, is_moc, rev, home, logger)?,
+            Diaries { command } => match command.unwrap_or(DiariesCommands::List) {
+                DiariesCommands::List => diaries::list_command(home, logger)?,
+                DiariesCommands::New { name } => diaries::create(home, &name, logger)?,
+                DiariesCommands::Rename { old, new } => diaries::rename(home, &old, &new, logger)?,
+                DiariesCommands::Delete { name } => diaries::delete(home, &name, logger)?,
+            },
         }
+
+        Ok(())
     }
 }
 
 pub fn run() {
     let args = Cli::parse();
     unsafe { VERBOSE = args.verbose };
-    args.command.execute();
-}
\ No newline at end of file
+    unsafe { WAIT = args.wait };
+    let home = Home::from_env(args.archive).with_diary(args.diary.as_deref());
+    let result = match &args.diary {
+        Some(name) => diaries::validate_name(name),
+        None => Ok(()),
+    }.and_then(|_| args.command.execute(&home));
+    if let Err(err) = result {
+        let mut logger = DynamicLogger::new();
+        log!((logger.error) Diary("{err}") as Fatal);
+        if let crate::Error::Query { query, error } = &err {
+            log!((logger.vital) Query("{}", error.highlight(query)) as Log);
+        }
+        if err.may_corrupt() {
+            log!((logger.vital) Diary("The archive may now be corrupted! Use `diary-cli rollback` to roll-back to the latest backup (that was made before any modification of the archive)") as Warning);
+        }
+        logger.crash()
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs, path::{Path, PathBuf}};
+use soulog::*;
+use crate::{archive::Archive, backups, check, sort, transaction, Context, Error, Home};
+
+/// Commits many entry and moc configs (files, glob patterns or, with `recursive`, folders of them) as one backed up change, sorting the archive once at the end
+///
+/// Nothing is committed unless every config is valid
+pub fn commit_command(paths: Vec<String>, recursive: bool, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    // Checks if the archive exists, as loading a missing one initialises it
+    if !home.archive().is_dir() {
+        return Err(Error::NotFound(format!("Archive '{}' doesn't exist! Run `diary-cli init` before you can commit", home.archive().to_string_lossy())));
+    }
+
+    let files = resolve(&paths, recursive)?;
+    let archive = Archive::load(home, logger.hollow())?;
+
+    // Check everything before touching the archive
+    log!((logger) Commit("Checking {} config file(s)...", files.len()));
+    let problems = check::check(&files, Some(&archive), logger.hollow())?;
+    for x in problems.iter() {
+        if x.warning {
+            log!((logger.vital) Commit("{x}") as Warning);
+        } else {
+            log!((logger.error) Commit("{x}") as Failure);
+        }
+    }
+    let errors = problems.iter().filter(|x| !x.warning).count();
+    if errors > 0 {
+        return Err(Error::Validation(format!("Found {errors} problem(s) in {} config file(s); nothing was committed", files.len())));
+    }
+
+    commit_all(&archive, &files, logger)
+}
+
+/// Commits already checked configs as one backed up change (sorting the archive once at the end), reporting what each was committed as
+pub fn commit_all(archive: &Archive, files: &[String], mut logger: impl Logger) -> crate::Result<()> {
+    let mut configs = Vec::with_capacity(files.len());
+    for file in files.iter() {
+        let config = fs::read_to_string(file).with_context(|| format!("While reading config file '{file}'"))?
+            .parse::<toml::Table>()
+            .map_err(|err| Error::Parse { path: file.clone(), message: err.to_string() })?;
+        Archive::validate(config.clone(), file, logger.hollow())?;
+        configs.push((file, config));
+    }
+
+    let operation = match files {
+        [file] => format!("commit '{file}'"),
+        _ => format!("commit {} config files", files.len()),
+    };
+    backups::create(archive, &operation, logger.hollow())?;
+    let mut committed = Vec::with_capacity(configs.len());
+    transaction::run(archive, logger.hollow(), |archive| {
+        for (file, config) in configs {
+            log!((logger) Commit("Committing '{file}'..."));
+            let item = Archive::uid_of(&config);
+            archive.write_table(config, file, logger.hollow())?;
+            committed.push((file, item));
+        }
+        sort::sort(archive, logger.hollow())
+    })?;
+
+    for (file, item) in committed {
+        match item {
+            Some((is_moc, uid)) => log!((logger.vital) Commit("Committed {} '{uid}' from '{file}'", if is_moc { "moc" } else { "entry" }) as Log),
+            None => log!((logger.vital) Commit("Committed '{file}'") as Log),
+        }
+    }
+    log!((logger.vital) Commit("Successfully committed {} config file(s) to archive", files.len()) as Log);
+    Ok(())
+}
+
+/// Resolves the paths given to `commit` into the config files they name, in order and without duplicates
+///
+/// Glob patterns (like `entries/*.toml`) are expanded and folders are searched for `.toml` files (leaving out hidden ones like `.git`) only if `recursive`
+pub fn resolve(paths: &[String], recursive: bool) -> crate::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let matches = if Path::new(path).exists() {
+            vec![PathBuf::from(path)]
+        } else {
+            let pattern = glob::glob(path).map_err(|err| Error::Validation(format!("Invalid glob pattern '{path}': {err}")))?;
+            let matches = pattern.filter_map(|x| x.ok()).collect::<Vec<_>>();
+            if matches.is_empty() {
+                return Err(Error::NotFound(format!("Entry config file '{path}' doesn't exist")));
+            }
+            matches
+        };
+
+        for x in matches {
+            if !x.is_dir() {
+                files.push(x);
+            } else if recursive {
+                let mut found = Vec::new();
+                find_configs(&x, &mut found)?;
+                found.sort();
+                files.extend(found);
+            } else {
+                return Err(Error::Validation(format!("'{}' is a folder; pass `--recursive` to commit the configs within it", x.to_string_lossy())));
+            }
+        }
+    }
+
+    // The same file may be named differently (eg `./a.toml` and `a.toml`)
+    let mut seen = Vec::with_capacity(files.len());
+    let mut resolved = Vec::with_capacity(files.len());
+    for x in files {
+        let canonical = fs::canonicalize(&x).unwrap_or_else(|_| x.clone());
+        if seen.contains(&canonical) { continue }
+        seen.push(canonical);
+        resolved.push(x.to_string_lossy().to_string());
+    }
+    if resolved.is_empty() {
+        return Err(Error::NotFound("Found no config files to commit".into()));
+    }
+    Ok(resolved)
+}
+
+/// Finds the `.toml` files within a folder, leaving out hidden ones (like `.git`)
+pub(crate) fn find_configs(path: &Path, configs: &mut Vec<PathBuf>) -> crate::Result<()> {
+    let context = || format!("While reading folder '{}'", path.to_string_lossy());
+    for x in fs::read_dir(path).with_context(context)? {
+        let path = x.with_context(context)?.path();
+        if path.file_name().is_some_and(|x| x.to_string_lossy().starts_with('.')) { continue }
+        if path.is_dir() {
+            find_configs(&path, configs)?;
+        } else if path.extension().is_some_and(|x| x == "toml") {
+            configs.push(path);
+        }
+    }
+    Ok(())
+}
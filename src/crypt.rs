@@ -0,0 +1,451 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::{Blake2bMac512, digest::Mac};
+use chacha20poly1305::{aead::{Aead, AeadCore, KeyInit, OsRng, Payload, rand_core::RngCore}, XChaCha20Poly1305, XNonce};
+use lazy_db::*;
+use soulog::*;
+use crate::{archive::Archive, backups, index, transaction, Context, Error, Home};
+
+/// The environment variable the archive's passphrase is read from instead of prompting for it
+pub const PASSPHRASE_VAR: &str = "DIARY_CLI_PASSPHRASE";
+/// The environment variable `change-passphrase` reads the new passphrase from instead of prompting for it
+pub const NEW_PASSPHRASE_VAR: &str = "DIARY_CLI_NEW_PASSPHRASE";
+
+/// The container in the root of encrypted archives that holds the salt, key derivation parameters and key check
+pub const HEADER: &str = "crypt";
+
+/// What sealed values start with, followed by the salt of the key they're sealed with, their nonce and their ciphertext
+const MAGIC: &[u8; 4] = b"dcx1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// The plaintext of the key check, which only the right passphrase's key can open
+const CHECK: &[u8] = b"diary-cli key check";
+
+/// The keys derived from an archive's passphrase
+pub struct Keys {
+    salt: [u8; SALT_LEN],
+    cipher: XChaCha20Poly1305,
+    /// For the keyed hashes the index is stored under
+    blind: [u8; 32],
+}
+
+impl Keys {
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN], params: &Params) -> crate::Result<Self> {
+        let params = Params::new(params.m_cost(), params.t_cost(), params.p_cost(), Some(64))
+            .map_err(|err| Error::Encryption(format!("Invalid key derivation parameters: {err}")))?;
+        let mut key = [0u8; 64];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|err| Error::Encryption(format!("While deriving the archive's key: {err}")))?;
+
+        let (cipher, blind) = key.split_at(32);
+        Ok(Self {
+            salt,
+            cipher: XChaCha20Poly1305::new(cipher.into()),
+            blind: blind.try_into().unwrap(),
+        })
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + plaintext.len() + 16);
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&self.salt);
+        let ciphertext = self.cipher.encrypt(&nonce, Payload { msg: plaintext, aad: &sealed })
+            .expect("values are far too small to fail encryption");
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Opens a value sealed with these keys, returning `None` if it was tampered with or sealed with other keys
+    fn open(&self, sealed: &[u8]) -> Option<Vec<u8>> {
+        let (header, rest) = sealed.split_at_checked(MAGIC.len() + SALT_LEN)?;
+        let (nonce, ciphertext) = rest.split_at_checked(NONCE_LEN)?;
+        self.cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: header }).ok()
+    }
+}
+
+/// The keys this process has unlocked, and the archive roots (including previews and decompiled backups) they encrypt
+struct Keyring {
+    keys: Vec<Arc<Keys>>,
+    roots: Vec<(PathBuf, Arc<Keys>)>,
+}
+
+static KEYRING: Mutex<Keyring> = Mutex::new(Keyring { keys: Vec::new(), roots: Vec::new() });
+
+fn keyring() -> std::sync::MutexGuard<'static, Keyring> {
+    KEYRING.lock().unwrap_or_else(|x| x.into_inner())
+}
+
+/// Sets the keys the archive at a root is encrypted with (`None` if it isn't)
+fn register(root: &Path, keys: Option<Arc<Keys>>) {
+    let mut keyring = keyring();
+    keyring.roots.retain(|(x, _)| x != root);
+    if let Some(keys) = keys {
+        if !keyring.keys.iter().any(|x| Arc::ptr_eq(x, &keys)) {
+            keyring.keys.push(keys.clone());
+        }
+        keyring.roots.push((root.to_path_buf(), keys));
+    }
+}
+
+/// Forgets the keys of the archive at a root, so that values written to it aren't encrypted
+#[inline]
+pub fn forget(root: &Path) {
+    register(root, None);
+}
+
+/// The keys values written to a path are encrypted with, if it is within an encrypted archive
+pub fn keys_for(path: &Path) -> Option<Arc<Keys>> {
+    keyring().roots.iter()
+        .filter(|(root, _)| path.starts_with(root))
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, keys)| keys.clone())
+}
+
+/// Returns if the archive at a root is encrypted
+#[inline]
+pub fn is_encrypted(root: &Path) -> bool {
+    root.join(HEADER).is_dir()
+}
+
+fn corrupt(message: String) -> LDBError {
+    LDBError::IOError(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Writes a string into a file of the archive, sealing it with the keys if there are any
+pub fn new_string(file: FileWrapper, keys: Option<&Keys>, value: &str) -> Result<(), LDBError> {
+    match keys {
+        Some(keys) => LazyData::new_binary(file, &keys.seal(value.as_bytes())),
+        None => LazyData::new_string(file, value),
+    }
+}
+
+/// Writes a string into a container, encrypting it if the container is within an encrypted archive
+pub fn write_string(container: &LazyContainer, key: &str, value: &str) -> Result<(), LDBError> {
+    let keys = keys_for(container.path());
+    new_string(container.data_writer(key)?, keys.as_deref(), value)
+}
+
+/// Reads a string from the archive, decrypting it if it was encrypted
+pub fn collect_string(data: LazyData) -> Result<String, LDBError> {
+    if data.lazy_type != LazyType::Binary {
+        return data.collect_string();
+    }
+
+    let path = data.path.clone();
+    let sealed = data.collect_binary()?;
+    if !sealed.starts_with(MAGIC) {
+        return Err(LDBError::IncorrectType(LazyType::Binary, "String".into()));
+    }
+    open(&path, &sealed)
+}
+
+/// Decrypts a sealed string with whichever unlocked keys it was sealed with
+fn open(path: &Path, sealed: &[u8]) -> Result<String, LDBError> {
+    let salt = &sealed[MAGIC.len()..(MAGIC.len() + SALT_LEN).min(sealed.len())];
+    let keys = keyring().keys.iter().find(|x| x.salt == salt).cloned()
+        .ok_or_else(|| corrupt(format!("'{}' is encrypted with a key that hasn't been unlocked", path.to_string_lossy())))?;
+    let plaintext = keys.open(sealed)
+        .ok_or_else(|| corrupt(format!("'{}' couldn't be decrypted (it may have been tampered with)", path.to_string_lossy())))?;
+    String::from_utf8(plaintext).map_err(|err| LDBError::InvalidUTF8String(err.into_bytes().into_boxed_slice()))
+}
+
+/// The keyed hash a term is stored under in the index of an encrypted archive (`None` if the path isn't within one)
+pub fn blind(path: &Path, term: &str) -> Option<String> {
+    let keys = keys_for(path)?;
+    let mut mac = <Blake2bMac512 as Mac>::new_from_slice(&keys.blind).expect("blake2b takes 32 byte keys");
+    mac.update(term.as_bytes());
+    Some(mac.finalize().into_bytes()[..16].iter().map(|x| format!("{x:02x}")).collect())
+}
+
+fn read_header(root: &Path) -> crate::Result<([u8; SALT_LEN], Params, Box<[u8]>)> {
+    let header = LazyContainer::load(root.join(HEADER)).context("While loading the archive's encryption header")?;
+    let salt = header.read_data("salt").and_then(|x| x.collect_binary()).context("While reading the archive's salt")?;
+    let salt = <[u8; SALT_LEN]>::try_from(salt.as_ref())
+        .map_err(|_| Error::Encryption(format!("Archive '{}' has a salt of the wrong length", root.to_string_lossy())))?;
+    let [memory, iterations, parallelism] = ["memory", "iterations", "parallelism"]
+        .map(|x| header.read_data(x).and_then(|x| x.collect_u32()));
+    let params = Params::new(
+        memory.context("While reading the archive's key derivation parameters")?,
+        iterations.context("While reading the archive's key derivation parameters")?,
+        parallelism.context("While reading the archive's key derivation parameters")?,
+        None,
+    ).map_err(|err| Error::Encryption(format!("Archive '{}' has invalid key derivation parameters: {err}", root.to_string_lossy())))?;
+    let check = header.read_data("check").and_then(|x| x.collect_binary()).context("While reading the archive's key check")?;
+    Ok((salt, params, check))
+}
+
+fn write_header(root: &Path, keys: &Keys, params: &Params) -> crate::Result<()> {
+    let root = LazyContainer::load(root).context("While loading archive as container")?;
+    let header = root.new_container(HEADER).context("While writing the archive's encryption header")?;
+    let check = keys.seal(CHECK);
+    write_container!((header) salt = new_binary(&keys.salt)).context("While writing the archive's salt")?;
+    write_container!((header) memory = new_u32(params.m_cost())).context("While writing the archive's key derivation parameters")?;
+    write_container!((header) iterations = new_u32(params.t_cost())).context("While writing the archive's key derivation parameters")?;
+    write_container!((header) parallelism = new_u32(params.p_cost())).context("While writing the archive's key derivation parameters")?;
+    write_container!((header) check = new_binary(&check)).context("While writing the archive's key check")
+}
+
+fn prompt(message: &str) -> crate::Result<String> {
+    rpassword::prompt_password(message)
+        .map_err(|err| Error::Encryption(format!("Couldn't read a passphrase ({err}); set `${PASSPHRASE_VAR}` to give it without a terminal")))
+}
+
+/// Reads a new passphrase from an environment variable, or prompts for it twice
+fn new_passphrase(var: &str) -> crate::Result<String> {
+    let passphrase = match std::env::var(var) {
+        Ok(x) if !x.is_empty() => x,
+        _ => {
+            let passphrase = prompt("New passphrase: ")?;
+            if prompt("Repeat the new passphrase: ")? != passphrase {
+                return Err(Error::Validation("The passphrases don't match".into()));
+            } passphrase
+        },
+    };
+
+    if passphrase.is_empty() {
+        return Err(Error::Validation("The passphrase can't be empty".into()));
+    }
+    Ok(passphrase)
+}
+
+/// Derives fresh keys (with a new salt) from a passphrase
+fn generate(passphrase: &str) -> crate::Result<(Arc<Keys>, Params)> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let params = Params::default();
+    Ok((Arc::new(Keys::derive(passphrase, salt, &params)?), params))
+}
+
+/// Unlocks the archive at a root if it is encrypted, asking for its passphrase unless this process already has its keys
+pub fn unlock(root: &Path, mut logger: impl Logger) -> crate::Result<()> {
+    if !is_encrypted(root) {
+        forget(root);
+        return Ok(());
+    }
+
+    let (salt, params, check) = read_header(root)?;
+    let known = keyring().keys.iter().find(|x| x.salt == salt).cloned();
+    if let Some(keys) = known {
+        register(root, Some(keys));
+        return Ok(());
+    }
+
+    log!((logger) Crypt("Unlocking archive '{}'...", root.to_string_lossy()));
+    let from_env = std::env::var(PASSPHRASE_VAR).ok().filter(|x| !x.is_empty());
+    let attempts = if from_env.is_some() { 1 } else { 3 };
+    for _ in 0..attempts {
+        let passphrase = match &from_env {
+            Some(x) => x.clone(),
+            None => prompt(&format!("Passphrase for archive '{}': ", root.to_string_lossy()))?,
+        };
+
+        let keys = Keys::derive(&passphrase, salt, &params)?;
+        if keys.open(&check).is_some_and(|x| x == CHECK) {
+            register(root, Some(Arc::new(keys)));
+            return Ok(());
+        }
+        if from_env.is_none() {
+            log!((logger.vital) Crypt("Incorrect passphrase, please retry") as Warning);
+        }
+    }
+
+    Err(Error::Encryption(match from_env {
+        Some(_) => format!("Incorrect passphrase for archive '{}' (from `${PASSPHRASE_VAR}`)", root.to_string_lossy()),
+        None => format!("Incorrect passphrase for archive '{}'", root.to_string_lossy()),
+    }))
+}
+
+/// Re-encrypts every string of the archive at a root (or below it) with the keys, decrypting them if there are none
+fn reseal_values(path: &Path, keys: Option<&Keys>) -> crate::Result<usize> {
+    let mut resealed = 0;
+    for x in fs::read_dir(path).with_context(|| format!("While reading directory '{}'", path.to_string_lossy()))? {
+        let x = x.with_context(|| format!("While reading directory '{}'", path.to_string_lossy()))?;
+        if x.file_type().context("While reading a file's type")?.is_dir() {
+            resealed += reseal_values(&x.path(), keys)?;
+            continue;
+        }
+        if x.file_name() == ".meta" { continue }
+
+        // Unreadable values are left to `fsck`
+        let Ok(data) = LazyData::load(x.path()) else { continue };
+        let value = match data.lazy_type {
+            LazyType::String => data.collect_string(),
+            LazyType::Binary => match data.collect_binary() {
+                Ok(sealed) if sealed.starts_with(MAGIC) => open(&x.path(), &sealed),
+                _ => continue,
+            },
+            _ => continue,
+        }.with_context(|| format!("While decrypting '{}'", x.path().to_string_lossy()))?;
+
+        let container = LazyContainer::load(path).with_context(|| format!("While loading container '{}'", path.to_string_lossy()))?;
+        let name = x.file_name();
+        new_string(container.data_writer(&name).context("While re-encrypting archive")?, keys, &value)
+            .with_context(|| format!("While re-encrypting '{}'", x.path().to_string_lossy()))?;
+        resealed += 1;
+    }
+    Ok(resealed)
+}
+
+/// Re-encrypts the archive at a root with new keys (or decrypts it if there are none), returning how many values it re-encrypted
+fn reseal(root: &Path, keys: Option<(Arc<Keys>, Params)>) -> crate::Result<usize> {
+    let header = root.join(HEADER);
+    if header.is_dir() {
+        fs::remove_dir_all(&header).context("While removing the archive's encryption header")?;
+    }
+
+    let resealed = reseal_values(root, keys.as_ref().map(|(x, _)| x.as_ref()))?;
+    if let Some((keys, params)) = &keys {
+        write_header(root, keys, params)?;
+    }
+    register(root, keys.map(|(x, _)| x));
+    index::rekey(root)?;
+    Ok(resealed)
+}
+
+/// Re-encrypts a compiled backup with new keys (or decrypts it if there are none) into a new file beside it, returning the new file's path
+fn reseal_backup(home: &Home, path: &Path, keys: &Option<(Arc<Keys>, Params)>, mut logger: impl Logger) -> crate::Result<PathBuf> {
+    let path_string = path.to_string_lossy();
+    log!((logger) Crypt("Re-encrypting backup '{path_string}'..."));
+    let scratch = home.scratch();
+    if scratch.exists() {
+        fs::remove_dir_all(&scratch).context("While removing stale scratch space")?;
+    }
+
+    let compiled = path.with_extension("ldb.new");
+    let _ = fs::remove_file(&compiled); // left by an interrupted re-encryption
+    let result = LazyDB::decompile(path, &scratch).with_context(|| format!("While decompiling backup '{path_string}'"))
+        .and_then(|_| unlock(&scratch, logger.hollow()))
+        .and_then(|_| reseal(&scratch, keys.clone()))
+        .and_then(|_| LazyDB::load_dir(&scratch).with_context(|| format!("While loading backup '{path_string}'")))
+        .and_then(|x| x.compile(&compiled).with_context(|| format!("While compiling backup '{path_string}'")));
+    let _ = fs::remove_dir_all(&scratch); // cleanup
+    forget(&scratch);
+    match result {
+        Ok(()) => Ok(compiled),
+        Err(err) => {
+            let _ = fs::remove_file(&compiled);
+            Err(err)
+        },
+    }
+}
+
+/// Re-encrypts the archive and every backup of it with new keys (or decrypts them if there are none)
+///
+/// Every backup is re-encrypted beside itself before anything is replaced, so a failure leaves the archive and all of its backups under the keys they had
+fn rekey(archive: &Archive, keys: Option<(Arc<Keys>, Params)>, operation: &str, mut logger: impl Logger) -> crate::Result<()> {
+    let home = archive.home();
+    backups::create(archive, operation, logger.hollow())?; // re-encrypted along with the rest of them
+
+    let mut paths: Vec<PathBuf> = backups::list(home)?.iter().map(|x| x.path(home)).collect();
+    paths.push(home.backup());
+    let mut resealed_backups = Vec::with_capacity(paths.len());
+    let mut failed = Vec::new();
+    for path in paths.into_iter().filter(|x| x.is_file()) {
+        match reseal_backup(home, &path, &keys, logger.hollow()) {
+            Ok(new) => resealed_backups.push((path, new)),
+            Err(err) => {
+                log!((logger.error) Crypt("Failed to re-encrypt backup '{}': {err}", path.to_string_lossy()) as Failure);
+                failed.push(path);
+            },
+        }
+    }
+    let discard = |resealed: &[(PathBuf, PathBuf)]| for (_, new) in resealed { let _ = fs::remove_file(new); };
+    if !failed.is_empty() {
+        discard(&resealed_backups);
+        return Err(Error::Encryption(format!("Failed to re-encrypt {} backup(s) ({}); the archive and its backups were left as they were", failed.len(), names(&failed))));
+    }
+
+    let resealed = transaction::run_all(archive, logger.hollow(), |archive| reseal(archive.database().path(), keys.clone()));
+    unlock(&home.archive(), logger.hollow())?; // for the keys it's encrypted with now, even if re-encrypting it was undone
+    let resealed = match resealed {
+        Ok(x) => x,
+        Err(err) => {
+            discard(&resealed_backups);
+            return Err(err);
+        },
+    };
+    log!((logger) Crypt("Re-encrypted {resealed} value(s) of the archive"));
+
+    // Only now that everything is re-encrypted are the backups replaced
+    let mut left = Vec::new();
+    for (path, new) in resealed_backups.iter() {
+        if let Err(err) = fs::rename(new, path) {
+            log!((logger.error) Crypt("Failed to replace backup '{}': {err}", path.to_string_lossy()) as Failure);
+            let _ = fs::remove_file(new);
+            left.push(path.clone());
+        }
+    }
+    if !left.is_empty() {
+        return Err(Error::Encryption(format!("The archive was re-encrypted, but {} backup(s) couldn't be replaced and were left as they were ({}); remove them if they shouldn't be kept under their old keys", left.len(), names(&left))));
+    }
+    Ok(())
+}
+
+/// Lists the file names of backups for error messages
+fn names(paths: &[PathBuf]) -> String {
+    paths.iter()
+        .map(|x| format!("'{}'", x.file_name().unwrap_or_default().to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Initialises a new archive that is encrypted with a passphrase
+pub fn init_command(home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    if home.archive().exists() {
+        return Err(Error::AlreadyExists(format!("Archive '{}' already exists, try wiping it before initialising again", home.archive().to_string_lossy())));
+    }
+
+    let passphrase = new_passphrase(PASSPHRASE_VAR)?;
+    let archive = Archive::init(home, logger.hollow())?;
+    log!((logger) Crypt("Deriving the archive's key from its passphrase..."));
+    let (keys, params) = generate(&passphrase)?;
+    write_header(archive.database().path(), &keys, &params)?;
+    register(archive.database().path(), Some(keys));
+    log!((logger.vital) Crypt("Archive '{}' is encrypted; keep its passphrase safe as it can't be recovered without it", home.archive().to_string_lossy()) as Log);
+    Ok(())
+}
+
+pub fn encrypt_command(home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
+    if is_encrypted(archive.database().path()) {
+        return Err(Error::Validation(format!("Archive '{}' is already encrypted; use `diary-cli change-passphrase` to change its passphrase", home.archive().to_string_lossy())));
+    }
+
+    let passphrase = new_passphrase(PASSPHRASE_VAR)?;
+    log!((logger) Crypt("Deriving the archive's key from its passphrase..."));
+    let keys = generate(&passphrase)?;
+    rekey(&archive, Some(keys), "encrypt", logger.hollow())?;
+    log!((logger.vital) Crypt("Successfully encrypted archive '{}' and its backups; keep its passphrase safe as it can't be recovered without it", home.archive().to_string_lossy()) as Log);
+    Ok(())
+}
+
+pub fn decrypt_command(home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
+    if !is_encrypted(archive.database().path()) {
+        return Err(Error::Validation(format!("Archive '{}' isn't encrypted", home.archive().to_string_lossy())));
+    }
+
+    rekey(&archive, None, "decrypt", logger.hollow())?;
+    log!((logger.vital) Crypt("Successfully decrypted archive '{}' and its backups; they are now stored in the clear", home.archive().to_string_lossy()) as Log);
+    Ok(())
+}
+
+pub fn change_passphrase_command(home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?; // unlocked with the current passphrase
+    if !is_encrypted(archive.database().path()) {
+        return Err(Error::Validation(format!("Archive '{}' isn't encrypted; use `diary-cli encrypt` to encrypt it", home.archive().to_string_lossy())));
+    }
+
+    let passphrase = new_passphrase(NEW_PASSPHRASE_VAR)?;
+    log!((logger) Crypt("Deriving the archive's new key from its new passphrase..."));
+    let keys = generate(&passphrase)?;
+    rekey(&archive, Some(keys), "change passphrase", logger.hollow())?;
+    log!((logger.vital) Crypt("Successfully changed the passphrase of archive '{}' and its backups", home.archive().to_string_lossy()) as Log);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use soulog::*;
+use crate::{archive::Archive, home::DEFAULT_DIARY, lock::{self, LockKind}, Context, Error, Home, Result};
+
+/// Checks that a diary name is usable as a directory name (and isn't the default diary's)
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::Validation("Diary name cannot be empty".into()));
+    }
+
+    if !name.chars().all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_') {
+        return Err(Error::Validation(format!("Diary name '{name}' may only contain letters, numbers, '-' and '_'")));
+    }
+
+    Ok(())
+}
+
+fn validate_new_name(name: &str) -> Result<()> {
+    validate_name(name)?;
+    if name == DEFAULT_DIARY {
+        return Err(Error::AlreadyExists(format!("'{DEFAULT_DIARY}' is the name of the default diary")));
+    }
+    Ok(())
+}
+
+fn named(home: &Home, name: &str) -> Result<Home> {
+    validate_name(name)?;
+    let diary = home.with_diary(Some(name));
+    if diary.is_default() {
+        return Err(Error::Conflict(format!("The '{DEFAULT_DIARY}' diary cannot be renamed or deleted; use `diary-cli wipe` to wipe it instead")));
+    }
+    if !diary.path().is_dir() {
+        return Err(Error::NotFound(format!("Diary '{name}' doesn't exist")));
+    }
+    Ok(diary)
+}
+
+/// Lists the names of all the diaries, the default one first
+pub fn list(home: &Home) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let path = home.diaries();
+
+    if path.is_dir() {
+        for x in fs::read_dir(&path).context("While reading the diaries directory")? {
+            let x = x.context("While reading the diaries directory")?;
+            if x.file_type().context("While reading the diaries directory")?.is_dir() {
+                names.push(x.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    names.sort();
+    names.insert(0, DEFAULT_DIARY.to_string());
+    Ok(names)
+}
+
+pub fn list_command(home: &Home, mut logger: impl Logger) -> Result<()> {
+    let names = list(home)?;
+    log!((logger.vital) diaries("{names:#?}") as Result);
+    log!((logger.vital) current(" {}", home.diary()) as Result);
+    Ok(())
+}
+
+/// Creates a new named diary with its own empty archive
+pub fn create(home: &Home, name: &str, mut logger: impl Logger) -> Result<()> {
+    validate_new_name(name)?;
+    let diary = home.with_diary(Some(name));
+    if diary.path().exists() {
+        return Err(Error::AlreadyExists(format!("Diary '{name}' already exists")));
+    }
+
+    log!((logger) Diaries("Creating diary '{name}'..."));
+    Archive::init(&diary, logger.hollow())?;
+    log!((logger.vital) Diaries("Successfully created diary '{name}'; use `--diary {name}` to use it") as Log);
+    Ok(())
+}
+
+/// Renames a named diary (its archive and backup go along with it)
+pub fn rename(home: &Home, old: &str, new: &str, mut logger: impl Logger) -> Result<()> {
+    let from = named(home, old)?;
+    validate_new_name(new)?;
+    let to = home.with_diary(Some(new));
+    if to.path().exists() {
+        return Err(Error::AlreadyExists(format!("Diary '{new}' already exists")));
+    }
+
+    let _lock = lock::acquire(&from, LockKind::Exclusive, logger.hollow())?;
+    log!((logger) Diaries("Renaming diary '{old}' to '{new}'..."));
+    fs::rename(from.path(), to.path()).with_context(|| format!("While renaming diary '{old}' to '{new}'"))?;
+    log!((logger.vital) Diaries("Successfully renamed diary '{old}' to '{new}'") as Log);
+    Ok(())
+}
+
+/// Deletes a named diary along with its backup after asking the user for confirmation
+pub fn delete(home: &Home, name: &str, mut logger: impl Logger) -> Result<()> {
+    let diary = named(home, name)?;
+    let _lock = lock::acquire(&diary, LockKind::Exclusive, logger.hollow())?;
+
+    // Confirm with the user about the action
+    let expected = format!("yes, delete the diary '{name}' and all of its backups permanently");
+    log!((logger.vital) Diaries("To confirm with deleting the diary '{name}' PERMANENTLY enter the phrase below (without quotes):") as Log);
+    let mut confirmed = false;
+    for _ in 0..3 {
+        log!((logger.vital) Diaries("\"{expected}\"") as Log);
+        let input = logger.ask("Diaries", "Enter the phrase");
+        if input.trim_end_matches(['\n', '\r']) == expected {
+            confirmed = true;
+            break;
+        }
+        log!((logger.vital) Diaries("Entered phrase incorrect, please retry") as Warning);
+    } if !confirmed {
+        return Err(Error::Aborted(format!("Entered phrase incorrect; not deleting diary '{name}'")));
+    }
+
+    log!((logger) Diaries("Deleting diary '{name}'..."));
+    fs::remove_dir_all(diary.path()).with_context(|| format!("While deleting diary '{name}'"))?;
+    log!((logger.vital) Diaries("Successfully deleted diary '{name}'") as Log);
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+use soulog::*;
+use toml::Table;
+use crate::{archive::Archive, backups, scaffold, transaction, uncommit, Context, Error, Home, Result};
+
+/// What to do when an edited item's uid is changed
+enum UidChange {
+    Rename,
+    Duplicate,
+}
+
+/// Pulls an entry or moc into a temporary file, opens it in `$EDITOR` and commits it back if it changed
+pub fn edit_command(uid: String, is_moc: bool, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
+    let table = if is_moc {
+        archive.get_moc(uid.clone())?.pull()?
+    } else {
+        archive.get_entry(uid.clone())?.pull(Path::new("."), true)?
+    };
+    drop(archive); // so the archive isn't locked while the editor is open
+
+    let dir = std::env::temp_dir().join(format!("diary-cli-edit-{}", std::process::id()));
+    fs::create_dir_all(&dir).context("While creating the temporary edit directory")?;
+    let path = dir.join(format!("{uid}.toml"));
+    let original = toml::to_string_pretty(&table)
+        .map_err(|err| Error::Validation(format!("While encoding '{uid}' as toml: {err}")))?;
+    fs::write(&path, &original).with_context(|| format!("While writing '{}'", path.to_string_lossy()))?;
+
+    let result = edit(home, &uid, is_moc, &path, &original, logger.hollow());
+    match &result {
+        // Don't lose the user's changes
+        Err(Error::Aborted(_)) => log!((logger.vital) Edit("Your edit was kept at '{}'", path.to_string_lossy()) as Warning),
+        _ => { let _ = fs::remove_dir_all(&dir); },
+    }
+    result
+}
+
+/// Opens the editor until the edit is valid, then commits it (only loading the archive once the editor is closed)
+fn edit(home: &Home, uid: &str, is_moc: bool, path: &Path, original: &str, mut logger: impl Logger) -> Result<()> {
+    let path_string = path.to_string_lossy();
+    loop {
+        scaffold::open_editor(path)?;
+        let edited = fs::read_to_string(path).with_context(|| format!("While reading '{path_string}'"))?;
+        if edited == original {
+            log!((logger.vital) Edit("No changes were made; not committing '{uid}'") as Inconvenience);
+            return Ok(());
+        }
+
+        let archive = Archive::load(home, logger.hollow())?;
+        let (table, new_uid) = match check(&archive, uid, is_moc, &edited, &path_string, logger.hollow()) {
+            Ok(x) => x,
+            // Reopen the editor for mistakes the user can fix
+            Err(err @ (Error::Parse { .. } | Error::Query { .. } | Error::Validation(_) | Error::NotFound(_) | Error::AlreadyExists(_))) => {
+                log!((logger.error) Edit("{err}") as Failure);
+                drop(archive);
+                let answer = logger.ask("Edit", "Reopen the editor to fix it? [Y/n]");
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    return Err(Error::Aborted(format!("Not committing the edit of '{uid}'")));
+                } continue;
+            },
+            Err(err) => return Err(err),
+        };
+
+        if new_uid == uid {
+            return archive.commit_table(table, &path_string, &format!("edit of '{uid}'"), logger.hollow());
+        }
+
+        return match ask_uid_change(uid, &new_uid, logger.hollow())? {
+            UidChange::Duplicate => archive.commit_table(table, &path_string, &format!("duplicate of '{uid}' as '{new_uid}'"), logger.hollow()),
+            UidChange::Rename => {
+                backups::create(&archive, &format!("rename of '{uid}' to '{new_uid}'"), logger.hollow())?;
+                transaction::run(&archive, logger.hollow(), |archive| {
+                    archive.write_table(table, &path_string, logger.hollow())?;
+                    let archive = archive.reload(logger.hollow())?; // for the new itver
+                    uncommit::remove(&archive, uid, is_moc, logger.hollow())
+                })?;
+                log!((logger.vital) Edit("Renamed '{uid}' to '{new_uid}'") as Log);
+                Ok(())
+            },
+        };
+    }
+}
+
+/// Parses and validates an edited config, returning it along with its (possibly changed) uid
+fn check(archive: &Archive, uid: &str, is_moc: bool, edited: &str, path_string: &str, logger: impl Logger) -> Result<(Table, String)> {
+    let table = edited.parse::<Table>()
+        .map_err(|err| Error::Parse { path: path_string.to_string(), message: err.to_string() })?;
+
+    if Archive::validate(table.clone(), path_string, logger)? != is_moc {
+        return Err(Error::Validation(format!("'{uid}' cannot be changed between an entry and a moc")));
+    }
+
+    // Validation already checked the uid is there
+    let new_uid = table[if is_moc { "moc" } else { "entry" }]["uid"].as_str().unwrap_or_default().to_string();
+    let exists = archive.database_exists(format!("{}/{new_uid}", if is_moc { "mocs" } else { "entries" }));
+    if new_uid != uid && exists {
+        return Err(Error::AlreadyExists(format!("Cannot change the uid of '{uid}' to '{new_uid}' as it is already taken")));
+    }
+
+    Ok((table, new_uid))
+}
+
+fn ask_uid_change(uid: &str, new_uid: &str, mut logger: impl Logger) -> Result<UidChange> {
+    log!((logger.vital) Edit("The uid was changed from '{uid}' to '{new_uid}'") as Warning);
+    for _ in 0..3 {
+        let answer = logger.ask("Edit", "[r]ename it, [d]uplicate it or [c]ancel?");
+        match answer.trim().to_lowercase().as_str() {
+            "r" | "rename" => return Ok(UidChange::Rename),
+            "d" | "duplicate" => return Ok(UidChange::Duplicate),
+            "c" | "cancel" => break,
+            _ => log!((logger.vital) Edit("Unknown answer, please retry") as Warning),
+        }
+    }
+    Err(Error::Aborted(format!("Not committing the edit of '{uid}'")))
+}
@@ -4,57 +4,79 @@ use toml::Table;
 use soulog::*;
 use lazy_db::*;
 use std::path::Path;
+use chrono::NaiveDate;
 use crate::search::Searchable;
+use crate::find::Field;
+use crate::Context;
 pub use crate::{
     list,
     unpack_array,
     unwrap_opt,
     read_db_container,
     write_db_container,
+    try_cache_field,
 };
 
+/// How entry dates are stored in the archive (and written in configs)
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
 // Some ease of life utils for section
 #[macro_export]
 macro_rules! unwrap_opt {
-    (($opt:expr) with $logger:ident, format: $origin:ident$error:tt) => {
+    (($opt:expr) else $variant:ident$error:tt) => {
         match $opt {
             Some(x) => x,
-            None => {
-                log!(($logger.error) $origin$error as Fatal);
-                $logger.crash()
-            }
+            None => return Err($crate::Error::$variant(format!$error)),
         }
     }
 }
 
 #[macro_export]
 macro_rules! read_db_container {
-    ($key:ident from $name:ident($container:expr) as $func:ident with $logger:ident) => {{
-        let data = if_err!(($logger) [$name, err => ("While reading from archive: {:?}", err)] retry $container.read_data(stringify!($key)));
-        if_err!(($logger) [$name, err => ("While reading from archive: {:?}", err)] {data.$func()} crash {
-            log!(($logger.error) $name("{err:#?}") as Fatal);
-            $logger.crash()
-        })
-    }}
+    // Strings are decrypted if the archive is encrypted
+    ($key:ident from $name:ident($container:expr) as collect_string) => {
+        $crate::Context::context(
+            $container.read_data(stringify!($key)).and_then($crate::crypt::collect_string),
+            concat!("While reading ", stringify!($name), "'s ", stringify!($key), " from archive"),
+        )?
+    };
+
+    ($key:ident from $name:ident($container:expr) as $func:ident) => {
+        $crate::Context::context(
+            $container.read_data(stringify!($key)).and_then(|x| x.$func()),
+            concat!("While reading ", stringify!($name), "'s ", stringify!($key), " from archive"),
+        )?
+    }
 }
 
 #[macro_export]
 macro_rules! write_db_container {
-    ($name:ident($container:expr) $key:ident = $func:ident($value:expr) with $logger:ident) => {
-        if_err!(($logger) [$name, err => ("While writing to archive: {err:?}")] retry write_container!(($container) $key = $func($value)));
+    // Strings are encrypted if the archive is encrypted
+    ($name:ident($container:expr) $key:ident = new_string($value:expr)) => {
+        $crate::Context::context(
+            $crate::crypt::write_string(&$container, stringify!($key), $value),
+            concat!("While writing ", stringify!($name), "'s ", stringify!($key), " to archive"),
+        )?
+    };
+
+    ($name:ident($container:expr) $key:ident = $func:ident($value:expr)) => {
+        $crate::Context::context(
+            write_container!(($container) $key = $func($value)),
+            concat!("While writing ", stringify!($name), "'s ", stringify!($key), " to archive"),
+        )?
     }
 }
 
 #[macro_export]
 macro_rules! unpack_array {
-    ($result:ident from $raw:ident with $logger:ident by $x:ident => $code:expr) => {
+    ($result:ident from $raw:ident by $x:ident => $code:expr) => {
         let mut $result = Vec::with_capacity($raw.len());
         for $x in $raw {
             $result.push($code)
         };
     };
 
-    ($result:ident from $raw:ident with $logger:ident by ($i:ident, $x:ident) => $code:expr) => {
+    ($result:ident from $raw:ident by ($i:ident, $x:ident) => $code:expr) => {
         let mut $result = Vec::with_capacity($raw.len());
         for ($i, $x) in $raw.iter().enumerate() {
             $result.push($code)
@@ -62,20 +84,43 @@ macro_rules! unpack_array {
     };
 }
 
+/// Like `lazy_db`'s `cache_field!` but for fields that can fail to load
+#[macro_export]
+macro_rules! try_cache_field {
+    ($name:ident($this:ident) -> $type:ty $code:block) => {
+        pub fn $name(&mut self) -> $crate::Result<&mut $type> {
+            let $this = self;
+            if $this.$name.is_none() {
+                $this.$name = Some($code);
+            }; Ok($this.$name.as_mut().unwrap())
+        }
+    }
+}
+
+/// Reads whether an entry, moc or section is private (older archives don't store it for ones that aren't)
+pub fn read_private(container: &LazyContainer, name: &str) -> crate::Result<bool> {
+    match container.read_data("private") {
+        Ok(x) => x.collect_bool().with_context(|| format!("While reading {name}'s privacy")),
+        Err(LDBError::FileNotFound(_)) => Ok(false),
+        Err(source) => Err(crate::Error::Storage { context: format!("While reading {name}'s privacy"), source }),
+    }
+}
+
 macro_rules! get {
-    ($key:ident at $entry:ident from $table:ident as $func:ident with $logger:ident) => {{
+    ($key:ident at $entry:ident from $table:ident as $func:ident) => {{
         let key = stringify!($key);
-        let obj = unwrap_opt!(($table.get(key)) with $logger, format: Entry("Entry '{0}' must have '{key}' attribute", $entry));
+        let obj = unwrap_opt!(($table.get(key)) else Validation("Entry '{0}' must have '{key}' attribute", $entry));
 
-        unwrap_opt!((obj.$func()) with $logger, format: Entry("Entry '{0}'s '{key}' attribute must be of correct type", $entry))
+        unwrap_opt!((obj.$func()) else Validation("Entry '{0}'s '{key}' attribute must be of correct type", $entry))
     }};
 
-    ($var:ident = $key:ident at $entry:ident from $table:ident as $func:ident with $logger:ident or $default:expr) => {
+    ($var:ident = $key:ident at $entry:ident from $table:ident as $func:ident or $default:expr) => {
         let key = stringify!($key);
         let default = $default;
-        let $var = $table.get(key)
-            .map(|x| unwrap_opt!((x.$func()) with $logger, format: Entry("Entry '{0}'s '{key}' attribute must be of the correct type", $entry)))
-            .unwrap_or(&default);
+        let $var = match $table.get(key) {
+            Some(x) => unwrap_opt!((x.$func()) else Validation("Entry '{0}'s '{key}' attribute must be of the correct type", $entry)),
+            None => &default,
+        };
     };
 }
 
@@ -87,54 +132,61 @@ pub struct Entry {
     pub description: Option<String>,
     pub tags: Option<Box<[String]>>,
     pub notes: Option<Box<[String]>>,
-    /// Date goes from `day` to `month` then to `year`
-    pub date: Option<[u16; 3]>,
+    pub date: Option<NaiveDate>,
+    /// Private entries are redacted or left out of public exports
+    pub private: Option<bool>,
 }
 
 impl Entry {
-    pub fn new(table: Table, entry_path: &str, database: LazyContainer, mut logger: impl Logger) -> Self {
+    pub fn new(table: Table, entry_path: &str, database: LazyContainer, mut logger: impl Logger) -> crate::Result<Self> {
         log!((logger) Entry("Reading entry '{entry_path}'s raw unchecked data..."));
 
-        let entry_table = get!(entry at entry_path from table as as_table with logger); // For nice entry nesting
-        let uid = get!(uid at entry_path from entry_table as as_str with logger).to_string();
+        let entry_table = get!(entry at entry_path from table as as_table); // For nice entry nesting
+        let uid = get!(uid at entry_path from entry_table as as_str).to_string();
 
-        let title = get!(title at entry_path from entry_table as as_str with logger).to_string();
-        let description = get!(description at entry_path from entry_table as as_str with logger).to_string();
-        get!(raw_notes = notes at entry_path from entry_table as as_array with logger or Vec::<toml::Value>::with_capacity(0));
-        let raw_tags = get!(tags at entry_path from entry_table as as_array with logger);
-        let raw_sections = get!(section at entry_path from table as as_array with logger);
+        let title = get!(title at entry_path from entry_table as as_str).to_string();
+        let description = get!(description at entry_path from entry_table as as_str).to_string();
+        get!(raw_notes = notes at entry_path from entry_table as as_array or Vec::<toml::Value>::with_capacity(0));
+        let raw_tags = get!(tags at entry_path from entry_table as as_array);
+        let private = match entry_table.get("private") {
+            Some(x) => unwrap_opt!((x.as_bool()) else Validation("Entry '{entry_path}'s 'private' attribute must be a boolean")),
+            None => false,
+        };
+        let raw_sections = get!(section at entry_path from table as as_array);
 
         // set the container
-        let container =
-            if_err!((logger) [Entry, err => ("While initialising entry: '{err:?}'")] retry database.new_container(&uid));
+        let container = database.new_container(&uid).context("While initialising entry")?;
 
         // Get date
         log!((logger) Entry("Parsing date..."));
         let date: toml::value::Date = unwrap_opt!(
-            (get!(date at entry_path from entry_table as as_datetime with logger).date)
-            with logger,
-            format: Entry("Datetime 'date' must contain the date")
-        ); let date = [ date.day as u16, date.month as u16, date.year ];
+            (get!(date at entry_path from entry_table as as_datetime).date)
+            else Validation("Entry '{entry_path}'s datetime 'date' must contain the date")
+        ); let date = unwrap_opt!(
+            (NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32))
+            else Validation("Entry '{entry_path}'s date '{date}' isn't a valid date")
+        );
 
         // Parse simple arrays
         log!((logger) Entry("Parsing notes & tags..."));
-        unpack_array!(notes from raw_notes with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: Entry("All notes in entry '{entry_path}' must be strings")).to_string()
+        unpack_array!(notes from raw_notes by x
+            => unwrap_opt!((x.as_str()) else Validation("All notes in entry '{entry_path}' must be strings")).to_string()
         );
 
-        unpack_array!(tags from raw_tags with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: Entry("All tags in entry '{entry_path}' must be strings")).to_string()
+        unpack_array!(tags from raw_tags by x
+            => unwrap_opt!((x.as_str()) else Validation("All tags in entry '{entry_path}' must be strings")).to_string()
         );
 
         // Parse sections
         log!((logger) Entry("Parsing entry's sections..."));
-        let list = if_err!((logger) [Entry, err => ("While initialising sections: {err:?}")] retry container.new_container("sections"));
-        unpack_array!(sections from raw_sections with logger by (i, x) => {
-            let container = if_err!((logger) [Entry, err => ("While initialising section {i}: {err:?}")] retry list.new_container(i.to_string()));
-            let table = unwrap_opt!((x.as_table()) with logger, format: Entry("Entry '{entry_path}', section {i} must be a toml table"));
-            Section::new(table, container, entry_path, i as u8, logger.hollow()) // Write into that container
+        let list = container.new_container("sections").context("While initialising sections")?;
+        unpack_array!(sections from raw_sections by (i, x) => {
+            let container = list.new_container(i.to_string()).with_context(|| format!("While initialising section {i}"))?;
+            let table = unwrap_opt!((x.as_table()) else Validation("Entry '{entry_path}', section {i} must be a toml table"));
+            Section::new(table, container, entry_path, i, logger.hollow())? // Write into that container
         });
-        if_err!((logger) [Entry, err => ("While writing section list length: {err:?}")] retry write_container!((list) length = new_u16(raw_sections.len() as u16)));
+        let length = list::stored_length(raw_sections.len())?;
+        write_container!((list) length = new_u32(length)).context("While writing section list length")?;
 
         log!((logger) Entry("Storing entry's parsed and checked data into archive..."));
 
@@ -147,82 +199,79 @@ impl Entry {
             notes: Some(notes.into_boxed_slice()),
             tags: Some(tags.into_boxed_slice()),
             sections: Some(sections.into_boxed_slice()),
+            private: Some(private),
         };
-        this.store_lazy(logger.hollow());
+        this.store_lazy(logger.hollow())?;
         log!((logger) Entry("Successfully written entry into archive"));
         log!((logger) Entry("")); // spacer
         this.clear_cache();
-        this
+        Ok(this)
     }
 
-    pub fn pull(&mut self, path: &Path, one_file: bool, logger: impl Logger) -> Table {
+    pub fn pull(&mut self, path: &Path, one_file: bool) -> crate::Result<Table> {
         let mut map = Table::new();
         let mut entry = Table::new();
 
         // Insert uid, title, description, notes, tags, and date
         entry.insert("uid".into(), self.uid.clone().into());
-        entry.insert("title".into(), self.title(logger.hollow()).clone().into());
-        entry.insert("description".into(), self.description(logger.hollow()).clone().into());
-        entry.insert("notes".into(), self.notes(logger.hollow()).to_vec().into());
-        entry.insert("tags".into(), self.tags(logger.hollow()).to_vec().into());
-        entry.insert("date".into(), Self::array_to_date(self.date(logger.hollow()), logger.hollow()));
+        entry.insert("title".into(), self.title()?.clone().into());
+        entry.insert("description".into(), self.description()?.clone().into());
+        entry.insert("notes".into(), self.notes()?.to_vec().into());
+        entry.insert("tags".into(), self.tags()?.to_vec().into());
+        entry.insert("date".into(), Self::date_to_toml(self.date()?)?);
+        if *self.private()? { entry.insert("private".into(), true.into()); }
         map.insert("entry".into(), entry.into());
 
         self.clear_cache();
 
-        map.insert("section".into(), self.sections(logger.hollow())
+        map.insert("section".into(), self.sections()?
             .iter_mut()
             .enumerate()
-            .map(|(i, x)| x.pull(i as u8, path, one_file, logger.hollow()))
-            .collect::<Vec<Table>>()
+            .map(|(i, x)| x.pull(i, path, one_file))
+            .collect::<crate::Result<Vec<Table>>>()?
             .into()
         );
 
         self.clear_cache();
 
-        map
+        Ok(map)
     }
 
-    fn array_to_date(arr: &[u16; 3], mut logger: impl Logger) -> toml::Value {
-        // Format the array of u16s to a string in the RFC 3339 date format
-        let date_string = format!("{:04}-{:02}-{:02}",
-            arr[2], // Year
-            arr[1], // Month
-            arr[0], // Day
-        );
-    
+    fn date_to_toml(date: &NaiveDate) -> crate::Result<toml::Value> {
+        // Format the date to a string in the RFC 3339 date format
+        let date_string = date.format(DATE_FORMAT).to_string();
+
         // Parse the string to a toml::Value::Datetime
-        toml::Value::Datetime(if_err!((logger) [Pull, _err => ("Invalid entry date")]
-            {date_string.parse()}
-            crash logger.crash()
-        ))
+        match date_string.parse() {
+            Ok(x) => Ok(toml::Value::Datetime(x)),
+            Err(_) => Err(crate::Error::Validation(format!("Invalid entry date '{date_string}'"))),
+        }
     }
 
-    pub fn store_lazy(&self, mut logger: impl Logger) {
+    pub fn store_lazy(&self, mut logger: impl Logger) -> crate::Result<()> {
         log!((logger) Entry("Storing entry into archive..."));
         // Only store them if modified
-        if let Some(x) = &self.title { write_db_container!(Entry(self.container) title = new_string(x) with logger); }
-        if let Some(x) = &self.description { write_db_container!(Entry(self.container) description = new_string(x) with logger); }
-        if let Some(x) = &self.date { write_db_container!(Entry(self.container) date = new_u16_array(x) with logger); }
+        if let Some(x) = &self.title { write_db_container!(Entry(self.container) title = new_string(x)); }
+        if let Some(x) = &self.description { write_db_container!(Entry(self.container) description = new_string(x)); }
+        if let Some(x) = &self.date { write_db_container!(Entry(self.container) date = new_string(&x.format(DATE_FORMAT).to_string())); }
+        if let Some(x) = self.private { write_db_container!(Entry(self.container) private = new_bool(x)); }
 
         // The bloody lists & arrays
         if let Some(x) = &self.notes {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [Entry, err => ("While writing notes to archive: {:?}", err)] retry self.container.new_container("notes")),
-                logger.hollow()
-            );
+                &self.container.new_container("notes").context("While writing notes to archive")?,
+            )?;
         }
 
         if let Some(x) = &self.tags {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [Entry, err => ("While writing tags to archive: {:?}", err)] retry self.container.new_container("tags")),
-                logger.hollow()
-            );
+                &self.container.new_container("tags").context("While writing tags to archive")?,
+            )?;
         }
+
+        Ok(())
     }
 
     pub fn load_lazy(uid: String, container: LazyContainer) -> Self {
@@ -235,6 +284,7 @@ impl Entry {
             tags: None,
             notes: None,
             date: None,
+            private: None,
         }
     }
 
@@ -245,57 +295,61 @@ impl Entry {
         self.tags = None;
         self.notes = None;
         self.date = None;
+        self.private = None;
     }
 
-    pub fn fill_cache(&mut self, logger: impl Logger) {
-        self.title(logger.hollow());
-        self.sections(logger.hollow());
-        self.tags(logger.hollow());
-        self.notes(logger.hollow());
-        self.date(logger.hollow());
+    pub fn fill_cache(&mut self) -> crate::Result<()> {
+        self.title()?;
+        self.sections()?;
+        self.tags()?;
+        self.notes()?;
+        self.date()?;
+        self.private()?;
+        Ok(())
     }
 
-    cache_field!(title(this, logger) -> String {
-        read_db_container!(title from EntrySection(this.container) as collect_string with logger)
+    try_cache_field!(private(this) -> bool {
+        read_private(&this.container, "entry")?
     });
 
-    cache_field!(description(this, logger) -> String {
-        read_db_container!(description from Entry(this.container) as collect_string with logger)
+    try_cache_field!(title(this) -> String {
+        read_db_container!(title from Entry(this.container) as collect_string)
     });
 
-    cache_field!(notes(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [Entry, err => ("While reading from entry's notes: {err:?}")] retry this.container.child_container("notes")),
-            logger
-        )
+    try_cache_field!(description(this) -> String {
+        read_db_container!(description from Entry(this.container) as collect_string)
     });
 
-    cache_field!(tags(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [Entry, err => ("While reading from entry's tags: {err:?}")] retry this.container.child_container("tags")),
-            logger
-        )
+    try_cache_field!(notes(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("notes").context("While reading from entry's notes")?,
+        )?
     });
 
-    cache_field!(date(this, logger) -> [u16; 3] {
-        let array = read_db_container!(date from Entry(this.container) as collect_u16_array with logger);
-        [array[0], array[1], array[2]]
+    try_cache_field!(tags(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("tags").context("While reading from entry's tags")?,
+        )?
     });
 
-    cache_field!(sections(this, logger) -> Box<[Section]> {
-        let container = if_err!((logger) [Entry, err => ("While reading from entry's sections: {err:?}")] retry this.container.child_container("sections"));
-        let length = if_err!((logger) [Entry, err => ("While reading from entry's sections' length: {err:?}")] retry container.read_data("length"));
-        let length = if_err!((logger) [Entry, err => ("While reading from entry's sections' length: {err:?}")] {length.collect_u16()} crash {
-            log!((logger) Entry("{err:#?}") as Fatal);
-            logger.crash()
-        });
+    try_cache_field!(date(this) -> NaiveDate {
+        let date = read_db_container!(date from Entry(this.container) as collect_string);
+        unwrap_opt!(
+            (NaiveDate::parse_from_str(&date, DATE_FORMAT).ok())
+            else Validation("Entry '{}'s date '{date}' isn't a valid date", this.uid)
+        )
+    });
+
+    try_cache_field!(sections(this) -> Box<[Section]> {
+        let container = this.container.child_container("sections").context("While reading from entry's sections")?;
+        let length = container.read_data("length")
+            .and_then(|x| x.collect_u32())
+            .context("While reading from entry's sections' length")?;
         let mut sections = Vec::with_capacity(length as usize);
 
         for i in 0..length {
             sections.push(Section::load_lazy(
-                if_err!((logger) [Entry, err => ("While reading entry section {i}: {err:?}")] retry container.child_container(i.to_string()))
+                container.child_container(i.to_string()).with_context(|| format!("While reading entry section {i}"))?
             ));
         }
 
@@ -308,9 +362,45 @@ impl Searchable for Entry {
         self.uid.clone()
     }
 
-    fn contains_tag(&mut self, tag: &String, logger: impl Logger) -> bool {
-        let result = self.tags(logger).contains(tag);
+    fn contains_tag(&mut self, tag: &String) -> crate::Result<bool> {
+        let result = self.tags()?.contains(tag);
         self.tags = None;
-        result
+        Ok(result)
+    }
+
+    fn get_tags(&mut self) -> crate::Result<Box<[String]>> {
+        let tags = self.tags()?.clone();
+        self.tags = None;
+        Ok(tags)
+    }
+
+    fn get_date(&mut self) -> crate::Result<Option<NaiveDate>> {
+        let date = *self.date()?;
+        self.date = None;
+        Ok(Some(date))
+    }
+
+    fn is_private(&mut self) -> crate::Result<bool> {
+        let private = *self.private()?;
+        self.private = None;
+        Ok(private)
+    }
+
+    fn get_text(&mut self) -> crate::Result<Vec<(Field, String)>> {
+        let mut text = vec![
+            (Field::Title, self.title()?.clone()),
+            (Field::Description, self.description()?.clone()),
+        ];
+        text.extend(self.notes()?.iter().enumerate().map(|(i, x)| (Field::Note(i), x.clone())));
+
+        for (i, section) in self.sections()?.iter_mut().enumerate() {
+            text.push((Field::SectionTitle(i), section.title()?.clone()));
+            text.extend(section.notes()?.iter().enumerate().map(|(j, x)| (Field::SectionNote(i, j), x.clone())));
+            text.push((Field::SectionContent(i), section.content()?.clone()));
+            section.clear_cache();
+        }
+
+        self.clear_cache();
+        Ok(text)
     }
-}
\ No newline at end of file
+}
@@ -4,23 +4,25 @@ use soulog::*;
 use std::path::Path;
 use crate::list;
 use crate::unpack_array;
+use crate::Context;
 use std::fs;
 
 // Some ease of life macros
 macro_rules! get {
-    ($key:ident at ($entry:ident, $idx:ident) from $table:ident as $func:ident with $logger:ident) => {{
+    ($key:ident at ($entry:ident, $idx:ident) from $table:ident as $func:ident) => {{
         let key = stringify!($key);
-        let obj = unwrap_opt!(($table.get(key)) with $logger, format: Section("Entry '{0}', section {1} must have '{key}' attribute", $entry, $idx));
+        let obj = unwrap_opt!(($table.get(key)) else Validation("Entry '{0}', section {1} must have '{key}' attribute", $entry, $idx));
 
-        unwrap_opt!((obj.$func()) with $logger, format: Section("Entry '{0}', section {1}'s '{key}' attribute must be of the correct type", $entry, $idx))
+        unwrap_opt!((obj.$func()) else Validation("Entry '{0}', section {1}'s '{key}' attribute must be of the correct type", $entry, $idx))
     }};
 
-    ($var:ident = $key:ident at ($entry:ident, $idx:ident) from $table:ident as $func:ident with $logger:ident or $default:expr) => {
+    ($var:ident = $key:ident at ($entry:ident, $idx:ident) from $table:ident as $func:ident or $default:expr) => {
         let key = stringify!($key);
         let default = $default;
-        let $var = $table.get(key)
-            .map(|x| unwrap_opt!((x.$func()) with $logger, format: Entry("Entry '{0}', section {1}'s '{key}' attribute must be of the correct type", $entry, $idx)))
-            .unwrap_or(&default);
+        let $var = match $table.get(key) {
+            Some(x) => unwrap_opt!((x.$func()) else Validation("Entry '{0}', section {1}'s '{key}' attribute must be of the correct type", $entry, $idx)),
+            None => &default,
+        };
     };
 }
 
@@ -29,36 +31,41 @@ pub struct Section {
     pub title: Option<String>,
     pub notes: Option<Box<[String]>>,
     pub content: Option<String>,
+    /// Private sections are redacted or left out of public exports
+    pub private: Option<bool>,
 }
 
 impl Section {
-    pub fn new(table: &Table, container: LazyContainer, entry: &str, idx: u8, mut logger: impl Logger) -> Self {
+    pub fn new(table: &Table, container: LazyContainer, entry: &str, idx: usize, mut logger: impl Logger) -> crate::Result<Self> {
         log!((logger) Section("Parsing entry '{entry}'s section {idx}..."));
 
         // Get the basic needed data
         log!((logger) Section("Reading section's data..."));
-        let title = get!(title at (entry, idx) from table as as_str with logger).to_string();
-        get!(raw_notes = notes at (entry, idx) from table as as_array with logger or Vec::<toml::Value>::with_capacity(0));
+        let title = get!(title at (entry, idx) from table as as_str).to_string();
+        get!(raw_notes = notes at (entry, idx) from table as as_array or Vec::<toml::Value>::with_capacity(0));
+        let private = match table.get("private") {
+            Some(x) => unwrap_opt!((x.as_bool()) else Validation("Entry '{entry}', section {idx}'s 'private' attribute must be a boolean")),
+            None => false,
+        };
 
         // Get contents
-        let content = table.get("path")
-            .map(|x| {
-                let path = unwrap_opt!((x.as_str()) with logger, format: Entry("Entry '{entry}', section {idx}'s 'path' attribute must be of the correct type"));
+        let content = match table.get("path") {
+            Some(x) => {
+                let path = unwrap_opt!((x.as_str()) else Validation("Entry '{entry}', section {idx}'s 'path' attribute must be of the correct type"));
                 log!((logger) Section("Checking if path specified in the section is valid..."));
                 // Check if path exists
                 if !Path::new(path).exists() {
-                    log!((logger.error) Section("Path '{path}' specified in entry '{entry}', section {idx} does not exist") as Fatal);
-                    return logger.crash();
+                    return Err(crate::Error::NotFound(format!("Path '{path}' specified in entry '{entry}', section {idx} does not exist")));
                 };
-                if_err!((logger) [Section, err => ("While reading entry '{entry}', section {idx}'s path contents: {err:?}")] retry fs::read_to_string(path))
-            }).unwrap_or_else(|| {
-                get!(contents at (entry, idx) from table as as_str with logger).to_string()
-            });
+                fs::read_to_string(path).with_context(|| format!("While reading entry '{entry}', section {idx}'s path contents"))?
+            },
+            None => get!(contents at (entry, idx) from table as as_str).to_string(),
+        };
 
         // Parse notes
         log!((logger) Section("Parsing section's notes"));
-        unpack_array!(notes from raw_notes with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: Section("All notes in entry '{entry}', section '{idx}' must be strings")).to_string()
+        unpack_array!(notes from raw_notes by x
+            => unwrap_opt!((x.as_str()) else Validation("All notes in entry '{entry}', section '{idx}' must be strings")).to_string()
         );
 
         log!((logger) Section("Writing entry '{entry}'s section {idx} into archive..."));
@@ -67,48 +74,51 @@ impl Section {
             title: Some(title),
             content: Some(content),
             notes: Some(notes.into_boxed_slice()),
+            private: Some(private),
         };
 
-        this.store_lazy(logger.hollow());
+        this.store_lazy()?;
         this.clear_cache();
         log!((logger) Section("Successfully parsed and written entry's section {idx} into archive"));
         log!((logger) Section("")); // spacer
-        this
+        Ok(this)
     }
 
-    pub fn pull(&mut self, idx: u8, path: &Path, one_file: bool, mut logger: impl Logger) -> Table {
+    pub fn pull(&mut self, idx: usize, path: &Path, one_file: bool) -> crate::Result<Table> {
         let mut map = Table::new();
 
         // Insert title and notes
-        map.insert("title".into(), Value::String(self.title(logger.hollow()).clone()));
-        map.insert("notes".into(), self.notes(logger.hollow()).to_vec().into());
+        map.insert("title".into(), Value::String(self.title()?.clone()));
+        map.insert("notes".into(), self.notes()?.to_vec().into());
+        if *self.private()? { map.insert("private".into(), true.into()); }
 
         if one_file {
-            map.insert("contents".into(), Value::String(self.content(logger.hollow()).clone()));
+            map.insert("contents".into(), Value::String(self.content()?.clone()));
         } else {
             let file_name = format!("section{idx}.txt");
             let path = path.join(&file_name);
             map.insert("path".into(), file_name.into());
-            if_err!((logger) [Pull, err => ("While writing section as text file: {err:?}")] retry fs::write(&path, self.content(logger.hollow())));
+            fs::write(&path, self.content()?).context("While writing section as text file")?;
         }
 
         self.clear_cache();
 
-        map
+        Ok(map)
     }
 
-    pub fn store_lazy(&self, mut logger: impl Logger) {
+    pub fn store_lazy(&self) -> crate::Result<()> {
         // Only store them if they are accessed (maybe modified)
-        if let Some(x) = &self.title { write_db_container!(Section(self.container) title = new_string(x) with logger); }
-        if let Some(x) = &self.content { write_db_container!(Section(self.container) content = new_string(x) with logger); }
+        if let Some(x) = &self.title { write_db_container!(Section(self.container) title = new_string(x)); }
+        if let Some(x) = &self.content { write_db_container!(Section(self.container) content = new_string(x)); }
+        if let Some(x) = self.private { write_db_container!(Section(self.container) private = new_bool(x)); }
         if let Some(x) = &self.notes {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [Section, err => ("While writing section's notes to archive: {:?}", err)] retry self.container.new_container("notes")),
-                logger
-            );
+                &self.container.new_container("notes").context("While writing section's notes to archive")?,
+            )?;
         }
+
+        Ok(())
     }
 
     pub fn load_lazy(container: LazyContainer) -> Self {
@@ -117,6 +127,7 @@ impl Section {
             title: None,
             notes: None,
             content: None,
+            private: None,
         }
     }
 
@@ -124,27 +135,32 @@ impl Section {
         self.title = None;
         self.content = None;
         self.notes = None;
+        self.private = None;
     }
 
-    pub fn fill_cache(&mut self, logger: impl Logger) {
-        self.title(logger.hollow());
-        self.content(logger.hollow());
-        self.notes(logger.hollow());
+    pub fn fill_cache(&mut self) -> crate::Result<()> {
+        self.title()?;
+        self.content()?;
+        self.notes()?;
+        self.private()?;
+        Ok(())
     }
 
-    cache_field!(notes(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [Section, err => ("While reading from section's notes: {err:?}")] retry this.container.child_container("notes")),
-            logger
-        )
+    try_cache_field!(notes(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("notes").context("While reading from section's notes")?,
+        )?
     });
 
-    cache_field!(title(this, logger) -> String {
-        read_db_container!(title from Section(this.container) as collect_string with logger)
+    try_cache_field!(private(this) -> bool {
+        read_private(&this.container, "section")?
     });
 
-    cache_field!(content(this, logger) -> String {
-        read_db_container!(content from Section(this.container) as collect_string with logger)
+    try_cache_field!(title(this) -> String {
+        read_db_container!(title from Section(this.container) as collect_string)
     });
-}
\ No newline at end of file
+
+    try_cache_field!(content(this) -> String {
+        read_db_container!(content from Section(this.container) as collect_string)
+    });
+}
@@ -0,0 +1,121 @@
+use std::{fmt, io};
+use lazy_db::LDBError;
+use crate::query::ParseError;
+#[cfg(feature = "failpoints")]
+use crate::transaction::failpoint;
+
+/// Everything that can go wrong within `diary-cli`
+#[derive(Debug)]
+pub enum Error {
+    /// A config file couldn't be parsed as toml (or an import as json)
+    Parse { path: String, message: String },
+    /// A query couldn't be parsed
+    Query { query: String, error: ParseError },
+    /// An entry or moc config (or some other input) is missing attributes or has invalid ones
+    Validation(String),
+    /// Something (an entry, moc, archive, file, etc.) doesn't exist
+    NotFound(String),
+    /// Something (like an archive) already exists
+    AlreadyExists(String),
+    /// An action was refused as it could lose archive data; it can be forced instead
+    Conflict(String),
+    /// The user didn't confirm a destructive action
+    Aborted(String),
+    /// The archive is locked by another process using it
+    Locked(String),
+    /// The archive is encrypted and couldn't be unlocked (eg with a wrong passphrase)
+    Encryption(String),
+    /// A count (like a list's length or the archive's itver) would go past the largest one the archive can store
+    Overflow(String),
+    /// Reading from or writing to the archive failed
+    Storage { context: String, source: LDBError },
+    /// Reading from or writing to a file failed
+    Io { context: String, source: io::Error },
+    /// Modifying the archive failed part-way through, but the archive was left as it was
+    Reverted(Box<Error>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns if the error may have happened half-way through writing to the archive
+    pub fn may_corrupt(&self) -> bool {
+        matches!(self, Error::Storage { .. } | Error::Io { .. })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Parse { path, message } => write!(f, "While parsing '{path}': {message}"),
+            Query { error, .. } => write!(f, "Invalid query at {error}"),
+            Validation(x) | NotFound(x) | AlreadyExists(x) | Conflict(x) | Aborted(x) | Locked(x) | Encryption(x) | Overflow(x) => write!(f, "{x}"),
+            Storage { context, source } => write!(f, "{context}: {source}"),
+            Io { context, source } => write!(f, "{context}: {source}"),
+            Reverted(x) => write!(f, "{x} (the archive was left as it was)"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Query { error, .. } => Some(error),
+            Error::Storage { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            Error::Reverted(x) => Some(x.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches what was being done to storage and io errors
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+    fn with_context(self, f: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, LDBError> {
+    #[inline]
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        let context = context.into();
+        #[cfg(feature = "failpoints")]
+        failpoint(|| context.clone())?;
+        self.map_err(|source| Error::Storage { context, source })
+    }
+
+    #[inline]
+    fn with_context(self, f: impl FnOnce() -> String) -> Result<T> {
+        match self {
+            Ok(x) => {
+                #[cfg(feature = "failpoints")]
+                failpoint(f)?;
+                Ok(x)
+            },
+            Err(source) => Err(Error::Storage { context: f(), source }),
+        }
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, io::Error> {
+    #[inline]
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        let context = context.into();
+        #[cfg(feature = "failpoints")]
+        failpoint(|| context.clone())?;
+        self.map_err(|source| Error::Io { context, source })
+    }
+
+    #[inline]
+    fn with_context(self, f: impl FnOnce() -> String) -> Result<T> {
+        match self {
+            Ok(x) => {
+                #[cfg(feature = "failpoints")]
+                failpoint(f)?;
+                Ok(x)
+            },
+            Err(source) => Err(Error::Io { context: f(), source }),
+        }
+    }
+}
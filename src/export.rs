@@ -1,155 +1,436 @@
-use std::path::Path;
-use crate::{entry::{Entry, Section}, Scribe, scribe_write, archive::Archive, search, moc::{MOC, Collection}, sort::sort_uids};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDate;
+use crate::{entry::{Entry, Section}, Scribe, scribe_write, archive::Archive, index::{self, Group}, query::{self, Query}, search::Searchable, moc::{MOC, Collection}, sort::sort_uids, Context, Home, Result};
 use soulog::*;
 
-pub fn export_md(strict: bool, tags: Option<Vec<String>>, path: String, mut logger: impl Logger) {
+pub mod html;
+pub mod json;
+
+/// What the archive is exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// An `Obsidian.md` vault
+    Md,
+    /// A static website
+    Html,
+    /// One json document (see `json`)
+    Json,
+    /// Newline delimited json, an entry or moc per line (see `json`)
+    Ndjson,
+}
+
+/// How private entries, mocs and sections are exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privacy {
+    /// Exported like everything else
+    Show,
+    /// Replaced with a placeholder
+    Redact,
+    /// Left out entirely
+    Omit,
+}
+
+impl Privacy {
+    /// Returns if the export is public (private material is kept out of it)
+    #[inline]
+    pub fn is_public(self) -> bool {
+        self != Privacy::Show
+    }
+}
+
+/// What redacted entries, mocs and sections are replaced with
+pub const REDACTED: &str = "*This is private and has been redacted.*";
+
+/// Marks the headings of private sections exported as they are (an `Obsidian.md` comment, so hidden when reading)
+pub const PRIVATE_MARKER: &str = " %%private%%";
+
+/// Starts the `Obsidian.md` comment (so hidden when reading) under a collection's heading that holds what it includes, so it can be imported again
+pub const COLLECTION_MARKER: &str = "%%collection ";
+
+/// Names the pages of redacted entries and mocs, so their uids aren't left in the export as file names or links
+#[derive(Debug, Default)]
+pub struct Redactions(RefCell<HashMap<PathBuf, usize>>);
+
+impl Redactions {
+    /// The name of the page of an item in a folder: its own if it isn't redacted, otherwise an opaque one numbered in the order they're first asked for
+    pub fn name(&self, folder: &Path, name: &str, redacted: bool) -> String {
+        if !redacted { return name.to_string() }
+        let mut names = self.0.borrow_mut();
+        let n = names.len() + 1;
+        format!("redacted-{}", names.entry(folder.join(name)).or_insert(n))
+    }
+}
+
+/// Exports the archive in the specified format
+#[allow(clippy::too_many_arguments)]
+pub fn export(format: Format, strict: bool, tags: Option<Vec<String>>, query: Option<String>, privacy: Privacy, path: String, home: &Home, logger: impl Logger) -> Result<()> {
+    match format {
+        Format::Md => export_md(strict, tags, query, privacy, path, home, logger),
+        Format::Html => html::export_html(strict, tags, query, privacy, path, home, logger),
+        Format::Json => json::export_json(false, strict, tags, query, privacy, path, home, logger),
+        Format::Ndjson => json::export_json(true, strict, tags, query, privacy, path, home, logger),
+    }
+}
+
+pub fn export_md(strict: bool, tags: Option<Vec<String>>, query: Option<String>, privacy: Privacy, path: String, home: &Home, mut logger: impl Logger) -> Result<()> {
     log!((logger) Export("Exporting archive to path '{path}'..."));
-    let archive = Archive::load(logger.hollow());
+    let (archive, mut entries, mut mocs) = select(strict, tags, query, privacy, home, logger.hollow())?;
+
+    // Export em
+    let path = Path::new(&path);
+    let redactions = Redactions::default();
+    let mut private = 0;
+    for entry in entries.iter_mut() {
+        if !privacy.is_public() && has_private(entry)? { private += 1 }
+        export_entry(path, entry, privacy, &redactions, logger.hollow())?
+    }
+    for moc in mocs.iter_mut() {
+        if !privacy.is_public() && moc.is_private()? { private += 1 }
+        export_moc(path, moc, &archive, privacy, &redactions, logger.hollow())?
+    }
+    warn_private(private, logger.hollow());
+
+    log!((logger.vital) Export("Successfully exported all specified items") as Log);
+    Ok(())
+}
+
+/// Warns about the private items that were exported as they are
+fn warn_private(private: usize, mut logger: impl Logger) {
+    if private > 0 {
+        log!((logger.vital) Export("Exported {private} private item(s) as they are; use `--redact` or `--omit` to keep private material out of exports") as Warning);
+    }
+}
+
+/// Loads the archive along with the entries and mocs that pass the tag, query and privacy filters
+fn select(strict: bool, tags: Option<Vec<String>>, query: Option<String>, privacy: Privacy, home: &Home, logger: impl Logger) -> Result<(Archive, Vec<Entry>, Vec<MOC>)> {
+    let query = match query {
+        Some(x) => Some(query::parse(&x)?),
+        None => None,
+    };
+    let archive = Archive::load_shared(home, logger.hollow())?;
 
-    // Get entries and mocs
     let mut entries = match &tags {
-        Some(x) => 
-            (if strict { search::search(x, archive.list_entries(logger.hollow()), logger.hollow()) }
-            else { search::search_strict(x, archive.list_entries(logger.hollow()), logger.hollow()) })
-                .into_iter().map(|x| archive.get_entry(x, logger.hollow()).unwrap()).collect(),
-        None => archive.list_entries(logger.hollow()),
+        Some(x) => index::search(&archive, Group::Tags, x, strict, false, logger.hollow())?
+            .into_iter().map(|x| archive.get_entry(x)).collect::<Result<Vec<_>>>()?,
+        None => archive.list_entries(logger.hollow())?,
     };
     let mut mocs = match &tags {
-        Some(x) => 
-            (if strict { search::search(x, archive.list_mocs(logger.hollow()), logger.hollow()) }
-            else { search::search_strict(x, archive.list_mocs(logger.hollow()), logger.hollow()) })
-                .into_iter().map(|x| archive.get_moc(x, logger.hollow()).unwrap()).collect(),
-        None => archive.list_mocs(logger.hollow()),
+        Some(x) => index::search(&archive, Group::Tags, x, strict, true, logger.hollow())?
+            .into_iter().map(|x| archive.get_moc(x)).collect::<Result<Vec<_>>>()?,
+        None => archive.list_mocs(logger.hollow())?,
     };
 
-    // Export em
-    let path = Path::new(&path);
-    entries.iter_mut().for_each(|x| export_entry(path, x, logger.hollow()));
-    mocs.iter_mut().for_each(|x| export_moc(path, x, &archive, logger.hollow()));
+    if let Some(query) = &query {
+        entries = retain_matching(query, entries)?;
+        mocs = retain_matching(query, mocs)?;
+    }
+    if privacy == Privacy::Omit {
+        entries = retain_public(entries)?;
+        mocs = retain_public(mocs)?;
+    }
 
-    log!((logger.vital) Export("Successfully exported all specified items") as Log);
+    Ok((archive, entries, mocs))
+}
+
+fn retain_public<T: Searchable>(items: Vec<T>) -> Result<Vec<T>> {
+    let mut result = Vec::with_capacity(items.len());
+    for mut item in items {
+        if !item.is_private()? { result.push(item) }
+    }
+    Ok(result)
 }
 
-pub fn export_entry(path: &Path, entry: &mut Entry, mut logger: impl Logger) {
+/// Returns if an entry or any of its sections is private
+fn has_private(entry: &mut Entry) -> Result<bool> {
+    if entry.is_private()? { return Ok(true) }
+    for section in entry.sections()?.iter_mut() {
+        if *section.private()? { return Ok(true) }
+    }
+    Ok(false)
+}
+
+/// Writes the placeholder of a redacted entry or moc
+fn export_redacted(scribe: &mut Scribe) -> Result<()> {
+    scribe_tags(&[], false, scribe)?;
+    scribe.write_line("# Redacted")?;
+    scribe.write_line("---")?;
+    scribe.write_line(REDACTED)
+}
+
+fn retain_matching<T: Searchable>(query: &Query, items: Vec<T>) -> Result<Vec<T>> {
+    let mut result = Vec::with_capacity(items.len());
+    for mut item in items {
+        if query.matches(&mut item)? { result.push(item) }
+    }
+    Ok(result)
+}
+
+pub fn export_entry(path: &Path, entry: &mut Entry, privacy: Privacy, redactions: &Redactions, mut logger: impl Logger) -> Result<()> {
+    if privacy == Privacy::Omit && entry.is_private()? { return Ok(()) }
     log!((logger) Export("Exporting entry of uid '{}'...", entry.uid));
-    let mut scribe = Scribe::new(path.join(&entry.uid).with_extension("md"), logger.hollow());
+    let redacted = privacy == Privacy::Redact && entry.is_private()?;
+    let mut scribe = Scribe::new(path.join(redactions.name(path, &entry.uid, redacted)).with_extension("md"))?;
+    if redacted {
+        export_redacted(&mut scribe)?;
+        return scribe.finish();
+    }
 
     // Tags, title and description
-    let date = *entry.date(logger.hollow());
-    scribe_tags_n_date(entry.tags(logger.hollow()), &date, &mut scribe);
-    scribe_write!((scribe) "# ", entry.title(logger.hollow()), "\n");
-    scribe.write_line("---");
-    scribe_write!((scribe) "**Description:** ", entry.description(logger.hollow()), "\n\n");
+    let date = *entry.date()?;
+    let private = *entry.private()?;
+    scribe_tags_n_date(entry.tags()?, &date, private, &mut scribe)?;
+    scribe_write!((scribe) "# ", entry.title()?, "\n");
+    scribe.write_line("---")?;
+    scribe_write!((scribe) "**Description:** ", entry.description()?, "\n\n");
 
     // Notes
-    let notes = entry.notes(logger.hollow());
+    let notes = entry.notes()?;
     let mut notes_header_written_to: bool = false;
-    if notes.len() > 0 {
+    if !notes.is_empty() {
         notes_header_written_to = true;
-        scribe.write_line("## Notes");
-        notes.iter().for_each(|x| scribe_write!((scribe) "- ", x, "\n"));  
+        scribe.write_line("## Notes")?;
+        for x in notes.iter() { scribe_write!((scribe) "- ", x, "\n") }
     }
 
     // Sections' notes
-    entry.sections(logger.hollow()).iter_mut().for_each(|section| {
-        let title = section.title(logger.hollow()).clone();
-        let notes = section.notes(logger.hollow());
-        if notes.len() > 0 {
-            if !notes_header_written_to { scribe.write_line("## Notes"); notes_header_written_to = true; }
+    for section in entry.sections()?.iter_mut() {
+        if privacy.is_public() && *section.private()? { section.clear_cache(); continue }
+        let title = section.title()?.clone();
+        let notes = section.notes()?;
+        if !notes.is_empty() {
+            if !notes_header_written_to { scribe.write_line("## Notes")?; notes_header_written_to = true; }
             scribe_write!((scribe) "- #### ", &title, "\n");
-            notes.iter().for_each(|x| scribe_write!((scribe) "\t- ", x, "\n"));
+            for x in notes.iter() { scribe_write!((scribe) "\t- ", x, "\n") }
         } section.clear_cache();
-    });
-    scribe.write_line("---");
+    }
+    scribe.write_line("---")?;
 
     // Sections
-    entry.sections(logger.hollow()).iter_mut().for_each(|x| export_section_content(&mut scribe, x, logger.hollow()));
+    for x in entry.sections()?.iter_mut() { export_section_content(&mut scribe, x, privacy)? }
 
     entry.clear_cache();
+    scribe.finish()
 }
 
-pub fn export_moc(path: &Path, moc: &mut MOC, archive: &Archive, mut logger: impl Logger) {
+pub fn export_moc(path: &Path, moc: &mut MOC, archive: &Archive, privacy: Privacy, redactions: &Redactions, mut logger: impl Logger) -> Result<()> {
+    if privacy == Privacy::Omit && moc.is_private()? { return Ok(()) }
     log!((logger) Export("Exporting moc of uid '{}'...", moc.uid));
-    let mut scribe = Scribe::new(path.join(&moc.uid).with_extension("md"), logger.hollow());
+    let redacted = privacy == Privacy::Redact && moc.is_private()?;
+    let mut scribe = Scribe::new(path.join(redactions.name(path, &moc.uid, redacted)).with_extension("md"))?;
+    if redacted {
+        export_redacted(&mut scribe)?;
+        return scribe.finish();
+    }
 
     // Tags, title and description
-    scribe_tags(moc.tags(logger.hollow()), &mut scribe);
-    scribe_write!((scribe) "# ", moc.title(logger.hollow()), "\n");
-    scribe.write_line("---");
-    scribe_write!((scribe) "**Description:** ", moc.description(logger.hollow()), "\n\n");
+    let private = *moc.private()?;
+    scribe_tags(moc.tags()?, private, &mut scribe)?;
+    scribe_write!((scribe) "# ", moc.title()?, "\n");
+    scribe.write_line("---")?;
+    scribe_write!((scribe) "**Description:** ", moc.description()?, "\n\n");
 
     // Notes
-    let notes = moc.notes(logger.hollow());
-    if notes.len() > 0 {
-        scribe.write_line("## Notes");
-        notes.iter().for_each(|x| scribe_write!((scribe) "- ", x, "\n"));  
+    let notes = moc.notes()?;
+    let mut notes_header_written_to: bool = false;
+    if !notes.is_empty() {
+        notes_header_written_to = true;
+        scribe.write_line("## Notes")?;
+        for x in notes.iter() { scribe_write!((scribe) "- ", x, "\n") }
     }
 
     // Collections' notes
-    moc.collections(logger.hollow()).iter_mut().for_each(|collection| {
-        let title = collection.title(logger.hollow()).clone();
-        let notes = collection.notes(logger.hollow());
-        if notes.len() > 0 {
+    for collection in moc.collections()?.iter_mut() {
+        let title = collection.title()?.clone();
+        let notes = collection.notes()?;
+        if !notes.is_empty() {
+            if !notes_header_written_to { scribe.write_line("## Notes")?; notes_header_written_to = true; }
             scribe_write!((scribe) "- #### ", &title, "\n");
-            notes.iter().for_each(|x| scribe_write!((scribe) "\t- ", x, "\n"));
+            for x in notes.iter() { scribe_write!((scribe) "\t- ", x, "\n") }
         } collection.clear_cache();
-    });
-    scribe.write_line("---");
+    }
+    scribe.write_line("---")?;
 
     // Collections
-    moc.collections(logger.hollow()).iter_mut().for_each(|x| export_collection_content(&mut scribe, x, archive, logger.hollow()));
+    for x in moc.collections()?.iter_mut() { export_collection_content(path, &mut scribe, x, archive, privacy, redactions, logger.hollow())? }
 
     moc.clear_cache();
+    scribe.finish()
 }
 
-fn export_collection_content(scribe: &mut Scribe<impl Logger>, collection: &mut Collection, archive: &Archive, logger: impl Logger) {
-    let tags = collection.include(logger.hollow());
+#[allow(clippy::too_many_arguments)]
+fn export_collection_content(path: &Path, scribe: &mut Scribe, collection: &mut Collection, archive: &Archive, privacy: Privacy, redactions: &Redactions, logger: impl Logger) -> Result<()> {
+    let linked = linked(collection, archive, privacy, logger.hollow())?;
+    scribe_write!((scribe) "## ", collection.title()?, "\n");
+    scribe_collection_marker(scribe, collection)?;
+    if linked.is_empty() { return Ok(()); }
+
+    // Collections of a sibling diary link to (and export) its entries in a sub-folder of the vault
+    if let Some((diary, sibling)) = &linked.diary {
+        let path = path.join(diary);
+        std::fs::create_dir_all(&path).with_context(|| format!("While creating the export folder of diary '{diary}'"))?;
+        for (i, uid) in linked.entries.iter().enumerate() {
+            let mut entry = sibling.get_entry(uid.clone())?;
+            let redacted = privacy == Privacy::Redact && entry.is_private()?;
+            let shown = if redacted { None } else { Some((entry.title()?.clone(), entry.description()?.clone(), entry.notes()?.clone())) };
+            scribe_link(scribe, i, &format!("{diary}/{}", redactions.name(&path, &entry.uid, redacted)), shown)?;
+            entry.clear_cache();
+            export_entry(&path, &mut entry, privacy, redactions, logger.hollow())?;
+        }
+
+        return Ok(());
+    }
 
-    let moc_uids = search::search_strict(tags, archive.list_mocs(logger.hollow()), logger.hollow());
-    let mut entry_uids = search::search_strict(tags, archive.list_entries(logger.hollow()), logger.hollow());
+    for (i, uid) in linked.mocs.into_iter().enumerate() {
+        let mut moc = archive.get_moc(uid)?;
+        let redacted = privacy == Privacy::Redact && moc.is_private()?;
+        let shown = if redacted { None } else { Some((moc.title()?.clone(), moc.description()?.clone(), moc.notes()?.clone())) };
+        scribe_link(scribe, i, &redactions.name(path, &moc.uid, redacted), shown)?;
+        moc.clear_cache();
+    }
 
-    if moc_uids.is_empty() && entry_uids.is_empty() { return; }
-    scribe_write!((scribe) "## ", collection.title(logger.hollow()), "\n");
+    for (i, uid) in linked.entries.into_iter().enumerate() {
+        let mut entry = archive.get_entry(uid)?;
+        let redacted = privacy == Privacy::Redact && entry.is_private()?;
+        let shown = if redacted { None } else { Some((entry.title()?.clone(), entry.description()?.clone(), entry.notes()?.clone())) };
+        scribe_link(scribe, i, &redactions.name(path, &entry.uid, redacted), shown)?;
+        entry.clear_cache();
+    }
 
-    entry_uids = sort_uids(&entry_uids, logger.hollow()).to_vec(); // Sorting stuff
+    Ok(())
+}
 
-    moc_uids.into_iter()
-        .map(|x| archive.get_moc(x, logger.hollow()).unwrap())
-        .enumerate()
-        .for_each(|(i, mut entry)| {
-            scribe_write!((scribe) &(i + 1).to_string(), ". \\[[", entry.title(logger.hollow()), "](", &entry.uid, ")\\] ", entry.description(logger.hollow()), &format!(" `notes: {:?}`\n", entry.notes(logger.hollow())));
-            entry.clear_cache();
-        });
+/// Writes what a collection includes (its tags, query and diary) as a toml inline table in a hidden comment
+fn scribe_collection_marker(scribe: &mut Scribe, collection: &mut Collection) -> Result<()> {
+    let mut spec = vec![format!("include = {}", toml::Value::from(collection.include()?.to_vec()))];
+    if let Some(query) = collection.query()? { spec.push(format!("query = {}", toml::Value::from(query.clone()))) }
+    if let Some(diary) = collection.diary()? { spec.push(format!("diary = {}", toml::Value::from(diary.clone()))) }
+    scribe_write!((scribe) COLLECTION_MARKER, "{ ", &spec.join(", "), " }%%\n");
+    Ok(())
+}
 
-    entry_uids.into_iter()
-        .map(|x| archive.get_entry(x, logger.hollow()).unwrap())
-        .enumerate()
-        .for_each(|(i, mut entry)| {
-            scribe_write!((scribe) &(i + 1).to_string(), ". \\[[", entry.title(logger.hollow()), "](", &entry.uid, ")\\] ", entry.description(logger.hollow()), &format!(" `notes: {:?}`\n", entry.notes(logger.hollow())));
-            entry.clear_cache();
-        });
+/// Writes a collection's numbered link to an entry or moc along with its title, description and notes (or just the link if it's redacted)
+fn scribe_link(scribe: &mut Scribe, i: usize, link: &str, shown: Option<(String, String, Box<[String]>)>) -> Result<()> {
+    match shown {
+        Some((title, description, notes)) => scribe_write!((scribe) &(i + 1).to_string(), ". \\[[", &title, "](", link, ")\\] ", &description, &format!(" `notes: {notes:?}`\n")),
+        None => scribe_write!((scribe) &(i + 1).to_string(), ". \\[[Redacted](", link, ")\\]\n"),
+    }
+    Ok(())
+}
+
+/// The mocs and entries a collection links to
+pub(crate) struct Linked {
+    /// The sibling diary (and its archive) the entries are from, if not this one
+    pub diary: Option<(String, Archive)>,
+    pub mocs: Vec<String>,
+    /// Sorted by date
+    pub entries: Vec<String>,
+}
+
+impl Linked {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.mocs.is_empty() && self.entries.is_empty()
+    }
+}
+
+/// Finds the mocs and entries a collection links to, leaving out private ones if they're omitted (redacted ones are linked to by their opaque names)
+pub(crate) fn linked(collection: &mut Collection, archive: &Archive, privacy: Privacy, logger: impl Logger) -> Result<Linked> {
+    let tags = collection.include()?.clone();
+    let query = match collection.query()? {
+        Some(x) => Some(query::parse(x)?),
+        None => None,
+    };
+
+    // Collections of a sibling diary only link to its entries
+    if let Some(diary) = collection.diary()?.clone() {
+        let sibling = Archive::load_shared(&archive.home().with_diary(Some(&diary)), logger.hollow())?;
+        let mut entry_uids = if tags.is_empty() {
+            sibling.list_entries(logger.hollow())?.into_iter().map(|x| x.uid).collect()
+        } else {
+            index::search(&sibling, Group::Tags, &tags, true, false, logger.hollow())?
+        };
+        if let Some(query) = &query {
+            entry_uids = filter_uids(query, entry_uids, |x| sibling.get_entry(x))?;
+        }
+        if privacy == Privacy::Omit {
+            entry_uids = public_uids(entry_uids, |x| sibling.get_entry(x))?;
+        }
+
+        let entries = sort_uids(&sibling, &entry_uids)?.to_vec();
+        return Ok(Linked { diary: Some((diary, sibling)), mocs: Vec::new(), entries });
+    }
+
+    let (mut moc_uids, mut entry_uids) = if tags.is_empty() {
+        (
+            archive.list_mocs(logger.hollow())?.into_iter().map(|x| x.uid).collect(),
+            archive.list_entries(logger.hollow())?.into_iter().map(|x| x.uid).collect(),
+        )
+    } else {
+        (
+            index::search(archive, Group::Tags, &tags, true, true, logger.hollow())?,
+            index::search(archive, Group::Tags, &tags, true, false, logger.hollow())?,
+        )
+    };
+
+    if let Some(query) = &query {
+        moc_uids = filter_uids(query, moc_uids, |x| archive.get_moc(x))?;
+        entry_uids = filter_uids(query, entry_uids, |x| archive.get_entry(x))?;
+    }
+    if privacy == Privacy::Omit {
+        moc_uids = public_uids(moc_uids, |x| archive.get_moc(x))?;
+        entry_uids = public_uids(entry_uids, |x| archive.get_entry(x))?;
+    }
+
+    let entries = sort_uids(archive, &entry_uids)?.to_vec(); // Sorting stuff
+    Ok(Linked { diary: None, mocs: moc_uids, entries })
 }
 
-fn export_section_content(scribe: &mut Scribe<impl Logger>, section: &mut Section, logger: impl Logger) {
-    scribe_write!((scribe) "### ", section.title(logger.hollow()), "\n");
-    let content = section.content(logger.hollow()).trim_end_matches('\n').split('\n');
-    content.for_each(|x| {
+fn filter_uids<T: Searchable>(query: &Query, uids: Vec<String>, get: impl Fn(String) -> Result<T>) -> Result<Vec<String>> {
+    query::filter(query, uids.into_iter().map(get).collect::<Result<_>>()?)
+}
+
+/// Keeps the uids of the items that aren't private
+fn public_uids<T: Searchable>(uids: Vec<String>, get: impl Fn(String) -> Result<T>) -> Result<Vec<String>> {
+    let items = uids.into_iter().map(get).collect::<Result<Vec<_>>>()?;
+    Ok(retain_public(items)?.iter().map(|x| x.get_uid()).collect())
+}
+
+fn export_section_content(scribe: &mut Scribe, section: &mut Section, privacy: Privacy) -> Result<()> {
+    if privacy.is_public() && *section.private()? {
+        if privacy == Privacy::Redact {
+            scribe.write_line("### Redacted")?;
+            scribe_write!((scribe) "> ", REDACTED, "\n");
+        }
+        section.clear_cache();
+        return Ok(());
+    }
+
+    scribe_write!((scribe) "### ", section.title()?);
+    if *section.private()? { scribe.write(PRIVATE_MARKER)? }
+    scribe.new_line()?;
+    let content = section.content()?.trim_end_matches('\n').split('\n');
+    for x in content {
         scribe_write!((scribe) "> ", x, "\n");
-    });
+    }
     section.clear_cache();
+    Ok(())
 }
 
-fn scribe_tags(tags: &[String], scribe: &mut Scribe<impl Logger>) {
-    scribe.write_line("---");
-    scribe.write("tags:\n  - obsidian-md\n  - diary-cli\n");
-    tags.iter().for_each(|x| scribe_write!((scribe) "  - ", x, "\n"));
-    scribe.write_line("---");
+fn scribe_tags(tags: &[String], private: bool, scribe: &mut Scribe) -> Result<()> {
+    scribe.write_line("---")?;
+    scribe.write("tags:\n  - obsidian-md\n  - diary-cli\n")?;
+    for x in tags.iter() { scribe_write!((scribe) "  - ", x, "\n") }
+    if private { scribe.write_line("private: true")? }
+    scribe.write_line("---")
 }
 
-fn scribe_tags_n_date(tags: &[String], date: &[u16; 3], scribe: &mut Scribe<impl Logger>) {
-    scribe.write_line("---");
-    scribe.write("tags:\n  - obsidian-md\n  - diary-cli\n");
-    tags.iter().for_each(|x| scribe_write!((scribe) "  - ", x, "\n"));
-    scribe.write(&format!("date: {0}-{1}-{2}\n", date[2], date[1], date[0]));
-    scribe.write_line("---");
-}
\ No newline at end of file
+fn scribe_tags_n_date(tags: &[String], date: &NaiveDate, private: bool, scribe: &mut Scribe) -> Result<()> {
+    scribe.write_line("---")?;
+    scribe.write("tags:\n  - obsidian-md\n  - diary-cli\n")?;
+    for x in tags.iter() { scribe_write!((scribe) "  - ", x, "\n") }
+    scribe.write(&format!("date: {}\n", date.format("%Y-%-m-%-d")))?;
+    if private { scribe.write_line("private: true")? }
+    scribe.write_line("---")
+}
@@ -0,0 +1,349 @@
+use std::{collections::BTreeMap, fs, path::Path};
+use chrono::{Datelike, NaiveDate};
+use pulldown_cmark::{html::push_html, Event, Options, Parser};
+use soulog::*;
+use super::{has_private, linked, select, warn_private, Privacy, Redactions, REDACTED};
+use crate::{archive::Archive, entry::{Entry, Section}, moc::{MOC, Collection}, scribe_write, search::Searchable, sort::sort_uids, Context, Home, Result, Scribe};
+
+/// The stylesheet every page of the website links to
+const STYLESHEET: &str = "\
+body { margin: 0; background: #fbfaf7; color: #222; font: 17px/1.6 Georgia, 'Times New Roman', serif; }
+nav { padding: 0.8em 1.5em; background: #2f3b45; }
+nav a { color: #f1efe9; margin-right: 1.2em; text-decoration: none; font-family: sans-serif; }
+main { max-width: 46em; margin: 0 auto; padding: 1em 1.5em 3em; }
+a { color: #2a6496; }
+h1, h2, h3 { line-height: 1.25; }
+.date, .description { color: #666; }
+.tags { list-style: none; padding: 0; }
+.tags li { display: inline-block; margin: 0 0.4em 0.4em 0; padding: 0 0.6em; border-radius: 1em; background: #e7e3d8; font: 14px sans-serif; }
+.tags a { color: inherit; text-decoration: none; }
+.listing li { margin-bottom: 0.4em; }
+.listing .date { font-family: monospace; margin-right: 0.6em; }
+section { margin-top: 2em; }
+.redacted { color: #888; }
+blockquote { margin-left: 0; padding-left: 1em; border-left: 3px solid #ccc; color: #555; }
+pre, code { background: #efece4; font-size: 15px; }
+pre { padding: 0.8em; overflow-x: auto; }
+";
+
+/// A link to an exported entry or moc, as shown on the index and tag pages
+struct Listing {
+    title: String,
+    description: String,
+    date: Option<NaiveDate>,
+    /// Relative to the root of the website
+    href: String,
+}
+
+pub fn export_html(strict: bool, tags: Option<Vec<String>>, query: Option<String>, privacy: Privacy, path: String, home: &Home, mut logger: impl Logger) -> Result<()> {
+    log!((logger) Export("Exporting archive as a website to path '{path}'..."));
+    let (archive, mut entries, mut mocs) = select(strict, tags, query, privacy, home, logger.hollow())?;
+
+    let path = Path::new(&path);
+    for dir in ["entries", "mocs", "tags"] {
+        fs::create_dir_all(path.join(dir)).with_context(|| format!("While creating the '{dir}' folder of the website"))?;
+    }
+    fs::write(path.join("style.css"), STYLESHEET).context("While writing the website's stylesheet")?;
+
+    // Export em, keeping track of what to list on the index and tag pages
+    let redactions = Redactions::default();
+    let mut private = 0;
+    let mut listed = BTreeMap::new();
+    let mut tagged: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut listed_mocs = Vec::new();
+    for entry in entries.iter_mut() {
+        if !privacy.is_public() && has_private(entry)? { private += 1 }
+        if !(privacy.is_public() && entry.is_private()?) {
+            let uid = entry.uid.clone();
+            for tag in entry.tags()?.iter() { tagged.entry(tag.clone()).or_default().push(uid.clone()) }
+            listed.insert(entry.uid.clone(), listing(entry, "entries")?);
+        }
+        export_entry(&path.join("entries"), "../", true, entry, privacy, &redactions, logger.hollow())?;
+    }
+    for moc in mocs.iter_mut() {
+        if !privacy.is_public() && moc.is_private()? { private += 1 }
+        if !(privacy.is_public() && moc.is_private()?) {
+            let uid = moc.uid.clone();
+            for tag in moc.tags()?.iter() { tagged.entry(tag.clone()).or_default().push(uid.clone()) }
+            listed_mocs.push((moc.uid.clone(), Listing {
+                title: moc.title()?.clone(),
+                description: moc.description()?.clone(),
+                date: None,
+                href: format!("mocs/{}.html", file_name(&moc.uid)),
+            }));
+        }
+        export_moc(path, moc, &archive, privacy, &redactions, logger.hollow())?;
+    }
+    warn_private(private, logger.hollow());
+
+    // The chronological index
+    log!((logger) Export("Writing the website's index..."));
+    let uids = listed.keys().cloned().collect::<Vec<_>>();
+    let sorted = sort_uids(&archive, &uids)?;
+    let mut body = String::from("<h1>Diary</h1>\n");
+    if !listed_mocs.is_empty() {
+        body.push_str("<h2>Maps of Contents</h2>\n");
+        body.push_str(&list(listed_mocs.iter().map(|(_, x)| x), ""));
+    }
+    let year = |uid: &String| listed[uid].date.map(|x| x.year());
+    for group in sorted.chunk_by(|a, b| year(a) == year(b)) {
+        body.push_str(&format!("<h2>{}</h2>\n", year(&group[0]).unwrap_or_default()));
+        body.push_str(&list(group.iter().map(|x| &listed[x]), ""));
+    }
+    page(&path.join("index.html"), "", "Diary", &body)?;
+
+    // Per-tag pages (and the page listing them)
+    log!((logger) Export("Writing the website's tag pages..."));
+    let mut body = String::from("<h1>Tags</h1>\n<ul class=\"listing\">\n");
+    for (tag, uids) in tagged.iter() {
+        body.push_str(&format!("<li><a href=\"tags/{}.html\">{}</a> ({})</li>\n", file_name(tag), escape(tag), uids.len()));
+
+        let mocs = listed_mocs.iter().filter(|(uid, _)| uids.contains(uid)).map(|(_, x)| x);
+        let entries = sorted.iter().filter(|x| uids.contains(x)).map(|x| &listed[x]);
+        let mut tag_body = format!("<h1>Tagged '{}'</h1>\n", escape(tag));
+        tag_body.push_str(&list(mocs.chain(entries), "../"));
+        page(&path.join("tags").join(file_name(tag)).with_extension("html"), "../", tag, &tag_body)?;
+    }
+    body.push_str("</ul>\n");
+    page(&path.join("tags.html"), "", "Tags", &body)?;
+
+    log!((logger.vital) Export("Successfully exported all specified items as a website") as Log);
+    Ok(())
+}
+
+/// Writes the page of an entry into a folder, with `root` leading back to the root of the website
+#[allow(clippy::too_many_arguments)]
+fn export_entry(path: &Path, root: &str, link_tags: bool, entry: &mut Entry, privacy: Privacy, redactions: &Redactions, mut logger: impl Logger) -> Result<()> {
+    if privacy == Privacy::Omit && entry.is_private()? { return Ok(()) }
+    log!((logger) Export("Exporting entry of uid '{}' as a page...", entry.uid));
+    let redacted = privacy == Privacy::Redact && entry.is_private()?;
+    let path = path.join(redactions.name(path, &file_name(&entry.uid), redacted)).with_extension("html");
+    if redacted {
+        return page(&path, root, "Redacted", &format!("<h1>Redacted</h1>\n<div class=\"redacted\">{}</div>\n", markdown(REDACTED)));
+    }
+
+    // Title, date, description and tags
+    let mut body = format!("<article>\n<h1>{}</h1>\n", escape(entry.title()?));
+    body.push_str(&format!("<p class=\"date\">{}</p>\n", entry.date()?.format("%A, %-d %B %Y")));
+    body.push_str(&format!("<p class=\"description\">{}</p>\n", escape(entry.description()?)));
+    body.push_str(&tags(entry.tags()?, root, link_tags));
+
+    // Notes (and those of its sections)
+    let mut notes = notes(entry.notes()?);
+    for section in entry.sections()?.iter_mut() {
+        if privacy.is_public() && *section.private()? { section.clear_cache(); continue }
+        if !section.notes()?.is_empty() {
+            notes.push_str(&format!("<h3>{}</h3>\n", escape(section.title()?)));
+            notes.push_str(&self::notes(section.notes()?));
+        } section.clear_cache();
+    }
+    if !notes.is_empty() { body.push_str(&format!("<h2>Notes</h2>\n{notes}")) }
+    body.push_str("<hr>\n");
+
+    // Sections
+    for section in entry.sections()?.iter_mut() { body.push_str(&section_content(section, privacy)?) }
+    body.push_str("</article>\n");
+
+    let title = entry.title()?.clone();
+    entry.clear_cache();
+    page(&path, root, &title, &body)
+}
+
+fn section_content(section: &mut Section, privacy: Privacy) -> Result<String> {
+    let html = if privacy.is_public() && *section.private()? {
+        match privacy {
+            Privacy::Redact => format!("<section class=\"redacted\">\n<h2>Redacted</h2>\n{}</section>\n", markdown(REDACTED)),
+            _ => String::new(),
+        }
+    } else {
+        format!("<section>\n<h2>{}</h2>\n{}</section>\n", escape(section.title()?), markdown(section.content()?))
+    };
+    section.clear_cache();
+    Ok(html)
+}
+
+fn export_moc(path: &Path, moc: &mut MOC, archive: &Archive, privacy: Privacy, redactions: &Redactions, mut logger: impl Logger) -> Result<()> {
+    if privacy == Privacy::Omit && moc.is_private()? { return Ok(()) }
+    log!((logger) Export("Exporting moc of uid '{}' as a page...", moc.uid));
+    let redacted = privacy == Privacy::Redact && moc.is_private()?;
+    let page_path = path.join("mocs").join(redactions.name(&path.join("mocs"), &file_name(&moc.uid), redacted)).with_extension("html");
+    if redacted {
+        return page(&page_path, "../", "Redacted", &format!("<h1>Redacted</h1>\n<div class=\"redacted\">{}</div>\n", markdown(REDACTED)));
+    }
+
+    // Title, description, tags and notes
+    let mut body = format!("<article>\n<h1>{}</h1>\n", escape(moc.title()?));
+    body.push_str(&format!("<p class=\"description\">{}</p>\n", escape(moc.description()?)));
+    body.push_str(&tags(moc.tags()?, "../", true));
+    let notes = notes(moc.notes()?);
+    if !notes.is_empty() { body.push_str(&format!("<h2>Notes</h2>\n{notes}")) }
+    body.push_str("<hr>\n");
+
+    // Collections
+    for x in moc.collections()?.iter_mut() { body.push_str(&collection_content(path, x, archive, privacy, redactions, logger.hollow())?) }
+    body.push_str("</article>\n");
+
+    let title = moc.title()?.clone();
+    moc.clear_cache();
+    page(&page_path, "../", &title, &body)
+}
+
+fn collection_content(path: &Path, collection: &mut Collection, archive: &Archive, privacy: Privacy, redactions: &Redactions, logger: impl Logger) -> Result<String> {
+    let linked = linked(collection, archive, privacy, logger.hollow())?;
+    if linked.is_empty() { collection.clear_cache(); return Ok(String::new()); }
+    let mut html = format!("<section>\n<h2>{}</h2>\n{}", escape(collection.title()?), notes(collection.notes()?));
+    collection.clear_cache();
+
+    // Collections of a sibling diary link to (and export) its entries in a folder of their own
+    let mut listings = Vec::with_capacity(linked.mocs.len() + linked.entries.len());
+    if let Some((diary, sibling)) = &linked.diary {
+        let folder = format!("diaries/{}", file_name(diary));
+        let path = path.join(&folder);
+        fs::create_dir_all(&path).with_context(|| format!("While creating the website folder of diary '{diary}'"))?;
+        for uid in linked.entries.iter() {
+            let mut entry = sibling.get_entry(uid.clone())?;
+            let redacted = privacy == Privacy::Redact && entry.is_private()?;
+            listings.push(match redacted {
+                true => redacted_listing(&folder, &redactions.name(&path, &file_name(&entry.uid), true)),
+                false => listing(&mut entry, &folder)?,
+            });
+            export_entry(&path, "../../", false, &mut entry, privacy, redactions, logger.hollow())?;
+        }
+    } else {
+        for uid in linked.mocs.into_iter() {
+            let mut moc = archive.get_moc(uid)?;
+            if privacy == Privacy::Redact && moc.is_private()? {
+                listings.push(redacted_listing("mocs", &redactions.name(&path.join("mocs"), &file_name(&moc.uid), true)));
+                moc.clear_cache();
+                continue;
+            }
+            listings.push(Listing {
+                title: moc.title()?.clone(),
+                description: moc.description()?.clone(),
+                date: None,
+                href: format!("mocs/{}.html", file_name(&moc.uid)),
+            });
+            moc.clear_cache();
+        }
+        for uid in linked.entries.into_iter() {
+            let mut entry = archive.get_entry(uid)?;
+            listings.push(match privacy == Privacy::Redact && entry.is_private()? {
+                true => redacted_listing("entries", &redactions.name(&path.join("entries"), &file_name(&entry.uid), true)),
+                false => listing(&mut entry, "entries")?,
+            });
+        }
+    }
+
+    html.push_str(&list(listings.iter(), "../"));
+    html.push_str("</section>\n");
+    Ok(html)
+}
+
+/// Lists an entry whose page is in the specified folder of the website
+fn listing(entry: &mut Entry, folder: &str) -> Result<Listing> {
+    let listing = Listing {
+        title: entry.title()?.clone(),
+        description: entry.description()?.clone(),
+        date: Some(*entry.date()?),
+        href: format!("{folder}/{}.html", file_name(&entry.uid)),
+    };
+    entry.clear_cache();
+    Ok(listing)
+}
+
+/// Lists a redacted entry or moc by nothing but the opaque name of its page in the specified folder
+fn redacted_listing(folder: &str, name: &str) -> Listing {
+    Listing {
+        title: "Redacted".into(),
+        description: String::new(),
+        date: None,
+        href: format!("{folder}/{name}.html"),
+    }
+}
+
+/// Writes a full html page, with `root` leading back to the root of the website
+fn page(path: &Path, root: &str, title: &str, body: &str) -> Result<()> {
+    let mut scribe = Scribe::new(path)?;
+    scribe_write!((scribe)
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+        "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+        "<title>", &escape(title), "</title>\n",
+        "<link rel=\"stylesheet\" href=\"", root, "style.css\">\n</head>\n<body>\n",
+        "<nav><a href=\"", root, "index.html\">Index</a><a href=\"", root, "tags.html\">Tags</a></nav>\n",
+        "<main>\n", body, "</main>\n</body>\n</html>\n",
+    );
+    scribe.finish()
+}
+
+fn list<'a>(listings: impl Iterator<Item = &'a Listing>, root: &str) -> String {
+    let mut html = String::from("<ul class=\"listing\">\n");
+    for x in listings {
+        html.push_str("<li>");
+        if let Some(date) = x.date { html.push_str(&format!("<span class=\"date\">{date}</span>")) }
+        html.push_str(&format!("<a href=\"{root}{}\">{}</a>", x.href, escape(&x.title)));
+        if !x.description.is_empty() { html.push_str(&format!(" &mdash; {}", escape(&x.description))) }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn tags(tags: &[String], root: &str, link: bool) -> String {
+    if tags.is_empty() { return String::new() }
+    let mut html = String::from("<ul class=\"tags\">\n");
+    for x in tags.iter() {
+        match link {
+            true => html.push_str(&format!("<li><a href=\"{root}tags/{}.html\">{}</a></li>\n", file_name(x), escape(x))),
+            false => html.push_str(&format!("<li>{}</li>\n", escape(x))),
+        }
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn notes(notes: &[String]) -> String {
+    if notes.is_empty() { return String::new() }
+    let mut html = String::from("<ul>\n");
+    for x in notes.iter() { html.push_str(&format!("<li>{}</li>\n", escape(x))) }
+    html.push_str("</ul>\n");
+    html
+}
+
+/// Renders markdown as html; raw html is shown as text so the contents can't break the page
+fn markdown(text: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES;
+    let parser = Parser::new_ext(text, options).map(|event| match event {
+        Event::Html(x) | Event::InlineHtml(x) => Event::Text(x),
+        x => x,
+    });
+    let mut html = String::new();
+    push_html(&mut html, parser);
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A file name (also safe to link to as is) for a uid, tag or diary; anything other than letters, digits, `-` and `_` is escaped as `~xx`
+pub fn file_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => result.push(byte as char),
+            _ => result.push_str(&format!("~{byte:02x}")),
+        }
+    }
+    result
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashMap, fs, path::Path};
+use serde_json::{json, Map, Value};
+use soulog::*;
+use super::{select, warn_private, has_private, Privacy, REDACTED};
+use crate::{entry::{Entry, Section, DATE_FORMAT}, moc::{MOC, Collection}, search::Searchable, sort::sort_uids, Context, Error, Home, Result, Scribe};
+
+/// The version of the json schema; bumped whenever it changes in a way older readers can't handle
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// What the `format` field of exports is always set to
+pub const FORMAT_NAME: &str = "diary-cli";
+
+/// What the `privacy` field of exports is set to, for how their private material was exported
+fn privacy_name(privacy: Privacy) -> &'static str {
+    match privacy {
+        Privacy::Show => "shown",
+        Privacy::Redact => "redacted",
+        Privacy::Omit => "omitted",
+    }
+}
+
+/// Exports the archive as one json document, or as ndjson (a header line, then an entry or moc per line) if `lines`
+#[allow(clippy::too_many_arguments)]
+pub fn export_json(lines: bool, strict: bool, tags: Option<Vec<String>>, query: Option<String>, privacy: Privacy, path: String, home: &Home, mut logger: impl Logger) -> Result<()> {
+    log!((logger) Export("Exporting archive as {} to path '{path}'...", if lines { "ndjson" } else { "json" }));
+    let (archive, mut entries, mut mocs) = select(strict, tags, query, privacy, home, logger.hollow())?;
+
+    // Entries are exported in chronological order
+    let uids = entries.iter().map(|x| x.uid.clone()).collect::<Vec<_>>();
+    let order = sort_uids(&archive, &uids)?.iter().enumerate().map(|(i, x)| (x.clone(), i)).collect::<HashMap<_, _>>();
+    entries.sort_by_key(|x| order.get(&x.uid).copied());
+
+    let mut private = 0;
+    let mut entries_json = Vec::with_capacity(entries.len());
+    for entry in entries.iter_mut() {
+        if !privacy.is_public() && has_private(entry)? { private += 1 }
+        log!((logger) Export("Exporting entry of uid '{}'...", entry.uid));
+        entries_json.push(entry_json(entry, privacy)?);
+    }
+    let mut mocs_json = Vec::with_capacity(mocs.len());
+    for moc in mocs.iter_mut() {
+        if !privacy.is_public() && moc.is_private()? { private += 1 }
+        log!((logger) Export("Exporting moc of uid '{}'...", moc.uid));
+        mocs_json.push(moc_json(moc, privacy)?);
+    }
+    warn_private(private, logger.hollow());
+
+    let mut scribe = Scribe::new(&path)?;
+    if lines {
+        scribe.write_line(&json!({ "kind": "header", "format": FORMAT_NAME, "version": SCHEMA_VERSION, "privacy": privacy_name(privacy) }).to_string())?;
+        for (kind, items) in [("entry", entries_json), ("moc", mocs_json)] {
+            for mut item in items {
+                item["kind"] = kind.into();
+                scribe.write_line(&item.to_string())?;
+            }
+        }
+    } else {
+        let document = json!({ "format": FORMAT_NAME, "version": SCHEMA_VERSION, "privacy": privacy_name(privacy), "entries": entries_json, "mocs": mocs_json });
+        scribe.write_line(&serde_json::to_string_pretty(&document).map_err(|err| Error::Validation(format!("While encoding json: {err}")))?)?;
+    }
+    scribe.finish()?;
+
+    log!((logger.vital) Export("Successfully exported all specified items") as Log);
+    Ok(())
+}
+
+/// A placeholder for a redacted section
+fn redacted_section() -> Value {
+    json!({ "title": "Redacted", "notes": [], "contents": REDACTED, "private": true, "redacted": true })
+}
+
+/// Items are marked `redacted` unless they're exported in full (so `import` doesn't replace the real ones with what's left)
+fn entry_json(entry: &mut Entry, privacy: Privacy) -> Result<Value> {
+    let date = entry.date()?.format(DATE_FORMAT).to_string();
+    if privacy == Privacy::Redact && entry.is_private()? {
+        return Ok(json!({ "uid": entry.uid, "date": date, "title": "Redacted", "description": "", "tags": [], "notes": [], "private": true, "redacted": true, "sections": [redacted_section()] }));
+    }
+
+    let mut sections = Vec::new();
+    let mut redacted = false;
+    for section in entry.sections()?.iter_mut() {
+        match section_json(section, privacy)? {
+            Some(x) => {
+                redacted |= x["redacted"] == true;
+                sections.push(x);
+            },
+            None => redacted = true,
+        }
+    }
+    let json = json!({
+        "uid": entry.uid,
+        "date": date,
+        "title": entry.title()?,
+        "description": entry.description()?,
+        "tags": entry.tags()?,
+        "notes": entry.notes()?,
+        "private": entry.private()?,
+        "redacted": redacted,
+        "sections": sections,
+    });
+    entry.clear_cache();
+    Ok(json)
+}
+
+fn section_json(section: &mut Section, privacy: Privacy) -> Result<Option<Value>> {
+    let json = match (privacy.is_public() && *section.private()?, privacy) {
+        (true, Privacy::Omit) => None,
+        (true, _) => Some(redacted_section()),
+        (false, _) => Some(json!({
+            "title": section.title()?,
+            "notes": section.notes()?,
+            "contents": section.content()?,
+            "private": section.private()?,
+            "redacted": false,
+        })),
+    };
+    section.clear_cache();
+    Ok(json)
+}
+
+fn moc_json(moc: &mut MOC, privacy: Privacy) -> Result<Value> {
+    if privacy == Privacy::Redact && moc.is_private()? {
+        return Ok(json!({ "uid": moc.uid, "title": "Redacted", "description": "", "tags": [], "notes": [], "private": true, "redacted": true, "collections": [] }));
+    }
+
+    let collections = moc.collections()?.iter_mut().map(collection_json).collect::<Result<Vec<_>>>()?;
+    let json = json!({
+        "uid": moc.uid,
+        "title": moc.title()?,
+        "description": moc.description()?,
+        "tags": moc.tags()?,
+        "notes": moc.notes()?,
+        "private": moc.private()?,
+        "redacted": false,
+        "collections": collections,
+    });
+    moc.clear_cache();
+    Ok(json)
+}
+
+fn collection_json(collection: &mut Collection) -> Result<Value> {
+    let json = json!({
+        "title": collection.title()?,
+        "notes": collection.notes()?,
+        "include": collection.include()?,
+        "query": collection.query()?,
+        "diary": collection.diary()?,
+    });
+    collection.clear_cache();
+    Ok(json)
+}
+
+/// Reads a json or ndjson export back into the entry and moc configs (as they'd be committed) it was made from, each named by where in the file it is
+///
+/// Redacted items are read as the reason they can't be imported instead, as their private material isn't in the export
+pub fn read(path: &Path) -> Result<Vec<(String, std::result::Result<toml::Table, String>)>> {
+    let path_string = path.to_string_lossy().to_string();
+    let parse_error = |message: String| Error::Parse { path: path_string.clone(), message };
+    let contents = fs::read_to_string(path).with_context(|| format!("While reading '{path_string}'"))?;
+
+    // Ndjson starts with a header line
+    let first = contents.lines().find(|x| !x.trim().is_empty()).unwrap_or_default();
+    let is_ndjson = serde_json::from_str::<Value>(first).is_ok_and(|x| x["kind"] == "header");
+
+    let mut items = Vec::new();
+    if is_ndjson {
+        let mut lines = contents.lines().enumerate().filter(|(_, x)| !x.trim().is_empty());
+        let (_, header) = lines.next().unwrap_or_default();
+        check_header(&serde_json::from_str(header).map_err(|err| parse_error(format!("line 1: {err}")))?, &path_string)?;
+        for (i, line) in lines {
+            let item: Value = serde_json::from_str(line).map_err(|err| parse_error(format!("line {}: {err}", i + 1)))?;
+            let at = format!("{path_string}:{}", i + 1);
+            let is_moc = match item["kind"].as_str() {
+                Some("entry") => false,
+                Some("moc") => true,
+                _ => return Err(Error::Validation(format!("Item '{at}' must have a 'kind' of either 'entry' or 'moc'"))),
+            };
+            items.push((at.clone(), read_item(item, is_moc, &at)?));
+        }
+    } else {
+        let document: Value = serde_json::from_str(&contents).map_err(|err| parse_error(err.to_string()))?;
+        check_header(&document, &path_string)?;
+        for (key, is_moc) in [("entries", false), ("mocs", true)] {
+            let Some(list) = document.get(key) else { continue };
+            let list = list.as_array().ok_or_else(|| Error::Validation(format!("'{path_string}'s '{key}' must be an array")))?;
+            for (i, item) in list.iter().enumerate() {
+                let at = format!("{path_string} ({key} {i})");
+                items.push((at.clone(), read_item(item.clone(), is_moc, &at)?));
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+fn check_header(header: &Value, path: &str) -> Result<()> {
+    if header["format"] != FORMAT_NAME {
+        return Err(Error::Validation(format!("'{path}' isn't a diary-cli export (its 'format' must be '{FORMAT_NAME}')")));
+    }
+    match header["version"].as_u64() {
+        Some(x) if x <= SCHEMA_VERSION as u64 => Ok(()),
+        Some(x) => Err(Error::Validation(format!("'{path}' is of schema version {x}, newer than the {SCHEMA_VERSION} this version of diary-cli supports; update diary-cli to import it"))),
+        None => Err(Error::Validation(format!("'{path}' must have a numeric 'version'"))),
+    }
+}
+
+fn read_item(item: Value, is_moc: bool, at: &str) -> Result<std::result::Result<toml::Table, String>> {
+    Ok(match is_redacted(&item) {
+        true => Err("is redacted (its private material isn't in the export), so it would replace the real one".into()),
+        false => Ok(item_table(item, is_moc, at)?),
+    })
+}
+
+/// Returns if an exported entry or moc isn't exported in full (including exports from before items were marked, by their placeholder sections)
+fn is_redacted(item: &Value) -> bool {
+    let sections = item["sections"].as_array().map(|x| x.as_slice()).unwrap_or_default();
+    item["redacted"] == true || sections.iter().any(|x| x["redacted"] == true || x["contents"] == REDACTED)
+}
+
+/// Turns an exported entry or moc back into its config, refusing redacted ones
+fn item_table(item: Value, is_moc: bool, at: &str) -> Result<toml::Table> {
+    if is_redacted(&item) {
+        return Err(Error::Validation(format!("Item '{at}' is redacted, so it can't be turned back into a config")));
+    }
+    let Value::Object(mut item) = item else {
+        return Err(Error::Validation(format!("Item '{at}' must be an object")));
+    };
+    item.remove("kind");
+    item.remove("redacted");
+    let (name, list, list_name) = if is_moc { ("moc", "collections", "collection") } else { ("entry", "sections", "section") };
+    let mut list = item.remove(list).unwrap_or(Value::Array(Vec::new()));
+    for x in list.as_array_mut().into_iter().flatten() {
+        if let Some(x) = x.as_object_mut() { x.remove("redacted"); }
+    }
+
+    let mut table = toml::Table::new();
+    if is_moc { table.insert("is-moc".into(), true.into()); }
+    let mut fields = toml_table(item, at)?;
+    if let Some(date) = fields.get_mut("date") {
+        let parsed = date.as_str().and_then(|x| x.parse().ok());
+        *date = toml::Value::Datetime(parsed.ok_or_else(|| Error::Validation(format!("Item '{at}'s 'date' must be a `yyyy-mm-dd` date")))?);
+    }
+    table.insert(name.into(), fields.into());
+    table.insert(list_name.into(), toml_value(list, at)?.unwrap_or(toml::Value::Array(Vec::new())));
+    Ok(table)
+}
+
+fn toml_table(object: Map<String, Value>, at: &str) -> Result<toml::Table> {
+    let mut table = toml::Table::new();
+    for (key, value) in object {
+        // Absent optional attributes are exported as null
+        if let Some(value) = toml_value(value, at)? { table.insert(key, value); }
+    }
+    Ok(table)
+}
+
+fn toml_value(value: Value, at: &str) -> Result<Option<toml::Value>> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Bool(x) => x.into(),
+        Value::String(x) => x.into(),
+        Value::Number(x) => match x.as_i64() {
+            Some(x) => x.into(),
+            None => x.as_f64().unwrap_or_default().into(),
+        },
+        Value::Array(x) => x.into_iter()
+            .map(|x| toml_value(x, at)?.ok_or_else(|| Error::Validation(format!("Item '{at}' can't have nulls in its arrays"))))
+            .collect::<Result<Vec<_>>>()?
+            .into(),
+        Value::Object(x) => toml_table(x, at)?.into(),
+    }))
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use regex::{Regex, RegexBuilder};
+use soulog::*;
+use crate::{archive::Archive, search::Searchable, sort, index::{self, Group}, Error, Home, Result};
+
+/// The amount of characters of context shown on either side of a match
+pub const SNIPPET_CONTEXT: usize = 32;
+
+/// The field of an entry or moc that a match was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Description,
+    Note(usize),
+    SectionTitle(usize),
+    SectionNote(usize, usize),
+    SectionContent(usize),
+    CollectionTitle(usize),
+    CollectionNote(usize, usize),
+}
+
+impl Field {
+    /// Returns the index of the section the field belongs to (if any)
+    pub fn section(&self) -> Option<usize> {
+        use Field::*;
+        match self {
+            SectionTitle(i) | SectionNote(i, _) | SectionContent(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Field::*;
+        match self {
+            Title => write!(f, "title"),
+            Description => write!(f, "description"),
+            Note(i) => write!(f, "note {i}"),
+            SectionTitle(i) => write!(f, "section {i}, title"),
+            SectionNote(i, j) => write!(f, "section {i}, note {j}"),
+            SectionContent(i) => write!(f, "section {i}, content"),
+            CollectionTitle(i) => write!(f, "collection {i}, title"),
+            CollectionNote(i, j) => write!(f, "collection {i}, note {j}"),
+        }
+    }
+}
+
+/// A single occurrence of a pattern within an entry or moc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub uid: String,
+    pub field: Field,
+    /// Text leading up to the match
+    pub before: String,
+    /// The matched text itself
+    pub matched: String,
+    /// Text trailing the match
+    pub after: String,
+}
+
+impl Match {
+    /// Returns the snippet of the match highlighted for the console
+    pub fn highlighted(&self) -> String {
+        colour_format![none(&self.before), red(&self.matched), none(&self.after)]
+    }
+}
+
+/// Builds a pattern for finding text; plain text is matched literally unless `regex` is set
+pub fn pattern(pattern: &str, regex: bool, whole_word: bool, ignore_case: bool) -> std::result::Result<Regex, regex::Error> {
+    let mut pattern = if regex { pattern.to_string() } else { regex::escape(pattern) };
+    if whole_word { pattern = format!(r"\b(?:{pattern})\b") };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+}
+
+/// Finds every occurrence of a pattern in some text and pushes them onto the matches
+pub fn find_in(uid: &str, field: Field, text: &str, pattern: &Regex, matches: &mut Vec<Match>) {
+    for found in pattern.find_iter(text) {
+        if found.is_empty() { continue }; // empty matches would match everything
+
+        let before = &text[..found.start()];
+        let before_start = before.char_indices()
+            .rev()
+            .nth(SNIPPET_CONTEXT - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let rest = &text[found.end()..];
+        let after_end = rest.char_indices()
+            .nth(SNIPPET_CONTEXT)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+
+        let mut before = flatten(&before[before_start..]);
+        let mut after = flatten(&rest[..after_end]);
+        if before_start > 0 { before.insert_str(0, "...") };
+        if after_end < rest.len() { after.push_str("...") };
+
+        matches.push(Match {
+            uid: uid.to_string(),
+            field,
+            before,
+            matched: flatten(found.as_str()),
+            after,
+        });
+    }
+}
+
+/// Keeps snippets on a single line
+fn flatten(text: &str) -> String {
+    text.replace(['\n', '\r', '\t'], " ")
+}
+
+pub fn find(pattern: &Regex, items: Vec<impl Searchable>) -> Result<Vec<Match>> {
+    let mut matches = Vec::new();
+    for mut item in items {
+        matches.extend(item.find(pattern)?);
+    }
+    Ok(matches)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn find_command(raw_pattern: String, regex: bool, whole_word: bool, ignore_case: bool, show_entries: bool, show_mocs: bool, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let pattern = pattern(&raw_pattern, regex, whole_word, ignore_case)
+        .map_err(|err| Error::Validation(format!("Invalid pattern '{raw_pattern}': {err}")))?;
+    let archive = Archive::load_shared(home, logger.hollow())?;
+
+    // Whole words can be narrowed down with the archive's word index first
+    let words: Option<Vec<String>> = if whole_word && !regex {
+        Some(index::tokenize(&raw_pattern).collect())
+    } else { None };
+
+    // Searches both if neither is specified
+    let (show_entries, show_mocs) = if !show_entries && !show_mocs { (true, true) } else { (show_entries, show_mocs) };
+
+    let mut matches = Vec::new();
+    if show_entries {
+        log!((logger) Find("Searching entries for '{raw_pattern}'..."));
+        let mut uids = sort::sorted(&archive)?.into_vec();
+        if let Some(words) = &words {
+            let found = index::search(&archive, Group::Words, words, true, false, logger.hollow())?;
+            uids.retain(|x| found.contains(x));
+        }
+
+        let entries = uids.into_iter()
+            .map(|x| archive.get_entry(x))
+            .collect::<Result<Vec<_>>>()?;
+        matches.extend(find(&pattern, entries)?);
+    }
+
+    if show_mocs {
+        log!((logger) Find("Searching mocs for '{raw_pattern}'..."));
+        let mocs = match &words {
+            Some(words) => index::search(&archive, Group::Words, words, true, true, logger.hollow())?
+                .into_iter()
+                .map(|x| archive.get_moc(x))
+                .collect::<Result<Vec<_>>>()?,
+            None => archive.list_mocs(logger.hollow())?,
+        };
+        matches.extend(find(&pattern, mocs)?);
+    }
+
+    if matches.is_empty() {
+        log!((logger.vital) Find("No matches found for '{raw_pattern}'") as Inconvenience);
+        return Ok(());
+    }
+
+    for found in matches.iter() {
+        log!((logger.vital) Find("{}", colour_format![cyan(&found.uid), blue(" ("), none(&found.field.to_string()), blue("): "), none(&found.highlighted())]) as Result);
+    }
+    log!((logger.vital) Find("Found {} matches", matches.len()) as Log);
+    Ok(())
+}
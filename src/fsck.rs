@@ -0,0 +1,326 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use lazy_db::*;
+use soulog::*;
+use crate::{archive::{self, Archive}, entry::DATE_FORMAT, backups, crypt, history, index, list, transaction, Context, Error, Home};
+
+/// How an issue found by `fsck` is repaired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// Rewrites a list (at a path relative to the archive) from the elements it still has
+    List(String),
+    /// Renumbers a list of containers (like sections or collections) and rewrites its length
+    Containers(String),
+    /// Recommits an entry or moc from its latest revision
+    Restore { uid: String, is_moc: bool },
+    /// Rebuilds the sorted and unsorted order lists from the entries that exist
+    Order,
+    /// It can't be repaired automatically
+    Manual,
+}
+
+/// Something wrong with the archive, found by `fsck`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// The path (relative to the archive) of the container it is in
+    pub path: String,
+    pub message: String,
+    pub fix: Fix,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The numbered children of a list container, in order
+fn numbered(path: &Path, dirs: bool) -> crate::Result<Vec<u32>> {
+    let mut numbers = Vec::new();
+    for x in fs::read_dir(path).with_context(|| format!("While reading container '{}'", path.to_string_lossy()))? {
+        let x = x.context("While reading container element")?;
+        if x.file_type().context("While reading container element")?.is_dir() != dirs { continue }
+        if let Some(i) = x.file_name().to_str().and_then(|x| x.parse::<u32>().ok()) {
+            numbers.push(i);
+        }
+    }
+    numbers.sort();
+    Ok(numbers)
+}
+
+/// Scans an entry or moc (or the archive itself) for problems
+struct Scanner<'a> {
+    root: &'a Path,
+    issues: Vec<Issue>,
+}
+
+impl<'a> Scanner<'a> {
+    fn issue(&mut self, path: &str, message: String, fix: Fix) {
+        self.issues.push(Issue { path: path.to_string(), message, fix });
+    }
+
+    /// Checks that a piece of data is there and readable, returning if it is
+    fn data(&mut self, path: &str, key: &str, read: impl FnOnce(LazyData) -> Result<(), LDBError>, fix: &Fix) -> bool {
+        let ok = LazyData::load(self.root.join(path).join(key)).and_then(read).is_ok();
+        if !ok { self.issue(path, format!("'{key}' is missing or unreadable"), fix.clone()) }
+        ok
+    }
+
+    /// Checks that a list's length matches its elements, returning its length if it does
+    fn list(&mut self, path: &str, containers: bool, missing: &Fix) -> crate::Result<Option<u32>> {
+        let full = self.root.join(path);
+        if !full.is_dir() {
+            self.issue(path, "list is missing".into(), missing.clone());
+            return Ok(None);
+        }
+
+        let children = numbered(&full, containers)?;
+        let length = LazyData::load(full.join("length")).and_then(|x| x.collect_u32()).ok();
+        let fix = if containers { Fix::Containers(path.to_string()) } else { Fix::List(path.to_string()) };
+        match length {
+            Some(length) if children.iter().copied().eq(0..length) => return Ok(Some(length)),
+            Some(length) if children.len() != length as usize => self.issue(path, format!("list length is {length} but it has {} element(s)", children.len()), fix),
+            Some(length) => self.issue(path, format!("list elements aren't numbered from 0 to {}", length.saturating_sub(1)), fix),
+            None => self.issue(path, "list length is missing or unreadable".into(), fix),
+        }
+        Ok(None)
+    }
+
+    fn strings(&mut self, path: &str, missing: &Fix) -> crate::Result<()> {
+        let Some(length) = self.list(path, false, missing)? else { return Ok(()) };
+        for i in 0..length {
+            if !self.data(path, &i.to_string(), |x| crypt::collect_string(x).map(|_| ()), &Fix::List(path.to_string())) { break }
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, uid: &str, restore: &Fix) -> crate::Result<()> {
+        let path = format!("entries/{uid}");
+        self.data(&path, "title", |x| crypt::collect_string(x).map(|_| ()), restore);
+        self.data(&path, "description", |x| crypt::collect_string(x).map(|_| ()), restore);
+        if self.data(&path, "date", |x| crypt::collect_string(x).map(|_| ()), restore) {
+            let date = LazyData::load(self.root.join(&path).join("date")).and_then(crypt::collect_string).context("While reading entry date")?;
+            if NaiveDate::parse_from_str(&date, DATE_FORMAT).is_err() {
+                self.issue(&path, format!("date '{date}' isn't a valid date"), restore.clone());
+            }
+        }
+        self.strings(&format!("{path}/tags"), restore)?;
+        self.strings(&format!("{path}/notes"), restore)?;
+
+        let sections = format!("{path}/sections");
+        if let Some(length) = self.list(&sections, true, restore)? {
+            for i in 0..length {
+                let section = format!("{sections}/{i}");
+                self.data(&section, "title", |x| crypt::collect_string(x).map(|_| ()), restore);
+                self.data(&section, "content", |x| crypt::collect_string(x).map(|_| ()), restore);
+                self.strings(&format!("{section}/notes"), restore)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn moc(&mut self, uid: &str, restore: &Fix) -> crate::Result<()> {
+        let path = format!("mocs/{uid}");
+        self.data(&path, "title", |x| crypt::collect_string(x).map(|_| ()), restore);
+        self.data(&path, "description", |x| crypt::collect_string(x).map(|_| ()), restore);
+        self.strings(&format!("{path}/tags"), restore)?;
+        self.strings(&format!("{path}/notes"), restore)?;
+
+        let collections = format!("{path}/collections");
+        if let Some(length) = self.list(&collections, true, restore)? {
+            for i in 0..length {
+                let collection = format!("{collections}/{i}");
+                self.data(&collection, "title", |x| crypt::collect_string(x).map(|_| ()), restore);
+                self.strings(&format!("{collection}/notes"), restore)?;
+                self.strings(&format!("{collection}/include"), restore)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The strings of a list, read leniently (skipping unreadable elements and ignoring its length)
+fn read_lenient(path: &Path) -> crate::Result<Vec<String>> {
+    if !path.is_dir() { return Ok(Vec::new()) }
+    Ok(numbered(path, false)?
+        .into_iter()
+        .filter_map(|i| LazyData::load(path.join(i.to_string())).and_then(crypt::collect_string).ok())
+        .collect())
+}
+
+/// Scans an archive for missing attributes, inconsistent lists and order lists that disagree with its entries
+pub fn scan(archive: &Archive) -> crate::Result<Vec<Issue>> {
+    let root = archive.database().path();
+    let mut scanner = Scanner { root, issues: Vec::new() };
+
+    // Entries and mocs (ones with revisions can be restored from them)
+    let mut entries = BTreeSet::new();
+    for is_moc in [false, true] {
+        let path = root.join(if is_moc { "mocs" } else { "entries" });
+        if !path.is_dir() { continue }
+
+        for uid in archive::list_dirs(&path)? {
+            let restorable = history::list(archive, &uid, is_moc).is_ok_and(|x| !x.is_empty());
+            let fix = if restorable { Fix::Restore { uid: uid.clone(), is_moc } } else { Fix::Manual };
+            if is_moc { scanner.moc(&uid, &fix)? } else { scanner.entry(&uid, &fix)? }
+            if !is_moc { entries.insert(uid); }
+        }
+    }
+
+    // Order lists (re-committed entries are in both until the archive is next sorted)
+    let mut ordered = BTreeSet::new();
+    for name in ["sorted", "unsorted"] {
+        let path = format!("order/{name}");
+        scanner.list(&path, false, &Fix::Order)?;
+
+        let mut listed = BTreeSet::new();
+        for uid in read_lenient(&root.join(&path))? {
+            if !entries.contains(&uid) {
+                scanner.issue(&path, format!("entry '{uid}' doesn't exist"), Fix::Order);
+            } else if !listed.insert(uid.clone()) {
+                scanner.issue(&path, format!("entry '{uid}' is listed more than once"), Fix::Order);
+            }
+        }
+        ordered.extend(listed);
+    }
+    for uid in entries.difference(&ordered) {
+        scanner.issue("order", format!("entry '{uid}' is missing from the sort order"), Fix::Order);
+    }
+
+    // The list fixes of items being restored are redundant
+    let restored: Vec<String> = scanner.issues.iter().filter_map(|x| match &x.fix {
+        Fix::Restore { uid, is_moc } => Some(format!("{}/{uid}", if *is_moc { "mocs" } else { "entries" })),
+        _ => None,
+    }).collect();
+    for x in scanner.issues.iter_mut() {
+        if let Fix::List(path) | Fix::Containers(path) = &x.fix {
+            if let Some(item) = restored.iter().find(|item| path.starts_with(&format!("{item}/"))) {
+                let (kind, uid) = item.split_once('/').unwrap_or_default();
+                x.fix = Fix::Restore { uid: uid.to_string(), is_moc: kind == "mocs" };
+            }
+        }
+    }
+
+    Ok(scanner.issues)
+}
+
+fn repair_list(root: &Path, path: &str) -> crate::Result<()> {
+    let full = root.join(path);
+    let elements = read_lenient(&full)?;
+    list::write_strings(&elements, &LazyContainer::init(&full).context("While loading list")?)
+}
+
+fn repair_containers(root: &Path, path: &str) -> crate::Result<()> {
+    let full = root.join(path);
+    let children = numbered(&full, true)?;
+
+    // Move them aside first so renumbering doesn't overwrite any
+    for i in children.iter() {
+        fs::rename(full.join(i.to_string()), full.join(format!("old-{i}"))).context("While renumbering list")?;
+    }
+    for (new, i) in children.iter().enumerate() {
+        fs::rename(full.join(format!("old-{i}")), full.join(new.to_string())).context("While renumbering list")?;
+    }
+
+    let container = LazyContainer::load(&full).context("While loading list")?;
+    let length = list::stored_length(children.len())?;
+    write_container!((container) length = new_u32(length)).context("While writing list length")
+}
+
+fn repair_order(archive: &Archive) -> crate::Result<()> {
+    let root = archive.database().path();
+    let entries: BTreeSet<String> = match root.join("entries").is_dir() {
+        true => archive::list_dirs(&root.join("entries"))?.into_iter().collect(),
+        false => BTreeSet::new(),
+    };
+
+    let mut seen = BTreeSet::new();
+    let mut keep = |uids: Vec<String>| uids.into_iter().filter(|x| entries.contains(x) && seen.insert(x.clone())).collect::<Vec<_>>();
+    let sorted = keep(read_lenient(&root.join("order/sorted"))?);
+    let mut unsorted = keep(read_lenient(&root.join("order/unsorted"))?);
+    unsorted.extend(entries.iter().filter(|x| !seen.contains(*x)).cloned()); // sorted on the next sort
+
+    for (name, uids) in [("sorted", sorted), ("unsorted", unsorted)] {
+        let container = search_database!((archive.database()) /order/(name)).context("While loading order list")?;
+        list::write_strings(&uids, &container)?;
+    }
+    Ok(())
+}
+
+/// Repairs the issues found by `scan` (the ones that can be), leaving the rest
+pub fn repair(archive: &Archive, issues: &[Issue], mut logger: impl Logger) -> crate::Result<usize> {
+    let root = archive.database().path().to_path_buf();
+    let mut repaired = 0;
+
+    // Restore broken items from their latest revision
+    let restores: BTreeSet<(String, bool)> = issues.iter().filter_map(|x| match &x.fix {
+        Fix::Restore { uid, is_moc } => Some((uid.clone(), *is_moc)),
+        _ => None,
+    }).collect();
+    for (uid, is_moc) in restores.iter() {
+        log!((logger) Fsck("Restoring {} '{uid}' from its latest revision...", if *is_moc { "moc" } else { "entry" }));
+        let archive = Archive::load_dir(archive.home(), root.clone(), logger.hollow())?; // for the latest itver
+        let revision = history::list(&archive, uid, *is_moc)?.pop()
+            .ok_or_else(|| Error::NotFound(format!("No revisions of '{uid}' to restore")))?;
+        fs::remove_dir_all(root.join(if *is_moc { "mocs" } else { "entries" }).join(uid)).context("While removing broken item")?;
+        archive.write_table(revision.item, &format!("revision {} of '{uid}'", revision.number), logger.hollow())?;
+    }
+
+    for x in issues.iter() {
+        match &x.fix {
+            Fix::List(path) => repair_list(&root, path)?,
+            Fix::Containers(path) => repair_containers(&root, path)?,
+            Fix::Restore { .. } => (),
+            Fix::Order | Fix::Manual => continue,
+        }
+        log!((logger) Fsck("Repaired {x}"));
+        repaired += 1;
+    }
+
+    let archive = Archive::load_dir(archive.home(), root, logger.hollow())?;
+    if issues.iter().any(|x| x.fix == Fix::Order) || !restores.is_empty() {
+        repair_order(&archive)?;
+        repaired += issues.iter().filter(|x| x.fix == Fix::Order).count();
+    }
+    if !restores.is_empty() {
+        index::rebuild(&archive, logger.hollow())?; // old versions of them may still be indexed
+    }
+
+    Ok(repaired)
+}
+
+pub fn fsck_command(repair: bool, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = match repair {
+        true => Archive::load(home, logger.hollow())?,
+        false => Archive::load_shared(home, logger.hollow())?,
+    };
+    log!((logger) Fsck("Checking archive '{}'...", home.archive().to_string_lossy()));
+    let issues = scan(&archive)?;
+    if issues.is_empty() {
+        log!((logger.vital) Fsck("No problems found") as Log);
+        return Ok(());
+    }
+
+    for x in issues.iter() {
+        log!((logger.error) Fsck("{x}") as Failure);
+    }
+    if !repair {
+        return Err(Error::Validation(format!("Found {} problem(s) in the archive; run `diary-cli fsck --repair` to repair them", issues.len())));
+    }
+
+    backups::create(&archive, "fsck --repair", logger.hollow())?;
+    let repaired = transaction::run_all(&archive, logger.hollow(), |archive| self::repair(archive, &issues, logger.hollow()))?;
+    log!((logger.vital) Fsck("Repaired {repaired} problem(s)") as Log);
+
+    let remaining = scan(&Archive::load(home, logger.hollow())?)?;
+    if !remaining.is_empty() {
+        for x in remaining.iter() {
+            log!((logger.vital) Fsck("{x}") as Warning);
+        }
+        return Err(Error::Validation(format!("{} problem(s) couldn't be repaired automatically; roll back, restore or remove the items they are in", remaining.len())));
+    }
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use chrono::{DateTime, FixedOffset, Local};
+use lazy_db::*;
+use soulog::*;
+use toml::{Table, Value};
+use crate::{archive::Archive, list, Context, Error, Home};
+
+/// A committed version of an entry or moc
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// Starts at 1 for the first committed version
+    pub number: usize,
+    /// The itver of the archive after the version was committed
+    pub itver: u32,
+    pub time: DateTime<FixedOffset>,
+    /// The item as it would be pulled (in one file)
+    pub item: Table,
+}
+
+impl Revision {
+    fn parse(number: usize, raw: &str) -> crate::Result<Self> {
+        let invalid = |key: &str| Error::Validation(format!("Revision {number} must have a valid '{key}' attribute"));
+        let mut table = raw.parse::<Table>()
+            .map_err(|err| Error::Parse { path: format!("revision {number}"), message: err.to_string() })?;
+
+        let itver = table.get("itver").and_then(|x| x.as_integer()).and_then(|x| u32::try_from(x).ok()).ok_or_else(|| invalid("itver"))?;
+        let time = table.get("time").and_then(|x| x.as_str()).and_then(|x| DateTime::parse_from_rfc3339(x).ok()).ok_or_else(|| invalid("time"))?;
+        let item = match table.remove("item") {
+            Some(Value::Table(x)) => x,
+            _ => return Err(invalid("item")),
+        };
+
+        Ok(Self { number, itver, time, item })
+    }
+
+    fn encode(itver: u32, time: DateTime<FixedOffset>, item: Table) -> crate::Result<String> {
+        let mut table = Table::new();
+        table.insert("itver".into(), (itver as i64).into());
+        table.insert("time".into(), time.to_rfc3339().into());
+        table.insert("item".into(), item.into());
+        toml::to_string(&table).map_err(|err| Error::Validation(format!("While encoding revision: {err}")))
+    }
+
+    /// The title of the item at this revision
+    pub fn title(&self) -> &str {
+        self.item.get("entry").or_else(|| self.item.get("moc"))
+            .and_then(|x| x.get("title"))
+            .and_then(|x| x.as_str())
+            .unwrap_or_default()
+    }
+}
+
+#[inline]
+fn kind(is_moc: bool) -> &'static str {
+    if is_moc { "mocs" } else { "entries" }
+}
+
+#[inline]
+fn name(is_moc: bool) -> &'static str {
+    if is_moc { "moc" } else { "entry" }
+}
+
+/// Records a new revision of an item (as pulled) at an itver
+pub fn record(archive: &Archive, uid: &str, is_moc: bool, itver: u32, item: Table) -> crate::Result<()> {
+    let exists = archive.database_exists(format!("revisions/{}/{uid}", kind(is_moc)));
+    archive.touch(format!("revisions/{}/{uid}", kind(is_moc)))?;
+    let container = search_database!((archive.database()) /revisions/(kind(is_moc))/(uid))
+        .with_context(|| format!("While loading the revisions of {} '{uid}'", name(is_moc)))?;
+    if !exists {
+        list::write_strings::<String>(&[], &container)?;
+    }
+
+    let raw = Revision::encode(itver, Local::now().fixed_offset(), item)?;
+    list::push_string(&raw, &container)
+}
+
+/// Removes every revision of an item (when it's removed from the archive)
+pub fn forget(archive: &Archive, uid: &str, is_moc: bool) -> crate::Result<()> {
+    let path = format!("revisions/{}/{uid}", kind(is_moc));
+    if !archive.database_exists(&path) { return Ok(()) }
+    archive.touch(&path)?;
+    fs::remove_dir_all(archive.database().path().join(&path))
+        .with_context(|| format!("While removing the revisions of {} '{uid}'", name(is_moc)))
+}
+
+/// Counts the revisions of an item without reading them
+pub fn count(archive: &Archive, uid: &str, is_moc: bool) -> crate::Result<u32> {
+    if !archive.database_exists(format!("revisions/{}/{uid}", kind(is_moc))) {
+        return Ok(0);
+    }
+
+    let container = search_database!((archive.database()) /revisions/(kind(is_moc))/(uid))
+        .with_context(|| format!("While loading the revisions of {} '{uid}'", name(is_moc)))?;
+    list::load_length(&container)
+}
+
+/// Lists the revisions of an item from oldest to newest
+pub fn list(archive: &Archive, uid: &str, is_moc: bool) -> crate::Result<Vec<Revision>> {
+    if !archive.database_exists(format!("revisions/{}/{uid}", kind(is_moc))) {
+        return Ok(Vec::with_capacity(0));
+    }
+
+    let container = search_database!((archive.database()) /revisions/(kind(is_moc))/(uid))
+        .with_context(|| format!("While loading the revisions of {} '{uid}'", name(is_moc)))?;
+    list::read_strings(&container)?
+        .iter()
+        .enumerate()
+        .map(|(i, x)| Revision::parse(i + 1, x))
+        .collect()
+}
+
+/// Gets a revision of an item by its number
+pub fn get(archive: &Archive, uid: &str, is_moc: bool, number: usize) -> crate::Result<Revision> {
+    let revisions = list(archive, uid, is_moc)?;
+    if revisions.is_empty() {
+        return Err(Error::NotFound(format!("{} '{uid}' has no revisions", name(is_moc))));
+    }
+
+    match number.checked_sub(1).and_then(|i| revisions.into_iter().nth(i)) {
+        Some(x) => Ok(x),
+        None => Err(Error::NotFound(format!("{} '{uid}' has no revision {number}", name(is_moc)))),
+    }
+}
+
+/// A difference between two revisions of an item
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(path, x) => write!(f, "{}", colour_format![green("+ "), cyan(path), blue(": "), none(&brief(x))]),
+            Change::Removed(path, x) => write!(f, "{}", colour_format![red("- "), cyan(path), blue(": "), none(&brief(x))]),
+            Change::Changed(path, old, new) => {
+                if let (Some(old), Some(new)) = (old.as_str(), new.as_str()) {
+                    if old.contains('\n') || new.contains('\n') {
+                        write!(f, "{}", colour_format![yellow("~ "), cyan(path), blue(":")])?;
+                        for (sign, line) in line_diff(old, new) {
+                            match sign {
+                                '+' => write!(f, "\n{}", colour_format![green("  + "), green(line)])?,
+                                '-' => write!(f, "\n{}", colour_format![red("  - "), red(line)])?,
+                                _ => write!(f, "\n    {line}")?,
+                            }
+                        }
+                        return Ok(());
+                    }
+                }
+                write!(f, "{}", colour_format![yellow("~ "), cyan(path), blue(": "), none(&brief(old)), blue(" -> "), none(&brief(new))])
+            },
+        }
+    }
+}
+
+/// A short, single line description of a toml value
+fn brief(value: &Value) -> String {
+    match value {
+        Value::String(x) if x.contains('\n') => format!("{:?}...", x.lines().next().unwrap_or_default()),
+        Value::String(x) => format!("{x:?}"),
+        Value::Table(x) => match x.get("title").and_then(|x| x.as_str()) {
+            Some(title) => format!("{title:?}"),
+            None => "{...}".into(),
+        },
+        x => x.to_string(),
+    }
+}
+
+/// Diffs two versions of an item field by field (and section by section)
+pub fn diff(old: &Table, new: &Table) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_tables("", old, new, &mut changes);
+    changes
+}
+
+fn join(path: &str, key: &str) -> String {
+    match (path, key) {
+        // The item's own attributes are shown without the `entry.` or `moc.` prefix
+        ("", "entry" | "moc") => String::new(),
+        ("", key) => key.to_string(),
+        (path, key) => format!("{path}.{key}"),
+    }
+}
+
+fn diff_tables(path: &str, old: &Table, new: &Table, changes: &mut Vec<Change>) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = join(path, key);
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => diff_values(&path, old, new, changes),
+            (Some(old), None) => changes.push(Change::Removed(path, old.clone())),
+            (None, Some(new)) => changes.push(Change::Added(path, new.clone())),
+            (None, None) => (),
+        }
+    }
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    if old == new { return }
+
+    match (old, new) {
+        (Value::Table(old), Value::Table(new)) => diff_tables(path, old, new, changes),
+        // Sections and collections are compared one by one
+        (Value::Array(old), Value::Array(new)) if old.iter().chain(new.iter()).all(|x| x.is_table()) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{path} {}", i + 1);
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_values(&path, old, new, changes),
+                    (Some(old), None) => changes.push(Change::Removed(path, old.clone())),
+                    (None, Some(new)) => changes.push(Change::Added(path, new.clone())),
+                    (None, None) => (),
+                }
+            }
+        },
+        // Tags and notes are compared element by element
+        (Value::Array(old), Value::Array(new)) => {
+            let removed: Vec<&Value> = old.iter().filter(|x| !new.contains(x)).collect();
+            let added: Vec<&Value> = new.iter().filter(|x| !old.contains(x)).collect();
+            if removed.is_empty() && added.is_empty() {
+                changes.push(Change::Changed(path.to_string(), Value::Array(old.clone()), Value::Array(new.clone())));
+            }
+            changes.extend(removed.into_iter().map(|x| Change::Removed(path.to_string(), x.clone())));
+            changes.extend(added.into_iter().map(|x| Change::Added(path.to_string(), x.clone())));
+        },
+        (old, new) => changes.push(Change::Changed(path.to_string(), old.clone(), new.clone())),
+    }
+}
+
+/// A line by line diff of two strings (`' '`, `'-'` or `'+'` for each line)
+pub fn line_diff<'a>(old: &'a str, new: &'a str) -> Vec<(char, &'a str)> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            result.push(('-', old[i]));
+            i += 1;
+        } else {
+            result.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    result
+}
+
+pub fn history_command(uid: String, is_moc: bool, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
+    let revisions = list(&archive, &uid, is_moc)?;
+    if revisions.is_empty() {
+        return Err(Error::NotFound(format!("{} '{uid}' has no revisions", name(is_moc))));
+    }
+
+    for x in revisions.iter() {
+        log!((logger.vital) History("{}", colour_format![
+            cyan(&format!("rev {}", x.number)), blue(" | "), green("itver "), none(&x.itver.to_string()), blue(" | "),
+            none(&x.time.format("%Y-%m-%d %H:%M:%S").to_string()), blue(" | "), none(x.title())
+        ]) as Log);
+    }
+    Ok(())
+}
+
+/// Diffs two revisions of an item (defaulting to the latest one and the one before it)
+pub fn diff_command(uid: String, is_moc: bool, old: Option<usize>, new: Option<usize>, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
+    let latest = list(&archive, &uid, is_moc)?.len();
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) => (old, new),
+        (Some(old), None) => (old, latest),
+        (None, _) => (latest.saturating_sub(1).max(1), latest),
+    };
+
+    let old = get(&archive, &uid, is_moc, old)?;
+    let new = get(&archive, &uid, is_moc, new)?;
+    let changes = diff(&old.item, &new.item);
+
+    log!((logger.vital) Diff("{}", colour_format![blue("# "), green("Changes from "), cyan(&format!("rev {}", old.number)), green(" to "), cyan(&format!("rev {}", new.number))]) as Log);
+    if changes.is_empty() {
+        log!((logger.vital) Diff("No changes") as Log);
+    }
+    for x in changes.iter() {
+        log!((logger.vital) Diff("{x}") as Log);
+    }
+    Ok(())
+}
+
+/// Re-commits an old revision of an item (which becomes its newest revision)
+pub fn restore_command(uid: String, is_moc: bool, number: usize, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
+    let revision = get(&archive, &uid, is_moc, number)?;
+
+    log!((logger) Restore("Restoring {} '{uid}' to revision {number}...", name(is_moc)));
+    archive.commit_table(
+        revision.item,
+        &format!("revision {number} of '{uid}'"),
+        &format!("restore of '{uid}' to revision {number}"),
+        logger.hollow(),
+    )?;
+    log!((logger.vital) Restore("Successfully restored {} '{uid}' to revision {number}", name(is_moc)) as Log);
+    Ok(())
+}
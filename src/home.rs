@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+/// The environment variable that overrides the default home of the cli
+pub const HOME_VAR: &str = "DIARY_CLI_HOME";
+
+/// The name of the diary kept directly in the cli's home (used when `--diary` isn't specified)
+pub const DEFAULT_DIARY: &str = "default";
+
+/// The directory the cli keeps its archive and backups in; every path the cli touches comes from here
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Home {
+    root: PathBuf,
+    /// The named diary this home points to (`None` for the default diary)
+    diary: Option<String>,
+}
+
+impl Home {
+    #[inline]
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { root: path.as_ref().to_path_buf(), diary: None }
+    }
+
+    /// Picks the home from (in order) the `--archive` flag, `$DIARY_CLI_HOME`, then `$HOME/.diary-cli`
+    pub fn from_env(archive: Option<String>) -> Self {
+        if let Some(path) = archive {
+            return Self::new(path);
+        }
+
+        match std::env::var(HOME_VAR) {
+            Ok(path) if !path.is_empty() => Self::new(path),
+            _ => Self::new(Self::default_path()),
+        }
+    }
+
+    pub fn default_path() -> PathBuf {
+        // Linux only; change this if you want to go cross platform
+        match std::env::var("HOME") {
+            Ok(path) => Path::new(&path).join(".diary-cli"),
+            Err(_) => PathBuf::from("/etc/diary-cli/"),
+        }
+    }
+
+    /// The same home but pointing to another diary (`default` or `None` being the default diary)
+    pub fn with_diary(&self, diary: Option<&str>) -> Self {
+        let diary = diary.filter(|x| *x != DEFAULT_DIARY).map(|x| x.to_string());
+        Self { root: self.root.clone(), diary }
+    }
+
+    /// The name of the diary this home points to
+    #[inline]
+    pub fn diary(&self) -> &str {
+        self.diary.as_deref().unwrap_or(DEFAULT_DIARY)
+    }
+
+    #[inline]
+    pub fn is_default(&self) -> bool {
+        self.diary.is_none()
+    }
+
+    /// The root of the cli's home, shared by all diaries
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory the named diaries are kept in
+    #[inline]
+    pub fn diaries(&self) -> PathBuf {
+        self.root.join("diaries")
+    }
+
+    /// The directory of the current diary
+    pub fn path(&self) -> PathBuf {
+        match &self.diary {
+            Some(name) => self.diaries().join(name),
+            None => self.root.clone(),
+        }
+    }
+
+    /// The directory of the archive itself
+    #[inline]
+    pub fn archive(&self) -> PathBuf {
+        self.path().join("archive")
+    }
+
+    /// Where a copy of the archive is staged to preview changes on
+    #[inline]
+    pub fn staging(&self) -> PathBuf {
+        self.path().join("archive.staging")
+    }
+
+    /// Where a transaction journals what it changes in the archive, so it can be undone
+    #[inline]
+    pub fn journal(&self) -> PathBuf {
+        self.path().join("archive.journal")
+    }
+
+    /// The advisory lock file that keeps writers of the archive from colliding with each other and with readers
+    #[inline]
+    pub fn lock(&self) -> PathBuf {
+        self.path().join("archive.lock")
+    }
+
+    /// The single backup older versions made before each modification of the archive
+    #[inline]
+    pub fn backup(&self) -> PathBuf {
+        self.path().join("backup.ldb")
+    }
+
+    /// The directory of the timestamped backups made before each modification of the archive
+    #[inline]
+    pub fn backups(&self) -> PathBuf {
+        self.path().join("backups")
+    }
+
+    /// The user's entry templates used by `diary-cli new`
+    #[inline]
+    pub fn templates(&self) -> PathBuf {
+        self.path().join("templates")
+    }
+
+    /// Scratch space for decompiling backups before they are loaded
+    #[inline]
+    pub fn scratch(&self) -> PathBuf {
+        self.path().join("new")
+    }
+}
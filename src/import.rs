@@ -0,0 +1,303 @@
+use std::{collections::HashSet, path::Path};
+use chrono::NaiveDate;
+use soulog::*;
+use toml::{Table, Value};
+use crate::{archive::Archive, backups, entry::DATE_FORMAT, export::{json, PRIVATE_MARKER, REDACTED}, transaction, Error, Home, Result};
+
+pub mod obsidian;
+pub mod day_one;
+pub mod jrnl;
+pub mod markdown;
+
+/// An entry or moc config read from an export, named by where in it it came from
+pub struct Item {
+    pub at: String,
+    /// The config, or the reason it can't be imported (eg a missing date)
+    pub config: std::result::Result<Table, String>,
+}
+
+impl Item {
+    #[inline]
+    pub fn new(at: impl Into<String>, config: std::result::Result<Table, String>) -> Self {
+        Self { at: at.into(), config }
+    }
+}
+
+/// An export format (usually of another journaling tool) entries can be imported from
+pub trait Source {
+    /// What the export is called in logs (eg `Day One export`)
+    fn name(&self) -> &'static str;
+    /// Reads the entries and mocs of an export (a file or folder)
+    fn read(&self, path: &Path, logger: impl Logger) -> Result<Vec<Item>>;
+}
+
+/// What to do with items of the same uid as one already in the archive (or earlier in the import)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Collision {
+    /// Leave the existing one as it is
+    Skip,
+    /// Replace the existing one (recording it as a new revision)
+    Overwrite,
+    /// Import it under a new uid, like `uid-2`
+    Suffix,
+}
+
+/// A json or ndjson export of diary-cli (see `export --format json`)
+pub struct DiaryJson;
+
+impl Source for DiaryJson {
+    fn name(&self) -> &'static str { "export" }
+
+    fn read(&self, path: &Path, _: impl Logger) -> Result<Vec<Item>> {
+        Ok(json::read(path)?.into_iter().map(|(at, x)| Item::new(at, x)).collect())
+    }
+}
+
+/// Imports the items of an export into the archive as one backed up change, reporting (and leaving out) the ones that can't be
+pub fn import(source: &impl Source, path: String, dry_run: bool, collision: Collision, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let name = source.name();
+    if !Path::new(&path).exists() {
+        return Err(Error::NotFound(format!("{} '{path}' doesn't exist", capitalise(name))));
+    }
+
+    log!((logger) Import("Reading {name} '{path}'..."));
+    let items = source.read(Path::new(&path), logger.hollow())?;
+    let archive = Archive::load(home, logger.hollow())?;
+
+    // Check every item before touching the archive
+    log!((logger) Import("Checking {} item(s)...", items.len()));
+    let (mut skipped, mut failed) = (0, 0);
+    let mut taken = HashSet::new();
+    let mut configs = Vec::with_capacity(items.len());
+    for Item { at, config } in items {
+        let mut config = match config {
+            Ok(x) => x,
+            Err(reason) => {
+                log!((logger.vital) Import("Skipping '{at}' as it {reason}") as Warning);
+                skipped += 1;
+                continue;
+            },
+        };
+
+        let Some((is_moc, uid)) = Archive::uid_of(&config) else {
+            log!((logger.vital) Import("Failed to import '{at}': it has no uid") as Warning);
+            failed += 1;
+            continue;
+        };
+        let exists = |uid: &str| taken.contains(&(is_moc, uid.to_string())) || archive.database_exists(format!("{}/{uid}", if is_moc { "mocs" } else { "entries" }));
+        let uid = match (exists(&uid), collision) {
+            (false, _) | (true, Collision::Overwrite) => uid,
+            (true, Collision::Skip) => {
+                log!((logger.vital) Import("Skipping '{at}' as an item of uid '{uid}' already exists") as Warning);
+                skipped += 1;
+                continue;
+            },
+            (true, Collision::Suffix) => {
+                let suffixed = (2..).map(|i| format!("{uid}-{i}")).find(|x| !exists(x)).unwrap_or_default();
+                log!((logger) Import("Importing '{at}' as '{suffixed}' since an item of uid '{uid}' already exists"));
+                config[if is_moc { "moc" } else { "entry" }]["uid"] = suffixed.clone().into();
+                suffixed
+            },
+        };
+
+        if let Err(err) = Archive::validate(config.clone(), &at, logger.hollow()) {
+            log!((logger.vital) Import("Failed to import '{at}': {err}") as Warning);
+            failed += 1;
+            continue;
+        }
+        taken.insert((is_moc, uid));
+        configs.push((at, config));
+    }
+
+    let imported = configs.len();
+    if dry_run {
+        for (at, _) in configs.iter() { log!((logger.vital) Import("Would import '{at}'") as Log) }
+    } else if imported > 0 {
+        backups::create(&archive, &format!("import {name} '{path}'"), logger.hollow())?;
+        transaction::run(&archive, logger.hollow(), |archive| {
+            for (at, config) in configs {
+                log!((logger) Import("Importing '{at}'..."));
+                archive.write_table(config, &at, logger.hollow())?;
+            }
+            Ok(())
+        })?;
+    }
+
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    let summary = format!("{verb} {imported} item(s) from {name} '{path}' ({skipped} skipped, {failed} failed)");
+    if skipped + failed > 0 {
+        log!((logger.vital) Import("{summary}") as Warning);
+    } else {
+        log!((logger.vital) Import("{summary}") as Log);
+    }
+    Ok(())
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|x| x.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// The attributes of an entry read from another tool
+pub struct Draft<'a> {
+    pub uid: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub notes: Vec<String>,
+    pub date: NaiveDate,
+    pub private: bool,
+    pub sections: Vec<Section<'a>>,
+}
+
+impl Draft<'_> {
+    /// Turns it into an entry config (as it would be committed)
+    pub fn config(self) -> Table {
+        let mut entry = Table::new();
+        entry.insert("uid".into(), self.uid.into());
+        entry.insert("title".into(), self.title.into());
+        entry.insert("description".into(), self.description.into());
+        entry.insert("tags".into(), self.tags.into());
+        entry.insert("notes".into(), self.notes.into());
+        if let Ok(date) = self.date.format(DATE_FORMAT).to_string().parse() {
+            entry.insert("date".into(), Value::Datetime(date));
+        }
+        if self.private { entry.insert("private".into(), true.into()); }
+
+        let mut table = Table::new();
+        table.insert("entry".into(), entry.into());
+        table.insert("section".into(), self.sections.into_iter().map(Section::table).collect::<Vec<_>>().into());
+        table
+    }
+}
+
+/// A section in the making
+pub struct Section<'a> {
+    pub title: String,
+    pub notes: Vec<String>,
+    pub lines: Vec<&'a str>,
+    pub private: bool,
+}
+
+impl<'a> Section<'a> {
+    /// A section of a heading (which may be marked as private by `export`)
+    pub fn new(title: &str) -> Self {
+        let (title, private) = match title.strip_suffix(PRIVATE_MARKER) {
+            Some(x) => (x, true),
+            None => (title, false),
+        };
+        Self { title: title.to_string(), notes: Vec::new(), lines: Vec::new(), private }
+    }
+
+    pub fn is_redacted(&self) -> bool {
+        self.title == "Redacted" && join(&self.lines) == REDACTED
+    }
+
+    fn table(self) -> Table {
+        let mut table = Table::new();
+        table.insert("title".into(), self.title.into());
+        table.insert("notes".into(), self.notes.into());
+        table.insert("contents".into(), join(&self.lines).into());
+        if self.private { table.insert("private".into(), true.into()); }
+        table
+    }
+}
+
+/// The attributes of a markdown note's yaml frontmatter that matter to entries
+#[derive(Default)]
+pub struct Frontmatter {
+    pub tags: Vec<String>,
+    pub date: Option<String>,
+    pub private: bool,
+}
+
+/// Splits a note into its frontmatter and the lines of its body
+pub fn frontmatter(contents: &str) -> (Frontmatter, Vec<&str>) {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let end = match lines.first() {
+        Some(&"---") => lines.iter().skip(1).position(|x| *x == "---").map(|x| x + 1),
+        _ => None,
+    };
+    let Some(end) = end else { return (Frontmatter::default(), lines) };
+
+    // Only the bits of yaml obsidian writes: `key: value`, `key: [a, b]` and `key:` followed by `- item`s
+    let unquote = |x: &str| x.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+    let mut attributes: Vec<(String, Vec<String>)> = Vec::new();
+    for line in &lines[1..end] {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if let Some((_, values)) = attributes.last_mut() { values.push(unquote(item)) }
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let values = match value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                Some(list) => list.split(',').map(unquote).filter(|x| !x.is_empty()).collect(),
+                None if value.is_empty() => Vec::new(),
+                None => vec![unquote(value)],
+            };
+            attributes.push((key.trim().to_string(), values));
+        }
+    }
+
+    let mut frontmatter = Frontmatter::default();
+    for (key, values) in attributes {
+        match key.as_str() {
+            "tags" | "tag" => frontmatter.tags.extend(values.into_iter().map(|x| x.trim_start_matches('#').to_string())),
+            "date" => frontmatter.date = values.into_iter().next(),
+            "private" => frontmatter.private = values.first().is_some_and(|x| x == "true"),
+            _ => (),
+        }
+    }
+    (frontmatter, lines[end + 1..].to_vec())
+}
+
+/// Parses a `yyyy-mm-dd` date (leaving out any time after it, like `2023-08-21T10:30`)
+pub fn parse_date(date: &str) -> std::result::Result<NaiveDate, String> {
+    let date = date.trim().split(['T', ' ']).next().unwrap_or_default();
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("has a date '{date}' that isn't a `yyyy-mm-dd` date"))
+}
+
+/// A heading's level and text (if the line is one)
+pub fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|x| *x == '#').count();
+    match (1..=6).contains(&level) {
+        true => line[level..].strip_prefix(' ').map(|x| (level, x.trim())),
+        false => None,
+    }
+}
+
+/// Strips a line of its blockquote marker (if it has one)
+pub fn unquote(line: &str) -> &str {
+    line.strip_prefix("> ").or_else(|| line.strip_prefix('>')).unwrap_or(line)
+}
+
+/// Joins lines, leaving out the blank ones at the start and end
+pub fn join(lines: &[&str]) -> String {
+    let start = lines.iter().position(|x| !x.trim().is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|x| !x.trim().is_empty()).map_or(start, |x| x + 1);
+    lines[start..end].join("\n")
+}
+
+/// Splits markdown into its title (a leading `# ` heading) and sections by its headings, with blockquotes read as plain content
+///
+/// Text before the first heading is a section of its own (without a title)
+pub fn split_markdown<'a>(lines: impl IntoIterator<Item = &'a str>) -> (Option<String>, Vec<Section<'a>>) {
+    let mut title = None;
+    let mut preamble = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
+    for line in lines {
+        match heading(line) {
+            Some((1, x)) if title.is_none() && sections.is_empty() && join(&preamble).is_empty() => title = Some(x.to_string()),
+            Some((_, x)) => sections.push(Section::new(x)),
+            None => match sections.last_mut() {
+                Some(section) => section.lines.push(unquote(line)),
+                None => preamble.push(unquote(line)),
+            },
+        }
+    }
+
+    if !join(&preamble).is_empty() {
+        let mut section = Section::new("");
+        section.lines = preamble;
+        sections.insert(0, section);
+    }
+    (title, sections)
+}
@@ -0,0 +1,51 @@
+use std::{fs, path::Path};
+use serde_json::Value;
+use soulog::*;
+use super::{parse_date, split_markdown, Draft, Item, Source};
+use crate::{Context, Error, Result};
+
+/// The `Journal.json` of a Day One json export
+///
+/// Entries are named after their date and the start of their uuid (like `2023-08-21-1a2b3c4d`), are titled by their first line and are tagged `starred` if they were
+pub struct DayOne;
+
+impl Source for DayOne {
+    fn name(&self) -> &'static str { "Day One export" }
+
+    fn read(&self, path: &Path, mut logger: impl Logger) -> Result<Vec<Item>> {
+        let path_string = path.to_string_lossy().to_string();
+        let contents = fs::read_to_string(path).with_context(|| format!("While reading '{path_string}'"))?;
+        let export: Value = serde_json::from_str(&contents).map_err(|err| Error::Parse { path: path_string.clone(), message: err.to_string() })?;
+        let entries = export["entries"].as_array().ok_or_else(|| Error::Validation(format!("'{path_string}' isn't a Day One export (it has no 'entries')")))?;
+
+        log!((logger) Import("Reading {} Day One entries...", entries.len()));
+        Ok(entries.iter().enumerate().map(|(i, x)| Item::new(format!("{path_string} (entry {i})"), parse(x))).collect())
+    }
+}
+
+fn parse(entry: &Value) -> std::result::Result<toml::Table, String> {
+    let date = parse_date(entry["creationDate"].as_str().ok_or("has no 'creationDate'")?)?;
+    let text = entry["text"].as_str().unwrap_or_default();
+    let uuid = entry["uuid"].as_str().unwrap_or_default().to_lowercase();
+
+    // Day One titles entries by their first line, which is usually (but not always) a heading
+    let (title, sections) = split_markdown(text.lines());
+    let title = title.unwrap_or_else(|| text.lines().find(|x| !x.trim().is_empty()).unwrap_or_default().trim().to_string());
+
+    let mut tags = entry["tags"].as_array().map(|x| x.iter().filter_map(|x| x.as_str()).map(String::from).collect::<Vec<_>>()).unwrap_or_default();
+    if entry["starred"].as_bool() == Some(true) { tags.push("starred".into()) }
+
+    Ok(Draft {
+        uid: match uuid.get(..8) {
+            Some(x) => format!("{date}-{x}"),
+            None => date.to_string(),
+        },
+        title,
+        description: String::new(),
+        tags,
+        notes: Vec::new(),
+        date,
+        private: false,
+        sections,
+    }.config())
+}
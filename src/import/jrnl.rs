@@ -0,0 +1,115 @@
+use std::{collections::HashMap, fs, path::Path};
+use chrono::NaiveDate;
+use regex::Regex;
+use serde_json::Value;
+use soulog::*;
+use super::{parse_date, Draft, Item, Section, Source};
+use crate::{Context, Error, Result};
+
+/// A jrnl journal, either as its plain text file (or `jrnl --export txt`) or as `jrnl --export json`
+///
+/// Entries are named after their date and their order within it (like `2023-08-21-1`), are titled by their first sentence and are tagged with their `@tags`
+pub struct Jrnl;
+
+impl Source for Jrnl {
+    fn name(&self) -> &'static str { "jrnl journal" }
+
+    fn read(&self, path: &Path, mut logger: impl Logger) -> Result<Vec<Item>> {
+        let path_string = path.to_string_lossy().to_string();
+        let contents = fs::read_to_string(path).with_context(|| format!("While reading '{path_string}'"))?;
+
+        // The json export, or else the plain text
+        let entries = match serde_json::from_str::<Value>(&contents) {
+            Ok(export) => {
+                let entries = export["entries"].as_array().ok_or_else(|| Error::Validation(format!("'{path_string}' isn't a jrnl export (it has no 'entries')")))?;
+                entries.iter().enumerate().map(|(i, x)| (format!("{path_string} (entry {i})"), from_json(x))).collect()
+            },
+            Err(_) => from_text(&contents, &path_string),
+        };
+
+        log!((logger) Import("Read {} jrnl entries...", entries.len()));
+        let mut per_day = HashMap::new();
+        Ok(entries.into_iter().map(|(at, x)| Item::new(at, x.map(|x| x.config(&mut per_day)))).collect())
+    }
+}
+
+/// A jrnl entry, before it's numbered
+struct Raw {
+    date: NaiveDate,
+    title: String,
+    body: String,
+    tags: Vec<String>,
+    starred: bool,
+}
+
+impl Raw {
+    fn config(self, per_day: &mut HashMap<NaiveDate, usize>) -> toml::Table {
+        let n = per_day.entry(self.date).or_insert(0);
+        *n += 1;
+
+        let mut tags = self.tags;
+        if self.starred { tags.push("starred".into()) }
+        let mut section = Section::new("");
+        section.lines = self.body.lines().collect();
+        Draft {
+            uid: format!("{}-{n}", self.date),
+            title: self.title,
+            description: String::new(),
+            tags,
+            notes: Vec::new(),
+            date: self.date,
+            private: false,
+            sections: vec![section],
+        }.config()
+    }
+}
+
+fn from_json(entry: &Value) -> std::result::Result<Raw, String> {
+    let date = parse_date(entry["date"].as_str().ok_or("has no 'date'")?)?;
+    let tags = entry["tags"].as_array().map(|x| x.iter().filter_map(|x| x.as_str()).map(|x| x.trim_start_matches('@').to_string()).collect()).unwrap_or_default();
+    Ok(Raw {
+        date,
+        title: entry["title"].as_str().unwrap_or_default().to_string(),
+        body: entry["body"].as_str().unwrap_or_default().to_string(),
+        tags,
+        starred: entry["starred"].as_bool() == Some(true),
+    })
+}
+
+/// Splits a plain text journal into its entries, each starting with a `[yyyy-mm-dd hh:mm] ` line
+fn from_text(contents: &str, path: &str) -> Vec<(String, std::result::Result<Raw, String>)> {
+    let start = Regex::new(r"^\[(\d{4}-\d{2}-\d{2})[^\]]*\] ?(.*)$").unwrap();
+    let tag = Regex::new(r"(?:^|\s)@([\w-]+)").unwrap();
+
+    let mut entries: Vec<(String, NaiveDate, String, Vec<&str>)> = Vec::new();
+    let mut invalid = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        match start.captures(line) {
+            Some(x) => match parse_date(&x[1]) {
+                Ok(date) => entries.push((format!("{path}:{}", i + 1), date, x[2].to_string(), Vec::new())),
+                Err(reason) => invalid.push((format!("{path}:{}", i + 1), Err(reason))),
+            },
+            None => if let Some((.., body)) = entries.last_mut() { body.push(line) },
+        }
+    }
+
+    let mut result = invalid;
+    for (at, date, first, body) in entries {
+        // jrnl titles entries by their first sentence
+        let split = first.match_indices(['.', '?', '!']).map(|(i, _)| i + 1).find(|i| first[*i..].starts_with(' '));
+        let (title, rest) = match split {
+            Some(i) => (first[..i].to_string(), first[i..].trim_start().to_string()),
+            None => (first, String::new()),
+        };
+        let starred = title.ends_with(" *");
+        let title = title.trim_end_matches(" *").to_string();
+        let body = std::iter::once(rest.as_str()).chain(body).collect::<Vec<_>>().join("\n");
+
+        let mut tags = Vec::new();
+        for x in tag.captures_iter(&format!("{title}\n{body}")) {
+            if !tags.contains(&x[1].to_string()) { tags.push(x[1].to_string()) }
+        }
+        result.push((at, Ok(Raw { date, title, body, tags, starred })));
+    }
+    result
+}
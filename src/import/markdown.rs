@@ -0,0 +1,55 @@
+use std::{fs, path::Path};
+use soulog::*;
+use super::{frontmatter, parse_date, split_markdown, Draft, Item, Source};
+use crate::{Context, Result};
+
+/// A folder of `yyyy-mm-dd*.md` notes, each imported as an entry (named after its file) dated by its name
+pub struct Markdown;
+
+impl Source for Markdown {
+    fn name(&self) -> &'static str { "folder" }
+
+    fn read(&self, path: &Path, mut logger: impl Logger) -> Result<Vec<Item>> {
+        let context = || format!("While reading folder '{}'", path.to_string_lossy());
+        let mut notes = Vec::new();
+        for x in fs::read_dir(path).with_context(context)? {
+            let path = x.with_context(context)?.path();
+            if path.is_file() && path.extension().is_some_and(|x| x == "md") { notes.push(path) }
+        }
+        notes.sort();
+
+        let mut items = Vec::with_capacity(notes.len());
+        for note in notes {
+            let at = note.to_string_lossy().to_string();
+            log!((logger) Import("Reading note '{at}'..."));
+            let contents = fs::read_to_string(&note).with_context(|| format!("While reading note '{at}'"))?;
+            let uid = note.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            items.push(Item::new(at, parse(uid, &contents)));
+        }
+        Ok(items)
+    }
+}
+
+fn parse(uid: String, contents: &str) -> std::result::Result<toml::Table, String> {
+    let date = match uid.get(..10) {
+        Some(x) => parse_date(x).map_err(|_| "isn't named `yyyy-mm-dd*.md`".to_string())?,
+        None => return Err("isn't named `yyyy-mm-dd*.md`".into()),
+    };
+    let (frontmatter, lines) = frontmatter(contents);
+    let (title, sections) = split_markdown(lines);
+
+    // Untitled notes are titled by the rest of their name (if there is any)
+    let rest = uid[10..].trim_matches([' ', '-', '_']);
+    let title = title.unwrap_or_else(|| if rest.is_empty() { uid[..10].to_string() } else { rest.to_string() });
+
+    Ok(Draft {
+        uid,
+        title,
+        description: String::new(),
+        tags: frontmatter.tags,
+        notes: Vec::new(),
+        date,
+        private: frontmatter.private,
+        sections,
+    }.config())
+}
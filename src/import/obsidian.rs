@@ -0,0 +1,202 @@
+use std::{collections::BTreeSet, fs, path::{Path, PathBuf}};
+use soulog::*;
+use toml::{Table, Value};
+use super::{frontmatter, heading, join, parse_date, split_markdown, unquote, Draft, Item, Section, Source};
+use crate::{export::{COLLECTION_MARKER, REDACTED}, Context, Result};
+
+/// The tags every note exported by diary-cli carries (on top of the entry's own)
+const EXPORT_TAGS: [&str; 2] = ["obsidian-md", "diary-cli"];
+
+/// An `Obsidian.md` vault, whose dated notes are imported as entries (named after their files), along with the mocs `export` wrote into it
+pub struct Obsidian;
+
+impl Source for Obsidian {
+    fn name(&self) -> &'static str { "vault" }
+
+    fn read(&self, path: &Path, mut logger: impl Logger) -> Result<Vec<Item>> {
+        let mut notes = Vec::new();
+        find_notes(path, &mut notes)?;
+        notes.sort();
+
+        let mut items = Vec::with_capacity(notes.len());
+        for note in notes.iter() {
+            let at = note.to_string_lossy().to_string();
+            log!((logger) Import("Reading note '{at}'..."));
+            let contents = fs::read_to_string(note).with_context(|| format!("While reading note '{at}'"))?;
+            let uid = note.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            items.push(Item::new(at, parse(uid, &contents)));
+        }
+
+        // `export` puts the entries of sibling diaries (linked by its mocs) in folders of their own, which belong to those diaries rather than this one
+        let siblings = notes.iter().zip(items.iter())
+            .filter(|(note, _)| note.parent() == Some(path))
+            .filter_map(|(_, item)| item.config.as_ref().ok())
+            .flat_map(|x| x.get("collection").and_then(|x| x.as_array()).cloned().unwrap_or_default())
+            .filter_map(|x| x.get("diary").and_then(|x| x.as_str()).map(|x| x.to_string()))
+            .collect::<BTreeSet<_>>();
+        for (note, item) in notes.iter().zip(items.iter_mut()) {
+            if let Some(diary) = siblings.iter().find(|x| note.starts_with(path.join(x))) {
+                item.config = Err(format!("is in the folder of sibling diary '{diary}' (import it into that diary with `--diary {diary}`)"));
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Finds the markdown notes within a vault, leaving out hidden folders (like `.obsidian`)
+fn find_notes(path: &Path, notes: &mut Vec<PathBuf>) -> Result<()> {
+    let context = || format!("While reading vault folder '{}'", path.to_string_lossy());
+    for x in fs::read_dir(path).with_context(context)? {
+        let path = x.with_context(context)?.path();
+        if path.file_name().is_some_and(|x| x.to_string_lossy().starts_with('.')) { continue }
+        if path.is_dir() {
+            find_notes(&path, notes)?;
+        } else if path.extension().is_some_and(|x| x == "md") {
+            notes.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a note into an entry (or moc) config, or the reason it can't be imported
+///
+/// Notes tagged `diary-cli` are read as they were exported (description, notes and all), those without a date being mocs; other notes are split into sections by their headings
+fn parse(uid: String, contents: &str) -> std::result::Result<Table, String> {
+    let (frontmatter, lines) = frontmatter(contents);
+    let exported = frontmatter.tags.iter().any(|x| x == "diary-cli");
+    if exported && lines.contains(&REDACTED) && lines.iter().any(|x| heading(x) == Some((1, "Redacted"))) {
+        return Err("is redacted (its private material isn't in the vault)".into());
+    }
+    let tags = frontmatter.tags.into_iter().filter(|x| !(exported && EXPORT_TAGS.contains(&x.as_str()))).collect::<Vec<_>>();
+    let Some(date) = &frontmatter.date else {
+        return match exported {
+            true => Ok(parse_moc(uid, tags, frontmatter.private, lines)),
+            false => Err("has no date (add one to its frontmatter to import it)".into()),
+        };
+    };
+    let date = parse_date(date)?;
+
+    let (title, description, notes, sections) = if exported {
+        parse_exported(lines)
+    } else {
+        let (title, sections) = split_markdown(lines);
+        (title, String::new(), Vec::new(), sections)
+    };
+    if sections.iter().any(|x| x.is_redacted()) {
+        return Err("has redacted sections (its private material isn't in the vault)".into());
+    }
+
+    Ok(Draft {
+        title: title.unwrap_or_else(|| uid.clone()),
+        uid,
+        description,
+        tags,
+        notes,
+        date,
+        private: frontmatter.private,
+        sections,
+    }.config())
+}
+
+/// The head of a note as `export` wrote it: `# title`, `---`, `**Description:** ...` and the `## Notes` (if any), up to the `---` ending it
+struct Head {
+    title: Option<String>,
+    description: String,
+    notes: Vec<String>,
+    /// The notes of each section (or collection) by its title, in the order they were exported
+    part_notes: Vec<(String, Vec<String>)>,
+}
+
+fn parse_head<'a>(lines: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Head {
+    let mut title = None;
+    let mut description = Vec::new();
+    let mut notes = Vec::new();
+    let mut part_notes: Vec<(String, Vec<String>)> = Vec::new();
+
+    let mut described = false;
+    while let Some(line) = lines.next_if(|x| heading(x).is_none_or(|(level, _)| level < 3)) {
+        if let Some((1, x)) = heading(line) {
+            title.get_or_insert_with(|| x.to_string());
+        } else if let Some(x) = line.strip_prefix("**Description:** ") {
+            described = true;
+            description.push(x);
+            while let Some(x) = lines.next_if(|x| *x != "---" && *x != "## Notes") { description.push(x) }
+        } else if line == "## Notes" {
+            while let Some(x) = lines.next_if(|x| *x != "---") {
+                if let Some(x) = x.strip_prefix("- #### ") {
+                    part_notes.push((x.to_string(), Vec::new()));
+                } else if let Some(x) = x.strip_prefix("\t- ") {
+                    if let Some((_, notes)) = part_notes.last_mut() { notes.push(x.to_string()) }
+                } else if let Some(x) = x.strip_prefix("- ") {
+                    notes.push(x.to_string());
+                }
+            }
+        } else if line == "---" && described {
+            break;
+        }
+    }
+
+    Head { title, description: join(&description), notes, part_notes }
+}
+
+/// Parses the body of a note as `export` wrote it: its head, then the `###` sections
+fn parse_exported(lines: Vec<&str>) -> (Option<String>, String, Vec<String>, Vec<Section<'_>>) {
+    let mut lines = lines.into_iter().peekable();
+    let Head { title, description, notes, part_notes: section_notes } = parse_head(&mut lines);
+
+    let mut sections: Vec<Section> = Vec::new();
+    for line in lines {
+        match heading(line) {
+            Some((3, x)) => sections.push(Section::new(x)),
+            _ => if let Some(section) = sections.last_mut() { section.lines.push(unquote(line)) },
+        }
+    }
+
+    // Give the sections back their notes (in the order they were exported)
+    for (title, notes) in section_notes {
+        let section = sections.iter_mut().find(|x| x.title == title && x.notes.is_empty());
+        if let Some(section) = section { section.notes = notes }
+    }
+
+    (title, description, notes, sections)
+}
+
+/// Parses the body of a moc's note as `export` wrote it into a moc config: its head, then a `##` heading per collection followed by what it includes (its links are left out, as they're found again from that)
+fn parse_moc(uid: String, tags: Vec<String>, private: bool, lines: Vec<&str>) -> Table {
+    let mut lines = lines.into_iter().peekable();
+    let Head { title, description, notes, part_notes } = parse_head(&mut lines);
+
+    let mut collections: Vec<Table> = Vec::new();
+    for line in lines {
+        if let Some((2, x)) = heading(line) {
+            let mut collection = Table::new();
+            collection.insert("title".into(), x.into());
+            collection.insert("notes".into(), Value::Array(Vec::new()));
+            collection.insert("include".into(), Value::Array(Vec::new()));
+            collections.push(collection);
+        } else if let Some(spec) = line.strip_prefix(COLLECTION_MARKER).and_then(|x| x.strip_suffix("%%")) {
+            let spec = format!("spec = {spec}").parse::<Table>().ok().and_then(|mut x| x.remove("spec"));
+            if let (Some(Value::Table(spec)), Some(collection)) = (spec, collections.last_mut()) { collection.extend(spec) }
+        }
+    }
+
+    // Give the collections back their notes (in the order they were exported)
+    for (title, notes) in part_notes {
+        let collection = collections.iter_mut().find(|x| x["title"].as_str() == Some(&title) && x["notes"].as_array().is_some_and(|x| x.is_empty()));
+        if let Some(collection) = collection { collection.insert("notes".into(), notes.into()); }
+    }
+
+    let mut moc = Table::new();
+    moc.insert("uid".into(), uid.clone().into());
+    moc.insert("title".into(), title.unwrap_or(uid).into());
+    moc.insert("description".into(), description.into());
+    moc.insert("notes".into(), notes.into());
+    moc.insert("tags".into(), tags.into());
+    if private { moc.insert("private".into(), true.into()); }
+
+    let mut table = Table::new();
+    table.insert("is-moc".into(), true.into());
+    table.insert("moc".into(), moc.into());
+    table.insert("collection".into(), collections.into());
+    table
+}
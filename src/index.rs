@@ -0,0 +1,299 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use lazy_db::*;
+use soulog::*;
+use crate::{archive::Archive, Home, Error, crypt, list, search::Searchable, transaction, Context};
+
+/// The group of terms an item is indexed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    Tags,
+    Words,
+}
+
+impl Group {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Group::Tags => "tags",
+            Group::Words => "words",
+        }
+    }
+}
+
+#[inline]
+fn kind(is_moc: bool) -> &'static str {
+    if is_moc { "mocs" } else { "entries" }
+}
+
+/// Words longer than this (in bytes) aren't indexed
+pub const MAX_WORD_LEN: usize = 64;
+
+/// Splits text into the lowercase words it is indexed by
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty() && x.len() <= MAX_WORD_LEN)
+        .map(|x| x.to_lowercase())
+}
+
+/// Encodes a term into a key that is safe to use as a file name
+pub fn encode_key(term: &str) -> String {
+    term.bytes().map(|x| format!("{x:02x}")).collect()
+}
+
+/// Decodes a key made by `encode_key` back into its term
+pub fn decode_key(key: &str) -> Option<String> {
+    if !key.len().is_multiple_of(2) { return None };
+    let bytes = (0..key.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(key.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// The key a term's postings are stored under; encrypted archives use a keyed hash of it so their terms aren't left in the clear
+fn key(container: &LazyContainer, term: &str) -> String {
+    crypt::blind(container.path(), term).unwrap_or_else(|| encode_key(term))
+}
+
+/// Writes a term's postings; encrypted archives also store the term itself (encrypted) as its key can't be decoded
+fn write_postings(postings: &LazyContainer, term: &str, uids: &[impl AsRef<str>]) -> crate::Result<()> {
+    list::write_strings(uids, postings)?;
+    if crypt::keys_for(postings.path()).is_some() {
+        crypt::write_string(postings, "term", term).with_context(|| format!("While writing postings of '{term}'"))?;
+    }
+    Ok(())
+}
+
+/// Reads the term of a postings container from its key (or from the term stored with it in encrypted archives)
+fn read_term(path: &Path) -> Option<String> {
+    match path.join("term").is_file() {
+        true => LazyData::load(path.join("term")).and_then(crypt::collect_string).ok(),
+        false => decode_key(&path.file_name()?.to_string_lossy()),
+    }
+}
+
+fn group_container(archive: &Archive, group: Group, is_moc: bool) -> crate::Result<LazyContainer> {
+    search_database!((archive.database()) /index/(kind(is_moc))/(group.name()))
+        .with_context(|| format!("While loading the {} index of {}", group.name(), kind(is_moc)))
+}
+
+/// Makes sure the archive has an index, building one (as a transaction) if it doesn't; only for archives loaded to modify them
+pub(crate) fn ensure(archive: &Archive, mut logger: impl Logger) -> crate::Result<()> {
+    if archive.database_exists("index") { return Ok(()) };
+    log!((logger.vital) Index("Archive has no index; building one...") as Inconvenience);
+    transaction::run(archive, logger.hollow(), |archive| rebuild(archive, logger))
+}
+
+/// Fails if the archive has no index, rather than building one, as it may only be loaded to read it
+fn present(archive: &Archive) -> crate::Result<()> {
+    match archive.database_exists("index") {
+        true => Ok(()),
+        false => Err(Error::NotFound("Archive has no index; run `diary-cli reindex` to build one".into())),
+    }
+}
+
+/// Adds an item's tags and words to the index
+pub fn insert(archive: &Archive, item: &mut impl Searchable, is_moc: bool, mut logger: impl Logger) -> crate::Result<()> {
+    present(archive)?;
+    let uid = item.get_uid();
+    log!((logger) Index("Indexing {} '{uid}'...", if is_moc { "moc" } else { "entry" }));
+
+    let tags = group_container(archive, Group::Tags, is_moc)?;
+    for tag in item.get_tags()?.iter() {
+        add_posting(archive, &tags, tag, &uid)?;
+    }
+
+    let words = group_container(archive, Group::Words, is_moc)?;
+    for word in item.get_words()? {
+        add_posting(archive, &words, &word, &uid)?;
+    }
+
+    Ok(())
+}
+
+/// Removes an item's tags and words from the index; the item must still be in the archive
+pub fn remove(archive: &Archive, item: &mut impl Searchable, is_moc: bool, mut logger: impl Logger) -> crate::Result<()> {
+    present(archive)?;
+    let uid = item.get_uid();
+    log!((logger) Index("Removing {} '{uid}' from index...", if is_moc { "moc" } else { "entry" }));
+
+    let tags = group_container(archive, Group::Tags, is_moc)?;
+    for tag in item.get_tags()?.iter() {
+        remove_posting(archive, &tags, tag, &uid)?;
+    }
+
+    let words = group_container(archive, Group::Words, is_moc)?;
+    for word in item.get_words()? {
+        remove_posting(archive, &words, &word, &uid)?;
+    }
+
+    Ok(())
+}
+
+/// Records a term's postings in the archive's transaction journal before they're changed
+fn touch(archive: &Archive, container: &LazyContainer, key: &str) -> crate::Result<()> {
+    let path = container.path().join(key);
+    archive.touch(path.strip_prefix(archive.database().path()).unwrap_or(&path))
+}
+
+fn add_posting(archive: &Archive, container: &LazyContainer, term: &str, uid: &str) -> crate::Result<()> {
+    let key = key(container, term);
+    touch(archive, container, &key)?;
+    let exists = container.path().join(&key).is_dir();
+    let postings = container.child_container(&key).with_context(|| format!("While loading postings of '{term}'"))?;
+
+    if !exists {
+        return write_postings(&postings, term, &[uid]);
+    }
+
+    let uids = list::read_strings(&postings)?;
+    if !uids.iter().any(|x| x == uid) {
+        list::push_string(uid, &postings)?;
+    }
+
+    Ok(())
+}
+
+fn remove_posting(archive: &Archive, container: &LazyContainer, term: &str, uid: &str) -> crate::Result<()> {
+    let key = key(container, term);
+    if !container.path().join(&key).is_dir() { return Ok(()) };
+    touch(archive, container, &key)?;
+    let postings = container.child_container(&key).with_context(|| format!("While loading postings of '{term}'"))?;
+
+    let uids: Vec<String> = list::read_strings(&postings)?
+        .into_vec()
+        .into_iter()
+        .filter(|x| x != uid)
+        .collect();
+
+    if uids.is_empty() {
+        container.remove(&key).with_context(|| format!("While removing postings of '{term}'"))
+    } else {
+        write_postings(&postings, term, &uids)
+    }
+}
+
+/// Returns the uids of the items indexed under a term
+pub fn lookup(archive: &Archive, group: Group, term: &str, is_moc: bool, mut logger: impl Logger) -> crate::Result<Box<[String]>> {
+    present(archive)?;
+    log!((logger) Index("Looking up '{term}' in the {} index of {}...", group.name(), kind(is_moc)));
+    let container = group_container(archive, group, is_moc)?;
+    let key = key(&container, term);
+    if !container.path().join(&key).is_dir() {
+        return Ok(Vec::with_capacity(0).into_boxed_slice());
+    }
+
+    list::read_strings(
+        &container.read_container(&key).with_context(|| format!("While loading postings of '{term}'"))?,
+    )
+}
+
+/// Returns the uids of the items that have any (or all if strict) of the terms, in alphabetical order
+pub fn search(archive: &Archive, group: Group, terms: &[String], strict: bool, is_moc: bool, logger: impl Logger) -> crate::Result<Vec<String>> {
+    let mut result: Option<BTreeSet<String>> = None;
+
+    for term in terms {
+        let uids: BTreeSet<String> = lookup(archive, group, term, is_moc, logger.hollow())?.into_vec().into_iter().collect();
+        result = Some(match result {
+            None => uids,
+            Some(x) if strict => x.intersection(&uids).cloned().collect(),
+            Some(x) => x.union(&uids).cloned().collect(),
+        });
+    }
+
+    Ok(result.map(|x| x.into_iter().collect()).unwrap_or_default())
+}
+
+/// Returns every term indexed in a group, in alphabetical order
+pub fn terms(archive: &Archive, group: Group, is_moc: bool, mut logger: impl Logger) -> crate::Result<Vec<String>> {
+    present(archive)?;
+    log!((logger) Index("Listing the terms in the {} index of {}...", group.name(), kind(is_moc)));
+    let container = group_container(archive, group, is_moc)?;
+    let dir = std::fs::read_dir(container.path()).with_context(|| format!("While reading the {} index", group.name()))?;
+
+    let mut terms: Vec<String> = dir.into_iter()
+        .filter_map(|x| x.ok())
+        .filter_map(|x| read_term(&x.path()))
+        .collect();
+    terms.sort();
+    Ok(terms)
+}
+
+/// Wipes the index and builds it again from every entry and moc in the archive
+pub fn rebuild(archive: &Archive, mut logger: impl Logger) -> crate::Result<()> {
+    log!((logger) Index("Wiping index..."));
+    archive.touch("index")?;
+    let root = archive.database().as_container().context("While loading archive as container")?;
+    root.new_container("index").context("While wiping index")?;
+
+    log!((logger) Index("Indexing entries..."));
+    write_all(archive, archive.list_entries(logger.hollow())?, false)?;
+    log!((logger) Index("Indexing mocs..."));
+    write_all(archive, archive.list_mocs(logger.hollow())?, true)
+}
+
+fn write_all(archive: &Archive, items: Vec<impl Searchable>, is_moc: bool) -> crate::Result<()> {
+    let mut tags = HashMap::<String, Vec<String>>::new();
+    let mut words = HashMap::<String, Vec<String>>::new();
+
+    for mut item in items {
+        let uid = item.get_uid();
+        for tag in item.get_tags()?.iter() {
+            tags.entry(tag.clone()).or_default().push(uid.clone());
+        }
+        for word in item.get_words()? {
+            words.entry(word).or_default().push(uid.clone());
+        }
+    }
+
+    for (group, postings) in [(Group::Tags, tags), (Group::Words, words)] {
+        let container = group_container(archive, group, is_moc)?;
+        for (term, uids) in postings {
+            write_postings(
+                &container.new_container(key(&container, &term)).with_context(|| format!("While writing postings of '{term}'"))?,
+                &term,
+                &uids,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the postings of an archive's index to the keys of its terms, for after it was encrypted, decrypted or its keys changed
+pub(crate) fn rekey(root: &Path) -> crate::Result<()> {
+    for kind in ["entries", "mocs"] {
+        for group in [Group::Tags, Group::Words] {
+            let path = root.join("index").join(kind).join(group.name());
+            if !path.is_dir() { continue }
+            let container = LazyContainer::load(&path).with_context(|| format!("While loading the {} index of {kind}", group.name()))?;
+
+            for x in fs::read_dir(&path).with_context(|| format!("While reading the {} index", group.name()))? {
+                let x = x.with_context(|| format!("While reading the {} index", group.name()))?.path();
+                let Some(term) = read_term(&x) else { continue }; // left for `reindex`
+                let new = path.join(key(&container, &term));
+                if new != x {
+                    fs::rename(&x, &new).with_context(|| format!("While moving postings of '{term}'"))?;
+                }
+
+                let postings = LazyContainer::load(&new).with_context(|| format!("While loading postings of '{term}'"))?;
+                match crypt::keys_for(&new) {
+                    Some(_) => crypt::write_string(&postings, "term", &term).with_context(|| format!("While writing postings of '{term}'"))?,
+                    None if new.join("term").exists() => postings.remove("term").with_context(|| format!("While writing postings of '{term}'"))?,
+                    None => (),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn reindex(home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
+    log!((logger) Index("Rebuilding the archive's index..."));
+    rebuild(&archive, logger.hollow())?;
+    log!((logger.vital) Index("Successfully rebuilt the archive's index") as Log);
+    Ok(())
+}
@@ -1,4 +1,18 @@
 pub mod cli;
+pub mod error;
+pub mod home;
+pub mod diaries;
+pub mod backups;
+pub mod transaction;
+pub mod lock;
+pub mod migrate;
+pub mod crypt;
+pub mod history;
+pub mod scaffold;
+pub mod edit;
+pub mod check;
+pub mod commit;
+pub mod watch;
 pub mod logger;
 pub mod list;
 pub mod entry;
@@ -7,19 +21,19 @@ pub mod moc;
 pub mod since;
 pub mod pull;
 pub mod export;
+pub mod import;
 pub mod search;
+pub mod find;
+pub mod index;
+pub mod fsck;
+pub mod query;
 pub mod sort;
 pub mod scribe;
 pub mod about;
+pub mod show;
 pub mod uncommit;
 
+pub use error::{Error, Result, Context};
+pub use home::Home;
 pub use logger::*;
 pub use scribe::*;
-
-pub fn home_dir() -> std::path::PathBuf {
-    // Linux only; change this if you want to go cross platform
-    match std::env::var("HOME") {
-        Ok(path) => std::path::Path::new(&path).join(".diary-cli"),
-        Err(_) => std::path::PathBuf::from("/etc/diary-cli/"),
-    }
-}
\ No newline at end of file
@@ -1,144 +1,140 @@
 use std::marker::PhantomData;
 
 use lazy_db::*;
-use soulog::*;
+use crate::{crypt, Context, Error};
 
-pub fn write<T>(list: &[T], f: impl Fn(FileWrapper, &T) -> Result<(), LDBError>, container: &LazyContainer, mut logger: impl Logger) {
-    if_err!((logger) [ListIO, err => ("While clearing container: {err:?}")] retry container.wipe());
+fn too_long() -> Error {
+    Error::Overflow(format!("Lists can't have more than {} elements", u32::MAX))
+}
+
+/// Converts a length to how it is stored in the archive, erroring instead of wrapping around if it's too long
+pub fn stored_length(length: usize) -> crate::Result<u32> {
+    u32::try_from(length).map_err(|_| too_long())
+}
+
+pub fn write<T>(list: &[T], f: impl Fn(FileWrapper, &T) -> Result<(), LDBError>, container: &LazyContainer) -> crate::Result<()> {
+    container.wipe().context("While clearing list container")?;
 
     for (i, x) in list.iter().enumerate() {
-        let data_writer = 
-            if_err!((logger) [ListIO, err => ("While writing element of list: {:?}", err)] retry container.data_writer(i.to_string()));
-        if_err!((logger) [ListIO, err => ("While writing element of list: {:?}", err)] {f(data_writer, x)} crash {
-            log!((logger.error) ListIO("{err:#?}") as Fatal);
-            logger.crash()
-        })
+        let data_writer = container.data_writer(i.to_string()).context("While writing element of list")?;
+        f(data_writer, x).context("While writing element of list")?;
     }
 
-    if_err!((logger) [ListIO, err => ("{:?}", err)] retry {
-        let data_writer = if_err!((logger) [ListIO, err => ("While writing list length: {:?}", err)] retry container.data_writer("length"));
-        LazyData::new_u16(data_writer, list.len() as u16)
-    })
+    let length = stored_length(list.len())?;
+    write_container!((container) length = new_u32(length)).context("While writing list length")
 }
 
-pub fn push(f: impl Fn(FileWrapper) -> Result<(), LDBError>, container: &LazyContainer, mut logger: impl Logger) {
-    let length = load_length(container, logger.hollow());
+pub fn push(f: impl Fn(FileWrapper) -> Result<(), LDBError>, container: &LazyContainer) -> crate::Result<()> {
+    let length = load_length(container)?;
+    let new_length = length.checked_add(1).ok_or_else(too_long)?;
 
-    let data_writer = if_err!((logger) [ListIO, err => ("While pushing to list: {:?}", err)] retry container.data_writer(length.to_string()));
-    if_err!((logger) [ListIO, err => ("While pushing to list: {:?}", err)] {f(data_writer)} crash {
-        log!((logger.error) ListIO("{err:#?}") as Fatal);
-        logger.crash()
-    });
+    let data_writer = container.data_writer(length.to_string()).context("While pushing to list")?;
+    f(data_writer).context("While pushing to list")?;
 
-    if_err!((logger) [ListIO, err => ("{:?}", err)] retry {
-        let data_writer = if_err!((logger) [ListIO, err => ("While writing list length: {:?}", err)] retry container.data_writer("length"));
-        LazyData::new_u16(data_writer, length + 1)
-    })
+    write_container!((container) length = new_u32(new_length)).context("While writing list length")
 }
 
-pub fn pop<T>(f: impl Fn(LazyData) -> Result<T, LDBError>, container: &LazyContainer, mut logger: impl Logger) -> Option<T> {
-    let length = load_length(container, logger.hollow());
+pub fn pop<T>(f: impl Fn(LazyData) -> Result<T, LDBError>, container: &LazyContainer) -> crate::Result<Option<T>> {
+    let length = load_length(container)?;
 
     if length == 0 {
-        return None;
+        return Ok(None);
     }
 
     let idx = (length - 1).to_string();
-    let item = if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] retry container.read_data(&idx));
-    let item = if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] {f(item)} crash {
-        log!((logger.error) ListIO("{err:#?}") as Fatal);
-        logger.crash()
-    });
-
-    if_err!((logger) {container.remove(idx)} else(err) {
-        log!((logger.vital) ListIO("While removing item: {err:?}; ignoring error...") as Inconvenience)
-    });
-
-    if_err!((logger) [ListIO, err => ("{:?}", err)] retry {
-        let data_writer = if_err!((logger) [ListIO, err => ("While writing list length: {:?}", err)] retry container.data_writer("length"));
-        LazyData::new_u16(data_writer, length - 1)
-    });
-
-    Some(item)
+    let item = container.read_data(&idx)
+        .and_then(f)
+        .context("While reading list element")?;
+
+    container.remove(idx).context("While removing list element")?;
+    write_container!((container) length = new_u32(length - 1)).context("While writing list length")?;
+
+    Ok(Some(item))
 }
 
-pub fn read<T>(f: impl Fn(LazyData) -> Result<T, LDBError>, container: &LazyContainer, mut logger: impl Logger) -> Box<[T]> {
-    let length = load_length(container, logger.hollow());
+pub fn read<T>(f: impl Fn(LazyData) -> Result<T, LDBError>, container: &LazyContainer) -> crate::Result<Box<[T]>> {
+    let length = load_length(container)?;
 
     let mut list = Vec::<T>::with_capacity(length as usize);
 
     for i in 0..length {
-        let item = if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] retry container.read_data(i.to_string()));
-        let item = if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] {f(item)} crash {
-            log!((logger.error) ListIO("{err:#?}") as Fatal);
-            logger.crash()
-        });
+        let item = container.read_data(i.to_string())
+            .and_then(&f)
+            .context("While reading list element")?;
         list.push(item)
     }
 
-    list.into_boxed_slice()
+    Ok(list.into_boxed_slice())
+}
+
+/// Writes a list of strings, encrypting them if the container is within an encrypted archive
+pub fn write_strings<S: AsRef<str>>(list: &[S], container: &LazyContainer) -> crate::Result<()> {
+    let keys = crypt::keys_for(container.path());
+    write(list, |file, x| crypt::new_string(file, keys.as_deref(), x.as_ref()), container)
+}
+
+/// Pushes a string onto a list, encrypting it if the container is within an encrypted archive
+pub fn push_string(value: &str, container: &LazyContainer) -> crate::Result<()> {
+    let keys = crypt::keys_for(container.path());
+    push(|file| crypt::new_string(file, keys.as_deref(), value), container)
+}
+
+/// Reads a list of strings, decrypting them if they were encrypted
+#[inline]
+pub fn read_strings(container: &LazyContainer) -> crate::Result<Box<[String]>> {
+    read(crypt::collect_string, container)
 }
 
-pub fn load_length(container: &LazyContainer, mut logger: impl Logger) -> u16 {
-    let length = if_err!((logger) [ListIO, err => ("While reading list length: {:?}", err)] retry container.read_data("length"));
-    if_err!((logger) [ListIO, err => ("While reading list length: {:?}", err)] {length.collect_u16()} crash {
-        log!((logger.error) ListIO("{err:#?}") as Fatal);
-        logger.crash()
-    })
+pub fn load_length(container: &LazyContainer) -> crate::Result<u32> {
+    container.read_data("length")
+        .and_then(|x| x.collect_u32())
+        .context("While reading list length")
 }
 
-pub struct List<T, L: Logger, F: Fn(LazyData) -> Result<T, LDBError>> {
+pub struct List<T, F: Fn(LazyData) -> Result<T, LDBError>> {
     _phantom_marker: PhantomData<T>,
     read_f: F,
     container: LazyContainer,
-    logger: L,
-    length: u16,
-    idx: u16,
+    length: u32,
+    idx: u32,
 }
 
-impl<T, L: Logger, F: Fn(LazyData) -> Result<T, LDBError>> List<T, L, F> {
-    pub fn init(container: LazyContainer, read_f: F, mut logger: L) -> Self {
-        if_err!((logger) [List, err => ("While inititalising list: {err:?}")] retry write_container!((container) length = new_u16(0)));
-        Self {
+impl<T, F: Fn(LazyData) -> Result<T, LDBError>> List<T, F> {
+    pub fn init(container: LazyContainer, read_f: F) -> crate::Result<Self> {
+        write_container!((container) length = new_u32(0)).context("While initialising list")?;
+        Ok(Self {
             _phantom_marker: PhantomData,
             container,
             read_f,
-            logger: logger.hollow(),
             length: 0,
             idx: 0,
-        }
+        })
     }
 
-    pub fn load(container: LazyContainer, read_f: F, mut logger: L) -> Self {
-        let length = if_err!((logger) [List, err => ("While loading list: {err:?}")] retry 
-            if_err!((logger) [List, err => ("While loading list: {err:?}")] retry
-                search_container!((container) length)
-            ).collect_u16()
-        );
+    pub fn load(container: LazyContainer, read_f: F) -> crate::Result<Self> {
+        let length = load_length(&container)?;
 
-        Self {
+        Ok(Self {
             _phantom_marker: PhantomData,
             container,
             read_f,
             length,
-            logger: logger.hollow(),
             idx: 0,
-        }
+        })
     }
 }
 
-impl<T, L:Logger, F: Fn(LazyData) -> Result<T, LDBError>> Iterator for List<T, L, F> {
-    type Item = T;
+impl<T, F: Fn(LazyData) -> Result<T, LDBError>> Iterator for List<T, F> {
+    type Item = crate::Result<T>;
 
-    fn next(&mut self) -> Option<T> {
+    fn next(&mut self) -> Option<crate::Result<T>> {
         if self.idx >= self.length { return None };
-        let mut logger = self.logger.hollow();
         let f: &F = &self.read_f;
 
-        let item = if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] retry self.container.read_data(self.idx.to_string()));
-        Some(if_err!((logger) [ListIO, err => ("While reading list element: {:?}", err)] {f(item)} crash {
-            log!((logger.error) ListIO("{err:#?}") as Fatal);
-            logger.crash()
-        }))
+        let item = self.container.read_data(self.idx.to_string())
+            .and_then(f)
+            .context("While reading list element");
+        self.idx += 1;
+        Some(item)
     }
-}
\ No newline at end of file
+}
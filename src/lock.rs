@@ -0,0 +1,147 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use soulog::*;
+use crate::cli::WAIT;
+use crate::{Context, Error, Home, Result};
+
+/// Whether a lock is for reading the archive (shared with other readers) or for modifying it (held alone)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+/// A lock file this process holds, counted so nested loads of the same archive don't wait on themselves
+struct Held {
+    path: PathBuf,
+    file: File,
+    shared: usize,
+    exclusive: usize,
+}
+
+static HELD: Mutex<Vec<Held>> = Mutex::new(Vec::new());
+
+/// An advisory lock on a diary's archive, released once dropped (or once the process exits, even if it crashed)
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+    kind: LockKind,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|x| x.into_inner());
+        let Some(i) = held.iter().position(|x| x.path == self.path) else { return };
+        let x = &mut held[i];
+        match self.kind {
+            LockKind::Shared => x.shared -= 1,
+            LockKind::Exclusive => x.exclusive -= 1,
+        }
+
+        if self.kind == LockKind::Exclusive && x.exclusive == 0 {
+            let _ = x.file.set_len(0); // no longer the holder to name
+            if x.shared > 0 {
+                let _ = x.file.lock_shared(); // back to reading only
+            }
+        }
+        if x.shared == 0 && x.exclusive == 0 {
+            held.remove(i); // closing the file releases the lock
+        }
+    }
+}
+
+/// Describes who holds the lock, from what its exclusive holder wrote into the lock file
+fn holder(mut file: &File) -> String {
+    let mut contents = String::new();
+    let _ = file.seek(SeekFrom::Start(0)).and_then(|_| file.read_to_string(&mut contents));
+    match contents.split_once('\n') {
+        Some((pid, command)) if !pid.is_empty() => format!("process {pid} (`{}`)", command.trim()),
+        _ => "another process reading it".into(),
+    }
+}
+
+/// Writes this process into the lock file so that others blocked on it can name it
+fn claim(mut file: &File) -> Result<()> {
+    let mut args = std::env::args();
+    let program = args.next()
+        .map(|x| Path::new(&x).file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or(x))
+        .unwrap_or_else(|| "diary-cli".into());
+    let command = std::iter::once(program).chain(args).collect::<Vec<_>>().join(" ");
+
+    file.set_len(0).context("While writing the archive's lock file")?;
+    file.seek(SeekFrom::Start(0)).context("While writing the archive's lock file")?;
+    write!(file, "{}\n{command}\n", std::process::id()).context("While writing the archive's lock file")
+}
+
+/// Takes the lock on the file, waiting for it only if `--wait` was specified
+fn take(file: &File, kind: LockKind, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let result = match kind {
+        LockKind::Shared => file.try_lock_shared(),
+        LockKind::Exclusive => file.try_lock(),
+    };
+    match result {
+        Ok(()) => return Ok(()),
+        Err(TryLockError::WouldBlock) => (),
+        Err(TryLockError::Error(err)) => return Err(err).context("While locking the archive"),
+    }
+
+    let holder = holder(file);
+    let archive = home.archive();
+    if !unsafe { WAIT } {
+        return Err(Error::Locked(format!("Archive '{}' is in use by {holder}; try again once it's done or use `--wait` to wait for it", archive.to_string_lossy())));
+    }
+
+    log!((logger.vital) Lock("Archive '{}' is in use by {holder}; waiting for it...", archive.to_string_lossy()) as Inconvenience);
+    match kind {
+        LockKind::Shared => file.lock_shared(),
+        LockKind::Exclusive => file.lock(),
+    }.context("While waiting for the archive's lock")
+}
+
+/// Locks the archive of the home's diary for reading (`Shared`) or for modifying it (`Exclusive`)
+///
+/// Locks this process already holds are reused, and a shared one is upgraded if an exclusive one is asked for
+pub fn acquire(home: &Home, kind: LockKind, mut logger: impl Logger) -> Result<Lock> {
+    let path = home.lock();
+    let mut held = HELD.lock().unwrap_or_else(|x| x.into_inner());
+
+    if let Some(x) = held.iter_mut().find(|x| x.path == path) {
+        match kind {
+            LockKind::Shared => x.shared += 1,
+            LockKind::Exclusive if x.exclusive > 0 => x.exclusive += 1,
+            LockKind::Exclusive => {
+                log!((logger) Lock("Upgrading the lock on the archive to modify it..."));
+                if let Err(err) = take(&x.file, kind, home, logger.hollow()) {
+                    let _ = x.file.lock_shared(); // a failed upgrade may have let go of the shared lock
+                    return Err(err);
+                }
+                claim(&x.file)?;
+                x.exclusive += 1;
+            },
+        }
+        return Ok(Lock { path, kind });
+    }
+
+    log!((logger) Lock("Locking archive '{}'...", home.archive().to_string_lossy()));
+    fs::create_dir_all(home.path()).with_context(|| format!("While creating directory '{}'", home.path().to_string_lossy()))?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false) // its contents name the current holder
+        .open(&path)
+        .with_context(|| format!("While opening lock file '{}'", path.to_string_lossy()))?;
+    take(&file, kind, home, logger.hollow())?;
+    if kind == LockKind::Exclusive {
+        claim(&file)?;
+    }
+
+    let (shared, exclusive) = match kind {
+        LockKind::Shared => (1, 0),
+        LockKind::Exclusive => (0, 1),
+    };
+    held.push(Held { path: path.clone(), file, shared, exclusive });
+    Ok(Lock { path, kind })
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use lazy_db::*;
+use soulog::*;
+use crate::{archive::{self, Archive}, backups, entry::DATE_FORMAT, transaction, Context, Error, Home};
+
+/// The format version of the archives this version of the cli writes (archives from before there were format versions are version `0`)
+pub const FORMAT_VERSION: u16 = 2;
+
+/// A step that upgrades an archive from the format version before `to`
+pub struct Migration {
+    pub to: u16,
+    pub description: &'static str,
+    /// Upgrades the archive, returning a line for each thing it changed
+    run: fn(&Archive) -> crate::Result<Vec<String>>,
+}
+
+/// Every migration in order; the last one's `to` is `FORMAT_VERSION`
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { to: 1, description: "store entry dates as `yyyy-mm-dd` instead of `[day, month, year]` arrays", run: dates },
+    Migration { to: 2, description: "store list lengths and the itver as u32s instead of u16s", run: widen },
+];
+
+/// Entry dates used to be stored as `[day, month, year]` arrays of u16s
+fn dates(archive: &Archive) -> crate::Result<Vec<String>> {
+    let entries = archive.database().path().join("entries");
+    if !entries.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut uids = archive::list_dirs(&entries)?;
+    uids.sort();
+    let mut changes = Vec::new();
+    for uid in uids {
+        let path = entries.join(&uid);
+        // Missing and already converted dates are left to `fsck`
+        let Ok(array) = LazyData::load(path.join("date")).and_then(|x| x.collect_u16_array()) else { continue };
+
+        // Invalid dates are kept as they were (for `fsck` to find) rather than stopping the migration
+        let date = match array.as_ref() {
+            [day, month, year] => match NaiveDate::from_ymd_opt(*year as i32, *month as u32, *day as u32) {
+                Some(x) => x.format(DATE_FORMAT).to_string(),
+                None => format!("{year:04}-{month:02}-{day:02}"),
+            },
+            _ => format!("{array:?}"),
+        };
+
+        let container = LazyContainer::load(&path).with_context(|| format!("While loading entry '{uid}'"))?;
+        write_container!((container) date = new_string(&date)).with_context(|| format!("While writing entry '{uid}'s date"))?;
+        changes.push(format!("entries/{uid}/date: {array:?} -> {date}"));
+    }
+    Ok(changes)
+}
+
+/// Widens the u16 list lengths within a directory (and its subdirectories) to u32s, returning how many it widened
+fn widen_lengths(path: &Path) -> crate::Result<usize> {
+    let mut widened = 0;
+    for x in fs::read_dir(path).with_context(|| format!("While reading directory '{}'", path.to_string_lossy()))? {
+        let x = x.with_context(|| format!("While reading directory '{}'", path.to_string_lossy()))?;
+        if x.file_type().context("While reading a file's type")?.is_dir() {
+            widened += widen_lengths(&x.path())?;
+            continue;
+        }
+        if x.file_name() != "length" { continue }
+
+        // Unreadable lengths are left to `fsck`
+        let Ok(data) = LazyData::load(x.path()) else { continue };
+        if data.lazy_type != LazyType::U16 { continue }
+        let length = data.collect_u16().with_context(|| format!("While reading list length '{}'", x.path().to_string_lossy()))?;
+        let container = LazyContainer::load(path).with_context(|| format!("While loading list '{}'", path.to_string_lossy()))?;
+        write_container!((container) length = new_u32(length as u32)).with_context(|| format!("While writing list length '{}'", x.path().to_string_lossy()))?;
+        widened += 1;
+    }
+    Ok(widened)
+}
+
+/// List lengths and the itver used to be u16s, which long-running diaries would outgrow
+fn widen(archive: &Archive) -> crate::Result<Vec<String>> {
+    let widened = widen_lengths(archive.database().path())?;
+    let itver = search_database!((archive.database()) itver).and_then(|x| x.collect_u16()).context("While reading archive itver")?;
+    write_database!((archive.database()) itver = new_u32(itver as u32)).context("While writing archive itver")?;
+    Ok(vec![format!("{widened} list length(s) widened"), format!("itver: {itver} widened")])
+}
+
+/// Refuses an archive of a newer format version than this version of the cli supports
+fn check_supported(archive: &Archive) -> crate::Result<()> {
+    if archive.format_version > FORMAT_VERSION {
+        return Err(Error::Conflict(format!(
+            "Archive '{}' has format version {} but this version of diary-cli only supports up to {FORMAT_VERSION}; update diary-cli to use it",
+            archive.home().archive().to_string_lossy(),
+            archive.format_version,
+        )));
+    }
+    Ok(())
+}
+
+/// The migrations an archive still needs, erroring if it's from a newer version of the cli
+pub fn pending(archive: &Archive) -> crate::Result<Vec<&'static Migration>> {
+    check_supported(archive)?;
+    Ok(MIGRATIONS.iter().filter(|x| x.to > archive.format_version).collect())
+}
+
+/// Returns if the archive is of an older format version
+#[inline]
+pub fn outdated(archive: &Archive) -> crate::Result<bool> {
+    Ok(!pending(archive)?.is_empty())
+}
+
+/// Runs the migrations one step at a time, returning the changes each one made
+fn apply(archive: &Archive, pending: &[&Migration]) -> crate::Result<Vec<Vec<String>>> {
+    let mut changes = Vec::with_capacity(pending.len());
+    for x in pending {
+        changes.push((x.run)(archive)?);
+        write_database!((archive.database()) format_version = new_u16(x.to)).context("While writing format version")?;
+    }
+    Ok(changes)
+}
+
+/// Migrates an archive of an older format version to the current one (after backing it up); returns if it did anything
+pub fn upgrade(archive: &Archive, mut logger: impl Logger) -> crate::Result<bool> {
+    let pending = pending(archive)?;
+    if pending.is_empty() {
+        return Ok(false);
+    }
+
+    log!((logger.vital) Migrate("Migrating archive '{}' from format version {} to {FORMAT_VERSION}...", archive.home().archive().to_string_lossy(), archive.format_version) as Log);
+    backups::create(archive, &format!("migrate to format version {FORMAT_VERSION}"), logger.hollow())?;
+    let changes = transaction::run_all(archive, logger.hollow(), |archive| apply(archive, &pending))?;
+    for (x, changes) in pending.iter().zip(changes) {
+        log!((logger) Migrate("Format version {}: {} ({} change(s))", x.to, x.description, changes.len()));
+    }
+    log!((logger.vital) Migrate("Successfully migrated archive to format version {FORMAT_VERSION}") as Log);
+    Ok(true)
+}
+
+pub fn migrate_command(dry_run: bool, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load_unmigrated(home, logger.hollow())?;
+    let pending = pending(&archive)?;
+    if pending.is_empty() {
+        log!((logger.vital) Migrate("Archive '{}' is already at format version {FORMAT_VERSION}", home.archive().to_string_lossy()) as Log);
+        return Ok(());
+    }
+
+    if !dry_run {
+        upgrade(&archive, logger)?;
+        return Ok(());
+    }
+
+    // Each step may depend on the ones before it, so they are run for real on a copy
+    let changes = transaction::preview(&archive, logger.hollow(), |staged| apply(staged, &pending))?;
+    for (x, changes) in pending.iter().zip(changes) {
+        log!((logger.vital) Migrate("Format version {}: {}", x.to, x.description) as Log);
+        for change in changes {
+            log!((logger.vital) Migrate("  {change}") as Log);
+        }
+    }
+    log!((logger.vital) Migrate("Dry run; run `diary-cli migrate` to migrate the archive from format version {} to {FORMAT_VERSION} (it is also migrated the next time it's used)", archive.format_version) as Log);
+    Ok(())
+}
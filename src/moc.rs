@@ -3,24 +3,25 @@ pub mod collection;
 pub use collection::*;
 use soulog::*;
 use lazy_db::*;
-use crate::{entry::*, search::Searchable};
+use crate::{entry::*, search::Searchable, find::Field, Context};
 use toml::Table;
 
 // Some ease of life macros
 macro_rules! get {
-    ($key:ident at $moc:ident from $table:ident as $func:ident with $logger:ident) => {{
+    ($key:ident at $moc:ident from $table:ident as $func:ident) => {{
         let key = stringify!($key);
-        let obj = unwrap_opt!(($table.get(key)) with $logger, format: MOC("moc '{0}' must have '{key}' attribute", $moc));
+        let obj = unwrap_opt!(($table.get(key)) else Validation("moc '{0}' must have '{key}' attribute", $moc));
 
-        unwrap_opt!((obj.$func()) with $logger, format: MOC("moc '{0}'s '{key}' attribute must be of correct type", $moc))
+        unwrap_opt!((obj.$func()) else Validation("moc '{0}'s '{key}' attribute must be of correct type", $moc))
     }};
 
-    ($var:ident = $key:ident at $entry:ident from $table:ident as $func:ident with $logger:ident or $default:expr) => {
+    ($var:ident = $key:ident at $entry:ident from $table:ident as $func:ident or $default:expr) => {
         let key = stringify!($key);
         let default = $default;
-        let $var = $table.get(key)
-            .map(|x| unwrap_opt!((x.$func()) with $logger, format: MOC("moc '{0}'s '{key}' attribute must be of the correct type", $entry)))
-            .unwrap_or(&default);
+        let $var = match $table.get(key) {
+            Some(x) => unwrap_opt!((x.$func()) else Validation("moc '{0}'s '{key}' attribute must be of the correct type", $entry)),
+            None => &default,
+        };
     };
 }
 
@@ -32,33 +33,34 @@ pub struct MOC {
     pub notes: Option<Box<[String]>>,
     pub tags: Option<Box<[String]>>,
     pub collections: Option<Box<[Collection]>>,
+    /// Private mocs are redacted or left out of public exports
+    pub private: Option<bool>,
 }
 
 impl MOC {
-    pub fn store_lazy(&self, mut logger: impl Logger) {
+    pub fn store_lazy(&self, mut logger: impl Logger) -> crate::Result<()> {
         log!((logger) MOC("Storing moc into archive..."));
         // Only store them if modified
-        if let Some(x) = &self.title { write_db_container!(MOC(self.container) title = new_string(x) with logger); }
-        if let Some(x) = &self.description { write_db_container!(MOC(self.container) description = new_string(x) with logger); }
+        if let Some(x) = &self.title { write_db_container!(MOC(self.container) title = new_string(x)); }
+        if let Some(x) = &self.description { write_db_container!(MOC(self.container) description = new_string(x)); }
+        if let Some(x) = self.private { write_db_container!(MOC(self.container) private = new_bool(x)); }
         
         // The bloody lists & arrays
         if let Some(x) = &self.notes {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [MOC, err => ("While writing notes to archive: {:?}", err)] retry self.container.new_container("notes")),
-                logger.hollow()
-            );
+                &self.container.new_container("notes").context("While writing notes to archive")?,
+            )?;
         }
 
         if let Some(x) = &self.tags {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [MOC, err => ("While writing tags to archive: {:?}", err)] retry self.container.new_container("tags")),
-                logger.hollow()
-            );
+                &self.container.new_container("tags").context("While writing tags to archive")?,
+            )?;
         }
+
+        Ok(())
     }
 
     pub fn load_lazy(uid: String, container: LazyContainer) -> Self {
@@ -70,6 +72,7 @@ impl MOC {
             notes: None,
             tags: None,
             collections: None,
+            private: None,
         }
     }
 
@@ -79,85 +82,88 @@ impl MOC {
         self.notes = None;
         self.tags = None;
         self.collections = None;
+        self.private = None;
     }
 
-    cache_field!(title(this, logger) -> String {
-        read_db_container!(title from MOCSection(this.container) as collect_string with logger)
+    try_cache_field!(title(this) -> String {
+        read_db_container!(title from MOC(this.container) as collect_string)
     });
 
-    cache_field!(description(this, logger) -> String {
-        read_db_container!(description from MOC(this.container) as collect_string with logger)
+    try_cache_field!(description(this) -> String {
+        read_db_container!(description from MOC(this.container) as collect_string)
     });
 
-    cache_field!(notes(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [MOC, err => ("While reading from moc's notes: {err:?}")] retry this.container.child_container("notes")),
-            logger
-        )
+    try_cache_field!(notes(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("notes").context("While reading from moc's notes")?,
+        )?
     });
 
-    cache_field!(tags(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [MOC, err => ("While reading from moc's tags: {err:?}")] retry this.container.child_container("tags")),
-            logger
-        )
+    try_cache_field!(tags(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("tags").context("While reading from moc's tags")?,
+        )?
     });
 
-    cache_field!(collections(this, logger) -> Box<[Collection]> {
-        let container = if_err!((logger) [MOC, err => ("While reading from moc's collections: {err:?}")] retry this.container.child_container("collections"));
-        let length = if_err!((logger) [MOC, err => ("While reading from moc's collections' length: {err:?}")] retry container.read_data("length"));
-        let length = if_err!((logger) [MOC, err => ("While reading from moc's collections' length: {err:?}")] {length.collect_u16()} crash {
-            log!((logger) MOC("{err:#?}") as Fatal);
-            logger.crash()
-        });
+    try_cache_field!(private(this) -> bool {
+        read_private(&this.container, "moc")?
+    });
+
+    try_cache_field!(collections(this) -> Box<[Collection]> {
+        let container = this.container.child_container("collections").context("While reading from moc's collections")?;
+        let length = container.read_data("length")
+            .and_then(|x| x.collect_u32())
+            .context("While reading from moc's collections' length")?;
         let mut colletions = Vec::with_capacity(length as usize);
 
         for i in 0..length {
             colletions.push(Collection::load_lazy(
-                if_err!((logger) [MOC, err => ("While reading moc collection {i}: {err:?}")] retry container.child_container(i.to_string()))
+                container.child_container(i.to_string()).with_context(|| format!("While reading moc collection {i}"))?
             ));
         }
 
         colletions.into_boxed_slice()
     });
 
-    pub fn new(table: Table, moc_path: &str, database: LazyContainer, mut logger: impl Logger) -> Self {
+    pub fn new(table: Table, moc_path: &str, database: LazyContainer, mut logger: impl Logger) -> crate::Result<Self> {
         log!((logger) MOC("Reading moc '{moc_path}'s raw unchecked data..."));
 
-        let moc_table = get!(moc at moc_path from table as as_table with logger);
-        let uid = get!(uid at moc_path from moc_table as as_str with logger).to_string();
+        let moc_table = get!(moc at moc_path from table as as_table);
+        let uid = get!(uid at moc_path from moc_table as as_str).to_string();
 
-        let title = get!(title at moc_path from moc_table as as_str with logger).to_string();
-        let description = get!(description at moc_path from moc_table as as_str with logger).to_string();
-        get!(raw_notes = notes at moc_path from moc_table as as_array with logger or Vec::<toml::Value>::with_capacity(0));
-        let raw_tags = get!(tags at moc_path from moc_table as as_array with logger);
-        let raw_collections = get!(collection at moc_path from table as as_array with logger);
+        let title = get!(title at moc_path from moc_table as as_str).to_string();
+        let description = get!(description at moc_path from moc_table as as_str).to_string();
+        get!(raw_notes = notes at moc_path from moc_table as as_array or Vec::<toml::Value>::with_capacity(0));
+        let raw_tags = get!(tags at moc_path from moc_table as as_array);
+        let private = match moc_table.get("private") {
+            Some(x) => unwrap_opt!((x.as_bool()) else Validation("moc '{moc_path}'s 'private' attribute must be a boolean")),
+            None => false,
+        };
+        let raw_collections = get!(collection at moc_path from table as as_array);
 
         // set the container
-        let container =
-            if_err!((logger) [MOC, err => ("While initialising moc: '{err:?}'")] retry database.new_container(&uid));
+        let container = database.new_container(&uid).context("While initialising moc")?;
 
         // parse simple arrays
         log!((logger) MOC("Parsing notes & tags"));
-        unpack_array!(notes from raw_notes with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: MOC("All notes in moc '{moc_path}' must be strings")).to_string()
+        unpack_array!(notes from raw_notes by x
+            => unwrap_opt!((x.as_str()) else Validation("All notes in moc '{moc_path}' must be strings")).to_string()
         );
 
-        unpack_array!(tags from raw_tags with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: MOC("All tags in moc '{moc_path}' must be strings")).to_string()
+        unpack_array!(tags from raw_tags by x
+            => unwrap_opt!((x.as_str()) else Validation("All tags in moc '{moc_path}' must be strings")).to_string()
         );
 
         // parse collections
         log!((logger) MOC("Parsing moc's collections..."));
-        let list = if_err!((logger) [MOC, err => ("While initialising collections: {err:?}")] retry container.new_container("collections"));
-        unpack_array!(collections from raw_collections with logger by (i, x) => {
-            let container = if_err!((logger) [MOC, err => ("While initialising collection {i}: {err:?}")] retry list.new_container(i.to_string()));
-            let table = unwrap_opt!((x.as_table()) with logger, format: MOC("MOC '{moc_path}', collection {i} must be a toml table"));
-            Collection::new(table, container, moc_path, i as u8, logger.hollow()) // Write into that container
+        let list = container.new_container("collections").context("While initialising collections")?;
+        unpack_array!(collections from raw_collections by (i, x) => {
+            let container = list.new_container(i.to_string()).with_context(|| format!("While initialising collection {i}"))?;
+            let table = unwrap_opt!((x.as_table()) else Validation("MOC '{moc_path}', collection {i} must be a toml table"));
+            Collection::new(table, container, moc_path, i, logger.hollow())? // Write into that container
         });
-        if_err!((logger) [MOC, err => ("While writing collection list length: {err:?}")] retry write_container!((list) length = new_u16(raw_collections.len() as u16)));
+        let length = list::stored_length(raw_collections.len())?;
+        write_container!((list) length = new_u32(length)).context("While writing collection list length")?;
 
         log!((logger) Entry("Storing moc's parsed and checked data into archive..."));
 
@@ -169,39 +175,41 @@ impl MOC {
             notes: Some(notes.into_boxed_slice()),
             tags: Some(tags.into_boxed_slice()),
             collections: Some(collections.into_boxed_slice()),
+            private: Some(private),
         };
-        this.store_lazy(logger.hollow());
+        this.store_lazy(logger.hollow())?;
         log!((logger) MOC("Successfully written moc into archive"));
         log!((logger) MOC(""));
         this.clear_cache();
-        this
+        Ok(this)
     }
 
-    pub fn pull(&mut self, logger: impl Logger) -> Table {
+    pub fn pull(&mut self) -> crate::Result<Table> {
         let mut map = Table::new();
         let mut moc = Table::new();
 
         // Insert uid, title, description, notes, tags, and date
         moc.insert("uid".into(), self.uid.clone().into());
-        moc.insert("title".into(), self.title(logger.hollow()).clone().into());
-        moc.insert("description".into(), self.description(logger.hollow()).clone().into());
-        moc.insert("notes".into(), self.notes(logger.hollow()).to_vec().into());
-        moc.insert("tags".into(), self.tags(logger.hollow()).to_vec().into());
+        moc.insert("title".into(), self.title()?.clone().into());
+        moc.insert("description".into(), self.description()?.clone().into());
+        moc.insert("notes".into(), self.notes()?.to_vec().into());
+        moc.insert("tags".into(), self.tags()?.to_vec().into());
+        if *self.private()? { moc.insert("private".into(), true.into()); }
         map.insert("moc".into(), moc.into());
         map.insert("is-moc".into(), true.into());
 
         self.clear_cache();
 
-        map.insert("collection".into(), self.collections(logger.hollow())
+        map.insert("collection".into(), self.collections()?
             .iter_mut()
-            .map(|x| x.pull(logger.hollow()))
-            .collect::<Vec<Table>>()
+            .map(|x| x.pull())
+            .collect::<crate::Result<Vec<Table>>>()?
             .into()
         );
 
         self.clear_cache();
 
-        map
+        Ok(map)
     }
 }
 
@@ -210,9 +218,38 @@ impl Searchable for MOC {
         self.uid.clone()
     }
 
-    fn contains_tag(&mut self, tag: &String, logger: impl Logger) -> bool {
-        let result = self.tags(logger).contains(tag);
+    fn contains_tag(&mut self, tag: &String) -> crate::Result<bool> {
+        let result = self.tags()?.contains(tag);
+        self.tags = None;
+        Ok(result)
+    }
+
+    fn get_tags(&mut self) -> crate::Result<Box<[String]>> {
+        let tags = self.tags()?.clone();
         self.tags = None;
-        result
+        Ok(tags)
     }
-}
\ No newline at end of file
+
+    fn is_private(&mut self) -> crate::Result<bool> {
+        let private = *self.private()?;
+        self.private = None;
+        Ok(private)
+    }
+
+    fn get_text(&mut self) -> crate::Result<Vec<(Field, String)>> {
+        let mut text = vec![
+            (Field::Title, self.title()?.clone()),
+            (Field::Description, self.description()?.clone()),
+        ];
+        text.extend(self.notes()?.iter().enumerate().map(|(i, x)| (Field::Note(i), x.clone())));
+
+        for (i, collection) in self.collections()?.iter_mut().enumerate() {
+            text.push((Field::CollectionTitle(i), collection.title()?.clone()));
+            text.extend(collection.notes()?.iter().enumerate().map(|(j, x)| (Field::CollectionNote(i, j), x.clone())));
+            collection.clear_cache();
+        }
+
+        self.clear_cache();
+        Ok(text)
+    }
+}
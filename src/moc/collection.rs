@@ -1,23 +1,25 @@
 use soulog::*;
 use lazy_db::*;
 use crate::entry::*;
+use crate::{crypt, Context, Error};
 use toml::{Table, Value};
 
 // Some ease of life macros
 macro_rules! get {
-    ($key:ident at ($moc:ident, $idx:ident) from $table:ident as $func:ident with $logger:ident) => {{
+    ($key:ident at ($moc:ident, $idx:ident) from $table:ident as $func:ident) => {{
         let key = stringify!($key);
-        let obj = unwrap_opt!(($table.get(key)) with $logger, format: Collection("moc '{0}', collection {1} must have '{key}' attribute", $moc, $idx));
+        let obj = unwrap_opt!(($table.get(key)) else Validation("moc '{0}', collection {1} must have '{key}' attribute", $moc, $idx));
 
-        unwrap_opt!((obj.$func()) with $logger, format: Collection("moc '{0}', collection {1}'s '{key}' attribute must be of the correct type", $moc, $idx))
+        unwrap_opt!((obj.$func()) else Validation("moc '{0}', collection {1}'s '{key}' attribute must be of the correct type", $moc, $idx))
     }};
 
-    ($var:ident = $key:ident at ($moc:ident, $idx:ident) from $table:ident as $func:ident with $logger:ident or $default:expr) => {
+    ($var:ident = $key:ident at ($moc:ident, $idx:ident) from $table:ident as $func:ident or $default:expr) => {
         let key = stringify!($key);
         let default = $default;
-        let $var = $table.get(key)
-            .map(|x| unwrap_opt!((x.$func()) with $logger, format: Collection("moc '{0}'s '{key}' attribute must be of the correct type", $moc)))
-            .unwrap_or(&default);
+        let $var = match $table.get(key) {
+            Some(x) => unwrap_opt!((x.$func()) else Validation("moc '{0}'s '{key}' attribute must be of the correct type", $moc)),
+            None => &default,
+        };
     };
 }
 
@@ -26,25 +28,51 @@ pub struct Collection {
     pub title: Option<String>,
     pub notes: Option<Box<[String]>>,
     pub include: Option<Box<[String]>>,
+    /// The query (if any) that included items must also match
+    pub query: Option<Option<String>>,
+    /// The named sibling diary (if any) the included items come from
+    pub diary: Option<Option<String>>,
 }
 
 impl Collection {
-    pub fn new(table: &Table, container: LazyContainer, moc: &str, idx: u8, mut logger: impl Logger) -> Self {
+    pub fn new(table: &Table, container: LazyContainer, moc: &str, idx: usize, mut logger: impl Logger) -> crate::Result<Self> {
         log!((logger) Collection("Parsing moc '{moc}'s collection {idx}..."));
 
         // Get the basic needed data
         log!((logger) Collection("Reading collection's data..."));
-        let title = get!(title at (moc, idx) from table as as_str with logger).to_string();
-        get!(raw_notes = notes at (moc, idx) from table as as_array with logger or Vec::<toml::Value>::with_capacity(0));
-        let raw_include = get!(include at (moc, idx) from table as as_array with logger);
+        let title = get!(title at (moc, idx) from table as as_str).to_string();
+        get!(raw_notes = notes at (moc, idx) from table as as_array or Vec::<toml::Value>::with_capacity(0));
+        get!(raw_include = include at (moc, idx) from table as as_array or Vec::<toml::Value>::with_capacity(0));
+        let query = match table.get("query") {
+            Some(x) => Some(unwrap_opt!((x.as_str()) else Validation("moc '{moc}', collection {idx}'s 'query' attribute must be of the correct type")).to_string()),
+            None => None,
+        };
+        let diary = match table.get("diary") {
+            Some(x) => Some(unwrap_opt!((x.as_str()) else Validation("moc '{moc}', collection {idx}'s 'diary' attribute must be of the correct type")).to_string()),
+            None => None,
+        };
+
+        if raw_include.is_empty() && query.is_none() {
+            return Err(Error::Validation(format!("moc '{moc}', collection {idx} must have an 'include' or 'query' attribute")));
+        }
+
+        // Check the query
+        if let Some(query) = &query {
+            crate::query::parse(query)?;
+        }
+
+        // Check the diary name (whether it exists is only checked on export, as it may be created later)
+        if let Some(diary) = &diary {
+            crate::diaries::validate_name(diary)?;
+        }
 
         // Parse arrays
-        unpack_array!(notes from raw_notes with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: Collection("All notes in moc '{moc}', collection '{idx}' must be strings")).to_string()
+        unpack_array!(notes from raw_notes by x
+            => unwrap_opt!((x.as_str()) else Validation("All notes in moc '{moc}', collection '{idx}' must be strings")).to_string()
         );
 
-        unpack_array!(include from raw_include with logger by x
-            => unwrap_opt!((x.as_str()) with logger, format: Collection("All included groups in moc '{moc}', collection '{idx}' must be strings")).to_string()
+        unpack_array!(include from raw_include by x
+            => unwrap_opt!((x.as_str()) else Validation("All included groups in moc '{moc}', collection '{idx}' must be strings")).to_string()
         );
 
         log!((logger) Collection("Writing moc '{moc}'s collection {idx} into archive..."));
@@ -53,47 +81,65 @@ impl Collection {
             title: Some(title),
             notes: Some(notes.into_boxed_slice()),
             include: Some(include.into_boxed_slice()),
+            query: Some(query),
+            diary: Some(diary),
         };
 
-        this.store_lazy(logger.hollow());
+        this.store_lazy()?;
         this.clear_cache();
         log!((logger) Collection("Successfully parsed and written moc's collection {idx} into archive"));
         log!((logger) Collection("")); // spacer
-        this
+        Ok(this)
     }
 
-    pub fn pull(&mut self, logger: impl Logger) -> Table {
+    pub fn pull(&mut self) -> crate::Result<Table> {
         let mut map = Table::new();
 
         // Insert title and notes
-        map.insert("title".into(), Value::String(self.title(logger.hollow()).clone()));
-        map.insert("notes".into(), self.notes(logger.hollow()).to_vec().into());
-        map.insert("include".into(), self.include(logger.hollow()).to_vec().into());
+        map.insert("title".into(), Value::String(self.title()?.clone()));
+        map.insert("notes".into(), self.notes()?.to_vec().into());
+        map.insert("include".into(), self.include()?.to_vec().into());
+        if let Some(query) = self.query()? {
+            map.insert("query".into(), query.clone().into());
+        }
+        if let Some(diary) = self.diary()? {
+            map.insert("diary".into(), diary.clone().into());
+        }
 
         self.clear_cache();
 
-        map
+        Ok(map)
     }
 
-    pub fn store_lazy(&self, mut logger: impl Logger) {
+    pub fn store_lazy(&self) -> crate::Result<()> {
         // Only store them if they are accessed (maybe modified)
-        if let Some(x) = &self.title { write_db_container!(Collection(self.container) title = new_string(x) with logger); }
+        if let Some(x) = &self.title { write_db_container!(Collection(self.container) title = new_string(x)); }
         if let Some(x) = &self.notes {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [Collection, err => ("While writing collection's notes to archive: {:?}", err)] retry self.container.new_container("notes")),
-                logger.hollow()
-            );
+                &self.container.new_container("notes").context("While writing collection's notes to archive")?,
+            )?;
         }
         if let Some(x) = &self.include {
-            list::write(
+            list::write_strings(
                 x.as_ref(),
-                |file, data| LazyData::new_string(file, data),
-                &if_err!((logger) [Collection, err => ("While writing collection's included groups to archive: {:?}", err)] retry self.container.new_container("include")),
-                logger
-            );
+                &self.container.new_container("include").context("While writing collection's included groups to archive")?,
+            )?;
         }
+        if let Some(x) = &self.query {
+            match x {
+                Some(x) => { write_db_container!(Collection(self.container) query = new_string(x)); },
+                None => { let _ = self.container.remove("query"); },
+            }
+        }
+        if let Some(x) = &self.diary {
+            match x {
+                Some(x) => { write_db_container!(Collection(self.container) diary = new_string(x)); },
+                None => { let _ = self.container.remove("diary"); },
+            }
+        }
+
+        Ok(())
     }
 
     pub fn load_lazy(container: LazyContainer) -> Self {
@@ -102,6 +148,8 @@ impl Collection {
             title: None,
             notes: None,
             include: None,
+            query: None,
+            diary: None,
         }
     }
 
@@ -109,31 +157,48 @@ impl Collection {
         self.title = None;
         self.notes = None;
         self.include = None;
+        self.query = None;
+        self.diary = None;
     }
 
-    pub fn fill_cache(&mut self, logger: impl Logger) {
-        self.title(logger.hollow());
-        self.include(logger.hollow());
-        self.notes(logger.hollow());
+    pub fn fill_cache(&mut self) -> crate::Result<()> {
+        self.title()?;
+        self.include()?;
+        self.notes()?;
+        self.query()?;
+        self.diary()?;
+        Ok(())
     }
 
-    cache_field!(notes(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [Collection, err => ("While reading from collection's notes: {err:?}")] retry this.container.child_container("notes")),
-            logger
-        )
+    try_cache_field!(notes(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("notes").context("While reading from collection's notes")?,
+        )?
+    });
+
+    try_cache_field!(title(this) -> String {
+        read_db_container!(title from Collection(this.container) as collect_string)
     });
 
-    cache_field!(title(this, logger) -> String {
-        read_db_container!(title from Collection(this.container) as collect_string with logger)
+    try_cache_field!(include(this) -> Box<[String]> {
+        list::read_strings(
+            &this.container.child_container("include").context("While reading from collection's included groups")?,
+        )?
     });
 
-    cache_field!(include(this, logger) -> Box<[String]> {
-        list::read(
-            |data| data.collect_string(),
-            &if_err!((logger) [Collection, err => ("While reading from collection's included groups: {err:?}")] retry this.container.child_container("include")),
-            logger
-        )
+    try_cache_field!(query(this) -> Option<String> {
+        match this.container.read_data("query") {
+            Ok(x) => Some(crypt::collect_string(x).context("While reading collection's query")?),
+            Err(LDBError::FileNotFound(_)) => None,
+            Err(err) => return Err(Error::Storage { context: "While reading collection's query".into(), source: err }),
+        }
     });
-}
\ No newline at end of file
+
+    try_cache_field!(diary(this) -> Option<String> {
+        match this.container.read_data("diary") {
+            Ok(x) => Some(crypt::collect_string(x).context("While reading collection's diary")?),
+            Err(LDBError::FileNotFound(_)) => None,
+            Err(err) => return Err(Error::Storage { context: "While reading collection's diary".into(), source: err }),
+        }
+    });
+}
@@ -1,45 +1,44 @@
 use soulog::*;
 use std::{path::PathBuf, fs};
-use crate::{archive::Archive, unwrap_opt};
+use crate::{archive::Archive, Context, Error, Home, Result};
 
-pub fn pull(path: PathBuf, file_name: String, is_moc: bool, uid: String, one_file: bool, mut logger: impl Logger) {
-    let archive = Archive::load(logger.hollow());
+pub fn pull(path: PathBuf, file_name: String, is_moc: bool, uid: String, one_file: bool, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
 
-    if_err!((logger) {fs::create_dir_all(&path)} else(err) {
+    if let Err(err) = fs::create_dir_all(&path) {
         log!((logger.vital) Pull("While initialising path '{}': {err:?}; ignoring error...", path.to_string_lossy()) as Inconvenience) 
-    });
+    }
     
     if is_moc {
         log!((logger) Pull("Pulling moc with uid '{uid}' from archive..."));
-        pull_moc(archive, path, file_name, uid, logger.hollow());
+        pull_moc(archive, path, file_name, uid)?;
     } else {
         log!((logger) Pull("Pulling entry with uid '{uid}' from archive..."));
-        pull_entry(archive, path, file_name, uid, one_file, logger.hollow());
+        pull_entry(archive, path, file_name, uid, one_file)?;
     }
 
     log!((logger.vital) Pull("Successfully pulled config file from archive") as Log);
+    Ok(())
 }
 
-fn pull_entry(archive: Archive, path: PathBuf, file_name: String, uid: String, one_file: bool, mut logger: impl Logger) {
-    let error_msg = format!("Entry of uid '{uid}' not found in archive");
-    let mut entry = unwrap_opt!((archive.get_entry(uid, logger.hollow())) with logger, format: Pull("{error_msg}"));
-    std::mem::drop(error_msg);
+fn pull_entry(archive: Archive, path: PathBuf, file_name: String, uid: String, one_file: bool) -> Result<()> {
+    let mut entry = archive.get_entry(uid)?;
 
-    let map = entry.pull(&path, one_file, logger.hollow());
-    let contents = if_err!((logger) [Pull, err => ("While encoding entry toml: {err:?}")] retry toml::to_string_pretty(&map));
+    let map = entry.pull(&path, one_file)?;
+    let contents = toml::to_string_pretty(&map)
+        .map_err(|err| Error::Validation(format!("While encoding entry toml: {err}")))?;
     
     let path = path.join(file_name);
-    if_err!((logger) [Pull, err => ("While writing toml to path '{}'", path.to_string_lossy())] retry std::fs::write(&path, &contents));
+    fs::write(&path, &contents).with_context(|| format!("While writing toml to path '{}'", path.to_string_lossy()))
 }
 
-fn pull_moc(archive: Archive, path: PathBuf, file_name: String, uid: String, mut logger: impl Logger) {
-    let error_msg = format!("moc of uid '{uid}' not found in archive");
-    let mut moc = unwrap_opt!((archive.get_moc(uid, logger.hollow())) with logger, format: Pull("{error_msg}"));
-    std::mem::drop(error_msg);
+fn pull_moc(archive: Archive, path: PathBuf, file_name: String, uid: String) -> Result<()> {
+    let mut moc = archive.get_moc(uid)?;
 
-    let map = moc.pull(logger.hollow());
-    let contents = if_err!((logger) [Pull, err => ("While encoding moc toml: {err:?}")] retry toml::to_string_pretty(&map));
+    let map = moc.pull()?;
+    let contents = toml::to_string_pretty(&map)
+        .map_err(|err| Error::Validation(format!("While encoding moc toml: {err}")))?;
     
     let path = path.join(file_name);
-    if_err!((logger) [Pull, err => ("While writing toml to path '{}'", path.to_string_lossy())] retry std::fs::write(&path, &contents));
-}
\ No newline at end of file
+    fs::write(&path, &contents).with_context(|| format!("While writing toml to path '{}'", path.to_string_lossy()))
+}
@@ -0,0 +1,432 @@
+//! A small boolean query language for filtering entries and mocs
+//!
+//! ```text
+//! (travel or work) and not draft and date>=2023-01-01 and title~"trip"
+//! ```
+//! - A bare word (or quoted string) matches items with that tag
+//! - `and`, `or` and `not` combine queries, and parenthesis group them
+//! - `date` can be compared with `=`, `!=`, `<`, `<=`, `>` and `>=` against a `yyyy-mm-dd` date
+//! - `tag`, `uid`, `title`, `description`, `note`, `content` and `text` can be compared with `=`, `!=` and `~` (contains, case-insensitive)
+
+use std::fmt;
+use soulog::*;
+use chrono::NaiveDate;
+use crate::{search::Searchable, find::Field, entry::DATE_FORMAT};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The column (starting from 1) the error occurred at
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        Self { column, message: message.into() }
+    }
+
+    /// Returns the query with everything from the column of the error highlighted
+    pub fn highlight(&self, query: &str) -> String {
+        let split = query.char_indices()
+            .nth(self.column.saturating_sub(1))
+            .map(|(i, _)| i)
+            .unwrap_or(query.len());
+        let (before, after) = query.split_at(split);
+        colour_format![none(before), red(if after.is_empty() { "_" } else { after })]
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Op::*;
+        write!(f, "{}", match self {
+            Eq => "=",
+            Ne => "!=",
+            Lt => "<",
+            Le => "<=",
+            Gt => ">",
+            Ge => ">=",
+            Contains => "~",
+        })
+    }
+}
+
+/// The text of an item that can be compared against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Tag,
+    Uid,
+    Title,
+    Description,
+    Note,
+    Content,
+    /// All of the item's text
+    Text,
+}
+
+impl TextField {
+    fn parse(name: &str) -> Option<Self> {
+        use TextField::*;
+        Some(match name {
+            "tag" => Tag,
+            "uid" => Uid,
+            "title" => Title,
+            "description" => Description,
+            "note" => Note,
+            "content" => Content,
+            "text" => Text,
+            _ => return None,
+        })
+    }
+
+    fn includes(&self, field: &Field) -> bool {
+        use TextField::*;
+        match self {
+            Title => *field == Field::Title,
+            Description => *field == Field::Description,
+            Note => matches!(field, Field::Note(_) | Field::SectionNote(..) | Field::CollectionNote(..)),
+            Content => matches!(field, Field::SectionContent(_)),
+            Text => true,
+            Tag | Uid => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Date(Op, NaiveDate),
+    Text(TextField, Op, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+    Str(String),
+}
+
+fn lex(query: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        let (token, length) = match c {
+            _ if c.is_whitespace() => { i += 1; continue },
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '=' => (Token::Op(Op::Eq), 1),
+            '~' => (Token::Op(Op::Contains), 1),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' => (Token::Op(Op::Gt), 1),
+            '!' => return Err(ParseError::new(column, "expected '=' after '!'")),
+            '"' => {
+                let mut string = String::new();
+                let mut end = None;
+                let mut j = i + 1;
+                while j < chars.len() {
+                    match chars[j] {
+                        '"' => { end = Some(j); break },
+                        '\\' if j + 1 < chars.len() => { string.push(chars[j + 1]); j += 2 },
+                        x => { string.push(x); j += 1 },
+                    }
+                }
+                let end = end.ok_or_else(|| ParseError::new(column, "unterminated string"))?;
+                (Token::Str(string), end + 1 - i)
+            },
+            _ => {
+                let word: String = chars[i..].iter()
+                    .take_while(|x| !x.is_whitespace() && !"()=!<>~\"".contains(**x))
+                    .collect();
+                let length = word.chars().count();
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                (token, length)
+            },
+        };
+
+        tokens.push((column, token));
+        i += length;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    idx: usize,
+    /// Column just past the end of the query
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx).map(|(_, x)| x)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.idx).map(|(x, _)| *x).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.idx += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.idx += 1;
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<Query, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.idx += 1;
+            return Ok(Query::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Query, ParseError> {
+        let column = self.column();
+        match self.next() {
+            Some((_, Token::LParen)) => {
+                let query = self.or()?;
+                let column = self.column();
+                match self.next() {
+                    Some((_, Token::RParen)) => Ok(query),
+                    _ => Err(ParseError::new(column, "expected ')'")),
+                }
+            },
+            Some((_, Token::Str(tag))) => Ok(Query::Text(TextField::Tag, Op::Eq, tag)),
+            Some((_, Token::Word(word))) => match self.peek() {
+                Some(Token::Op(_)) => self.comparison(column, word),
+                _ => Ok(Query::Text(TextField::Tag, Op::Eq, word)),
+            },
+            Some((_, token)) => Err(ParseError::new(column, format!("unexpected {}", describe(&token)))),
+            None => Err(ParseError::new(column, "unexpected end of query")),
+        }
+    }
+
+    fn comparison(&mut self, field_column: usize, field: String) -> Result<Query, ParseError> {
+        let op_column = self.column();
+        let op = match self.next() {
+            Some((_, Token::Op(op))) => op,
+            _ => unreachable!(),
+        };
+
+        let value_column = self.column();
+        let value = match self.next() {
+            Some((_, Token::Word(x))) | Some((_, Token::Str(x))) => x,
+            Some((_, token)) => return Err(ParseError::new(value_column, format!("expected a value but found {}", describe(&token)))),
+            None => return Err(ParseError::new(value_column, "expected a value but found the end of the query")),
+        };
+
+        if field.to_lowercase() == "date" {
+            if op == Op::Contains {
+                return Err(ParseError::new(op_column, "dates can't be compared with '~'"));
+            }
+            return match parse_date(&value) {
+                Some(date) => Ok(Query::Date(op, date)),
+                None => Err(ParseError::new(value_column, format!("invalid date '{value}'; dates must be formatted as yyyy-mm-dd"))),
+            };
+        }
+
+        let text_field = TextField::parse(&field.to_lowercase())
+            .ok_or_else(|| ParseError::new(field_column, format!("unknown field '{field}'")))?;
+        if !matches!(op, Op::Eq | Op::Ne | Op::Contains) {
+            return Err(ParseError::new(op_column, format!("'{field}' can only be compared with '=', '!=' or '~'")));
+        }
+
+        Ok(Query::Text(text_field, op, value))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::LParen => "'('".into(),
+        Token::RParen => "')'".into(),
+        Token::And => "'and'".into(),
+        Token::Or => "'or'".into(),
+        Token::Not => "'not'".into(),
+        Token::Op(op) => format!("'{op}'"),
+        Token::Word(x) => format!("'{x}'"),
+        Token::Str(x) => format!("\"{x}\""),
+    }
+}
+
+/// Parses a `yyyy-mm-dd` date
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT).ok()
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, ParseError> {
+        let tokens = lex(query)?;
+        let mut parser = Parser {
+            tokens,
+            idx: 0,
+            end: query.chars().count() + 1,
+        };
+
+        let result = parser.or()?;
+        if let Some((column, token)) = parser.tokens.get(parser.idx) {
+            return Err(ParseError::new(*column, format!("unexpected {}", describe(token))));
+        }
+        Ok(result)
+    }
+
+    /// Checks if an entry or moc matches the query
+    pub fn matches(&self, item: &mut impl Searchable) -> crate::Result<bool> {
+        let mut subject = Subject { item, tags: None, text: None, date: None };
+        self.eval(&mut subject)
+    }
+
+    fn eval<T: Searchable>(&self, subject: &mut Subject<T>) -> crate::Result<bool> {
+        Ok(match self {
+            Query::And(a, b) => a.eval(subject)? && b.eval(subject)?,
+            Query::Or(a, b) => a.eval(subject)? || b.eval(subject)?,
+            Query::Not(a) => !a.eval(subject)?,
+            Query::Date(op, date) => match subject.date()? {
+                Some(x) => compare(&x, op, date),
+                None => false, // mocs don't have dates
+            },
+            Query::Text(TextField::Tag, op, value) => {
+                let tags = subject.tags()?;
+                match op {
+                    Op::Ne => !tags.iter().any(|x| x == value),
+                    _ => tags.iter().any(|x| text_matches(x, op, value)),
+                }
+            },
+            Query::Text(TextField::Uid, op, value) => text_matches(&subject.item.get_uid(), op, value),
+            Query::Text(field, op, value) => {
+                let mut texts = subject.text()?
+                    .iter()
+                    .filter(|(x, _)| field.includes(x))
+                    .map(|(_, x)| x);
+                match op {
+                    Op::Ne => !texts.any(|x| x == value),
+                    _ => texts.any(|x| text_matches(x, op, value)),
+                }
+            },
+        })
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = ParseError;
+
+    #[inline]
+    fn from_str(query: &str) -> Result<Self, ParseError> {
+        Self::parse(query)
+    }
+}
+
+/// Lazily loads the parts of an item that a query needs
+struct Subject<'a, T: Searchable> {
+    item: &'a mut T,
+    tags: Option<Box<[String]>>,
+    text: Option<Vec<(Field, String)>>,
+    date: Option<Option<NaiveDate>>,
+}
+
+impl<T: Searchable> Subject<'_, T> {
+    fn tags(&mut self) -> crate::Result<&[String]> {
+        if self.tags.is_none() { self.tags = Some(self.item.get_tags()?) };
+        Ok(self.tags.as_ref().unwrap())
+    }
+
+    fn text(&mut self) -> crate::Result<&[(Field, String)]> {
+        if self.text.is_none() { self.text = Some(self.item.get_text()?) };
+        Ok(self.text.as_ref().unwrap())
+    }
+
+    fn date(&mut self) -> crate::Result<Option<NaiveDate>> {
+        if self.date.is_none() { self.date = Some(self.item.get_date()?) };
+        Ok(self.date.unwrap())
+    }
+}
+
+fn compare<T: Ord>(a: &T, op: &Op, b: &T) -> bool {
+    match op {
+        Op::Eq => a == b,
+        Op::Ne => a != b,
+        Op::Lt => a < b,
+        Op::Le => a <= b,
+        Op::Gt => a > b,
+        Op::Ge => a >= b,
+        Op::Contains => false,
+    }
+}
+
+fn text_matches(text: &str, op: &Op, value: &str) -> bool {
+    match op {
+        Op::Contains => text.to_lowercase().contains(&value.to_lowercase()),
+        op => compare(&text, op, &value),
+    }
+}
+
+/// Parses a query, keeping the query itself alongside the error so it can be highlighted
+pub fn parse(query: &str) -> crate::Result<Query> {
+    Query::parse(query).map_err(|error| crate::Error::Query { query: query.to_string(), error })
+}
+
+/// Filters items by a query, returning the uids of the ones that match
+pub fn filter(query: &Query, items: Vec<impl Searchable>) -> crate::Result<Vec<String>> {
+    let mut result = Vec::new();
+    for mut item in items {
+        if query.matches(&mut item)? { result.push(item.get_uid()) }
+    }
+    Ok(result)
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use chrono::{Datelike, Local, NaiveDate};
+use soulog::*;
+use toml::{Table, Value};
+use crate::{archive::Archive, Context, Error, Home, Result};
+
+/// The pattern new uids are made from if neither `--pattern` nor the template's `uid-pattern` is set
+pub const DEFAULT_PATTERN: &str = "{yyyy}-{mm}-{dd}-{slug}";
+
+/// Turns a title into a lowercase, dash separated uid component, eg `Trip to the Beach!` into `trip-to-the-beach`
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for x in title.chars().flat_map(|x| x.to_lowercase()) {
+        if x.is_alphanumeric() {
+            slug.push(x);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Replaces the date placeholders (`{yyyy}`, `{mm}` and `{dd}`) of a string
+pub fn expand_date(pattern: &str, date: NaiveDate) -> String {
+    pattern
+        .replace("{yyyy}", &format!("{:04}", date.year()))
+        .replace("{mm}", &format!("{:02}", date.month()))
+        .replace("{dd}", &format!("{:02}", date.day()))
+}
+
+/// Replaces all the placeholders (`{yyyy}`, `{mm}`, `{dd}`, `{title}` and `{slug}`) of a string
+pub fn expand(pattern: &str, title: &str, date: NaiveDate) -> String {
+    expand_date(pattern, date)
+        .replace("{title}", title)
+        .replace("{slug}", &slugify(title))
+}
+
+fn expand_value(value: &mut Value, title: &str, date: NaiveDate) {
+    match value {
+        Value::String(x) => *x = expand(x, title, date),
+        Value::Array(x) => x.iter_mut().for_each(|x| expand_value(x, title, date)),
+        Value::Table(x) => x.iter_mut().for_each(|(_, x)| expand_value(x, title, date)),
+        _ => (),
+    }
+}
+
+/// Loads a template from the home's templates (the `default` template, or a built-in one, if no name is given)
+pub fn load_template(home: &Home, name: Option<&str>) -> Result<Table> {
+    let path = home.templates().join(format!("{}.toml", name.unwrap_or("default")));
+    let path_string = path.to_string_lossy();
+
+    if !path.is_file() {
+        return match name {
+            Some(name) => Err(Error::NotFound(format!("Template '{name}' doesn't exist (templates are kept at '{}')", home.templates().to_string_lossy()))),
+            None => Ok(Table::new()),
+        };
+    }
+
+    fs::read_to_string(&path).with_context(|| format!("While reading template '{path_string}'"))?
+        .parse::<Table>()
+        .map_err(|err| Error::Parse { path: path_string.to_string(), message: err.to_string() })
+}
+
+/// Fills in a template with a uid, date and any missing attributes, returning the new item's uid and config
+pub fn scaffold(mut template: Table, title: Option<&str>, pattern: Option<&str>, date: NaiveDate) -> Result<(String, Table)> {
+    let is_moc = match template.get("is-moc") {
+        Some(x) => x.as_bool().ok_or_else(|| Error::Validation("Template's `is-moc` attribute must be boolean".into()))?,
+        None => false,
+    };
+    let key = if is_moc { "moc" } else { "entry" };
+
+    let template_pattern = match template.remove("uid-pattern") {
+        Some(Value::String(x)) => Some(x),
+        Some(_) => return Err(Error::Validation("Template's `uid-pattern` attribute must be a string".into())),
+        None => None,
+    };
+    let pattern = pattern.map(|x| x.to_string()).or(template_pattern).unwrap_or(DEFAULT_PATTERN.to_string());
+
+    let mut item = match template.remove(key) {
+        Some(Value::Table(x)) => x,
+        Some(_) => return Err(Error::Validation(format!("Template's `{key}` attribute must be a table"))),
+        None => Table::new(),
+    };
+
+    // The title may use the date but not itself
+    let title = match (title, item.get("title").and_then(|x| x.as_str())) {
+        (Some(x), _) | (None, Some(x)) => expand_date(x, date),
+        (None, None) => "Untitled".to_string(),
+    };
+    let uid = expand(&pattern, &title, date);
+    if uid.is_empty() {
+        return Err(Error::Validation(format!("uid pattern '{pattern}' made an empty uid")));
+    }
+
+    template.iter_mut().for_each(|(_, x)| expand_value(x, &title, date));
+    item.iter_mut().for_each(|(_, x)| expand_value(x, &title, date));
+
+    // Fill in the attributes
+    item.insert("uid".into(), uid.clone().into());
+    item.insert("title".into(), title.into());
+    item.entry("description").or_insert_with(|| "".into());
+    item.entry("notes").or_insert_with(|| Value::Array(Vec::new()));
+    item.entry("tags").or_insert_with(|| Value::Array(Vec::new()));
+    if !is_moc {
+        let date = format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day())
+            .parse::<toml::value::Datetime>()
+            .map_err(|err| Error::Validation(format!("Invalid date: {err}")))?;
+        item.insert("date".into(), Value::Datetime(date));
+
+        template.entry("section").or_insert_with(|| {
+            let mut section = Table::new();
+            section.insert("title".into(), "".into());
+            section.insert("contents".into(), "".into());
+            Value::Array(vec![Value::Table(section)])
+        });
+    }
+    template.insert(key.into(), item.into());
+
+    Ok((uid, template))
+}
+
+/// Opens a file with `$VISUAL` or `$EDITOR` (falling back to `vi`) and waits for it to close
+pub fn open_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL").ok().filter(|x| !x.is_empty())
+        .or_else(|| std::env::var("EDITOR").ok().filter(|x| !x.is_empty()))
+        .unwrap_or("vi".into());
+
+    // Editors are often given arguments, eg `code --wait`
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(args)
+        .arg(path)
+        .status()
+        .with_context(|| format!("While opening editor '{editor}'"))?;
+
+    if !status.success() {
+        return Err(Error::Aborted(format!("Editor '{editor}' exited unsuccessfully ({status})")));
+    }
+    Ok(())
+}
+
+pub fn new_command(template: Option<String>, title: Option<String>, pattern: Option<String>, path: String, edit: bool, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let table = load_template(home, template.as_deref())?;
+    let (uid, table) = scaffold(table, title.as_deref(), pattern.as_deref(), Local::now().date_naive())?;
+
+    let path = Path::new(&path);
+    fs::create_dir_all(path).with_context(|| format!("While creating directory '{}'", path.to_string_lossy()))?;
+    let path = path.join(format!("{uid}.toml"));
+    let path_string = path.to_string_lossy();
+    if path.exists() {
+        return Err(Error::AlreadyExists(format!("Config file '{path_string}' already exists")));
+    }
+
+    let contents = toml::to_string_pretty(&table)
+        .map_err(|err| Error::Validation(format!("While encoding new config: {err}")))?;
+    fs::write(&path, &contents).with_context(|| format!("While writing new config to '{path_string}'"))?;
+    log!((logger.vital) New("Created config '{path_string}' for '{uid}'") as Log);
+
+    if !edit {
+        log!((logger.vital) New("Edit it and then run `diary-cli commit {path_string}` to commit it") as Log);
+        return Ok(());
+    }
+
+    open_editor(&path)?;
+    let edited = fs::read_to_string(&path).with_context(|| format!("While reading config '{path_string}'"))?;
+    if edited == contents {
+        log!((logger.vital) New("Config '{path_string}' wasn't changed; not committing it") as Inconvenience);
+        return Ok(());
+    }
+
+    Archive::load(home, logger.hollow())?.commit(&path, logger.hollow())
+}
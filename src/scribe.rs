@@ -1,49 +1,46 @@
 use std::{io::{BufWriter, Write}, fs::File, path::Path};
-use soulog::*;
+use crate::{Context, Result};
 
-pub struct Scribe<T: Logger>(BufWriter<File>, T);
+pub struct Scribe(BufWriter<File>);
 
-impl<T: Logger> Scribe<T> {
-    pub fn new(path: impl AsRef<Path>, mut logger: T) -> Self {
-        let file = if_err!((logger) [Scribe, err => ("While creating text file: {err:?}")] retry File::create(&path));
+impl Scribe {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(&path).context("While creating text file")?;
         let buffer = BufWriter::new(file);
-        Self(buffer, logger)
+        Ok(Self(buffer))
     }
 
     #[inline]
-    pub fn write_line(&mut self, line: &str) {
-        self.write(line);
-        self.new_line();
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        self.write(line)?;
+        self.new_line()
     }
 
-    pub fn write(&mut self, text: &str) {
-        let mut logger = self.1.hollow();
-        if_err!((logger) [Scribe, err => ("While writing to text file: {err:?}")] retry self.0.write_all(text.as_bytes()));
+    pub fn write(&mut self, text: &str) -> Result<()> {
+        self.0.write_all(text.as_bytes()).context("While writing to text file")
     }
 
     #[inline]
-    pub fn new_line(&mut self) {
-        let mut logger = self.1.hollow();
-        if_err!((logger) [Scribe, err => ("While writing to text file: {err:?}")] retry self.0.write_all("\n".as_bytes()));
+    pub fn new_line(&mut self) -> Result<()> {
+        self.0.write_all("\n".as_bytes()).context("While writing to text file")
     }
 
-    pub fn flush(&mut self) {
-        let logger = &mut self.1;
-        if_err!((logger) [Sribe, err => ("While writing to text file: {err:?}")] retry self.0.flush());
+    pub fn flush(&mut self) -> Result<()> {
+        self.0.flush().context("While writing to text file")
     }
 
     #[inline]
-    pub fn finish(mut self) { self.flush() }
+    pub fn finish(mut self) -> Result<()> { self.flush() }
 }
 
-impl<T: Logger> Drop for Scribe<T> {
+impl Drop for Scribe {
     #[inline]
-    fn drop(&mut self) { self.flush() }
+    fn drop(&mut self) { let _ = self.flush(); }
 }
 
 #[macro_export]
 macro_rules! scribe_write {
     (($scribe:ident) $($text:expr),* $(,)?) => {{
-        $($scribe.write($text);)*
+        $($scribe.write($text)?;)*
     }}
-}
\ No newline at end of file
+}
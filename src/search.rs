@@ -1,18 +1,45 @@
 use soulog::*;
-use crate::{archive::Archive, entry::Entry, moc::MOC, sort};
+use regex::Regex;
+use chrono::NaiveDate;
+use crate::{archive::Archive, sort, query, index::{self, Group}, find::{self, Field, Match}, Home, Result};
 
 pub trait Searchable {
     fn get_uid(&self) -> String;
     #[allow(clippy::ptr_arg)]
-    fn contains_tag(&mut self, tag: &String, logger: impl Logger) -> bool;
+    fn contains_tag(&mut self, tag: &String) -> Result<bool>;
+    fn get_tags(&mut self) -> Result<Box<[String]>>;
+    /// Returns the date of the item (if it has one)
+    fn get_date(&mut self) -> Result<Option<NaiveDate>> { Ok(None) }
+    /// Returns if the item is private (kept out of public exports)
+    fn is_private(&mut self) -> Result<bool> { Ok(false) }
+    /// Returns every piece of searchable text within the item along with the field it's from
+    fn get_text(&mut self) -> Result<Vec<(Field, String)>>;
+
+    /// Finds all occurrences of a pattern within the item's text
+    fn find(&mut self, pattern: &Regex) -> Result<Vec<Match>> {
+        let uid = self.get_uid();
+        let mut matches = Vec::new();
+        for (field, text) in self.get_text()? {
+            find::find_in(&uid, field, &text, pattern, &mut matches);
+        }
+        Ok(matches)
+    }
+
+    /// Returns the unique words within the item's text (as indexed)
+    fn get_words(&mut self) -> Result<HashSet<String>> {
+        Ok(self.get_text()?
+            .iter()
+            .flat_map(|(_, text)| index::tokenize(text))
+            .collect())
+    }
 }
 
-pub fn search_strict(tags: &[String], items: Vec<impl Searchable>, logger: impl Logger) -> Vec<String> {
+pub fn search_strict(tags: &[String], items: Vec<impl Searchable>) -> Result<Vec<String>> {
     let mut result = Vec::new();
     for mut item in items.into_iter() {
         let mut all_tags_present = true;
         for tag in tags.iter() {
-            if !item.contains_tag(tag, logger.hollow()) {
+            if !item.contains_tag(tag)? {
                 all_tags_present = false;
                 break;
             }
@@ -21,80 +48,77 @@ pub fn search_strict(tags: &[String], items: Vec<impl Searchable>, logger: impl
         }
     }
 
-    result
+    Ok(result)
 }
 
-pub fn search<T: Searchable>(tags: &[String], items: Vec<T>, logger: impl Logger) -> Vec<String> {
+pub fn search<T: Searchable>(tags: &[String], items: Vec<T>) -> Result<Vec<String>> {
     let mut result = Vec::new();
     for mut item in items.into_iter() {
         for tag in tags {
-            if item.contains_tag(tag, logger.hollow()) {
+            if item.contains_tag(tag)? {
                 result.push(item.get_uid());
                 break;
             }
         }
     }
-    result
+    Ok(result)
 }
 
-pub fn list_command(strict: bool, show_mocs: bool, show_entries: bool, filter: Option<Vec<String>>, mut logger: impl Logger) {
-    let archive = Archive::load(logger.hollow());
+pub fn list_command(strict: bool, show_mocs: bool, show_entries: bool, filter: Option<Vec<String>>, query: Option<String>, home: &Home, mut logger: impl Logger) -> Result<()> {
+    let query = match query {
+        Some(x) => Some(query::parse(&x)?),
+        None => None,
+    };
+    let archive = Archive::load_shared(home, logger.hollow())?;
 
     // Get entries and mocs
-    sort::sort(logger.hollow());
-    let mut entries: Vec<_> = sort::read_sorted(&archive, logger.hollow())
-        .into_vec()
-        .into_iter()
-        .map(|x| archive.get_entry(x, logger.hollow()).unwrap())
-        .collect();
+    let mut entry_uids = sort::sorted(&archive)?.into_vec();
+    let mut moc_uids: Vec<String> = archive.list_mocs(logger.hollow())?.into_iter().map(|m| m.uid).collect();
 
-    let mut mocs = archive.list_mocs(logger.hollow());
+    if filter.is_none() && query.is_none() {
+        log!((logger) List("Listing selected items..."));
 
-    let filter = match filter {
-        Some(x) => x,
-        None => {
-            log!((logger) List("Listing selected items..."));
+        let tags = get_unique_tags(&archive, logger.hollow())?;
 
-            let tags = get_unique_tags(&mut entries, &mut mocs, logger.hollow());
+        log!((logger.vital) tags("{tags:#?}") as Result);
+        std::mem::drop(tags);
 
-            log!((logger.vital) tags("{tags:#?}") as Result);
-            std::mem::drop(tags);
-
-            let entry_uids: Vec<String> = entries.into_iter().map(|e| e.uid).collect();
-            let moc_uids: Vec<String> = mocs.into_iter().map(|m| m.uid).collect();
+        if show_entries { log!((logger.vital) entries("{entry_uids:#?}") as Result) }
+        if show_mocs { log!((logger.vital) mocs("{moc_uids:#?}") as Result) }
+        return Ok(());
+    }
 
-            if show_entries { log!((logger.vital) entries("{entry_uids:#?}") as Result) }
-            if show_mocs { log!((logger.vital) mocs("{moc_uids:#?}") as Result) }
-            return;
+    if let Some(filter) = &filter {
+        if strict {
+            log!((logger) List("Searching strictly with tags {filter:?} in mocs and entries..."));
+        } else {
+            log!((logger) List("Searching with tags {filter:?} in mocs and entries..."));
         }
-    };
+        let found = index::search(&archive, Group::Tags, filter, strict, false, logger.hollow())?;
+        entry_uids.retain(|x| found.contains(x)); // keep them sorted
+        moc_uids = index::search(&archive, Group::Tags, filter, strict, true, logger.hollow())?;
+    }
 
-    let entry_uids: Vec<String>;
-    let moc_uids: Vec<String>;
-
-    if strict {
-        log!((logger) List("Searching strictly with tags {filter:?} in mocs and entries..."));
-        entry_uids = search_strict(&filter, entries, logger.hollow());
-        moc_uids = search_strict(&filter, mocs, logger.hollow());
-    } else {
-        log!((logger) List("Searching with tags {filter:?} in mocs and entries..."));
-        entry_uids = search(&filter, entries, logger.hollow());
-        moc_uids = search(&filter, mocs, logger.hollow());
+    if let Some(query) = &query {
+        log!((logger) List("Filtering mocs and entries with query..."));
+        entry_uids = query::filter(query, entry_uids.into_iter().map(|x| archive.get_entry(x)).collect::<Result<_>>()?)?;
+        moc_uids = query::filter(query, moc_uids.into_iter().map(|x| archive.get_moc(x)).collect::<Result<_>>()?)?;
     }
 
     log!((logger) List("Listing found entries and mocs..."));
 
-    log!((logger.vital) tags("{filter:?}") as Result);
+    if let Some(filter) = &filter { log!((logger.vital) tags("{filter:?}") as Result) }
     if show_entries { log!((logger.vital) entries("{entry_uids:?}") as Result) }
     if show_mocs { log!((logger.vital) mocs("{moc_uids:?}") as Result) }
+    Ok(())
 }
 
-use std::collections::HashSet;
-fn get_unique_tags<'a>(entries: &'a mut [Entry], mocs: &'a mut [MOC], logger: impl Logger) -> HashSet<&'a String> {
-    let mut tags = HashSet::new();
+use std::collections::{BTreeSet, HashSet};
+fn get_unique_tags(archive: &Archive, logger: impl Logger) -> Result<BTreeSet<String>> {
+    let mut tags = BTreeSet::new();
 
-    tags.extend(entries.iter_mut().flat_map(|x| x.tags(logger.hollow()).iter()));
-    tags.extend(mocs.iter_mut().flat_map(|x| x.tags(logger.hollow()).iter()));
+    tags.extend(index::terms(archive, Group::Tags, false, logger.hollow())?);
+    tags.extend(index::terms(archive, Group::Tags, true, logger.hollow())?);
 
-    tags
-}
\ No newline at end of file
+    Ok(tags)
+}
@@ -0,0 +1,396 @@
+use std::{io::{self, IsTerminal, Write}, process::{Command, Stdio}};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use soulog::*;
+use crate::{archive::Archive, entry::{Entry, DATE_FORMAT}, export::{linked, Privacy}, moc::MOC, Context, Home, Result};
+
+/// How wide pages are when they aren't shown in a terminal (or its width can't be found)
+const PLAIN_WIDTH: usize = 80;
+
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Title,
+    Heading,
+    Bold,
+    Italic,
+    Strike,
+    Code,
+    Link,
+    Dim,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Title => "\x1b[1;4;32m",
+            Style::Heading => "\x1b[1;34m",
+            Style::Bold => "\x1b[1m",
+            Style::Italic => "\x1b[3m",
+            Style::Strike => "\x1b[9m",
+            Style::Code => "\x1b[36m",
+            Style::Link => "\x1b[4;34m",
+            Style::Dim => "\x1b[2m",
+        }
+    }
+}
+
+/// Lays out text (and markdown) into lines wrapped to a width, styling it with ansi escape codes if `styled`
+struct Page {
+    width: usize,
+    styled: bool,
+    text: String,
+    /// The line being laid out, its width and the width of its indentation (`None` if no line is started)
+    line: Option<(String, usize, usize)>,
+    /// What lines are indented with (like `│ ` in blockquotes)
+    indents: Vec<String>,
+    /// What the innermost indent is replaced with on the next line (like the bullet of a list item)
+    marker: Option<String>,
+    styles: Vec<Style>,
+    /// Escape codes to write before the next word (so spaces before it aren't styled)
+    pending: String,
+    /// If there's a space before the next word
+    space: bool,
+    /// The next number of each (nested) list, or `None` if it's unordered
+    lists: Vec<Option<u64>>,
+    links: Vec<String>,
+    in_code: bool,
+}
+
+impl Page {
+    fn new(width: usize, styled: bool) -> Self {
+        Self {
+            width,
+            styled,
+            text: String::new(),
+            line: None,
+            indents: Vec::new(),
+            marker: None,
+            styles: Vec::new(),
+            pending: String::new(),
+            space: false,
+            lists: Vec::new(),
+            links: Vec::new(),
+            in_code: false,
+        }
+    }
+
+    fn start_line(&mut self) {
+        let mut prefix = self.indents.concat();
+        if let Some(marker) = self.marker.take() {
+            prefix = self.indents[..self.indents.len().saturating_sub(1)].concat() + &marker;
+        }
+        let width = prefix.chars().count();
+        self.line = Some((prefix, width, width));
+        self.pending = self.codes();
+        self.space = false;
+    }
+
+    fn end_line(&mut self) {
+        let Some((line, _, _)) = self.line.take() else { return };
+        self.text.push_str(line.trim_end());
+        if self.styled && !self.styles.is_empty() { self.text.push_str(RESET) }
+        self.text.push('\n');
+        self.space = false;
+    }
+
+    /// Ends the line and leaves a blank line before whatever comes next
+    fn block(&mut self) {
+        self.end_line();
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") { self.text.push('\n') }
+    }
+
+    fn word(&mut self, word: &str) {
+        let len = word.chars().count();
+        if let Some((_, width, indent)) = &self.line {
+            if *width > *indent && width + 1 + len > self.width { self.end_line() }
+        }
+        if self.line.is_none() { self.start_line() }
+
+        let Some((line, width, indent)) = &mut self.line else { return };
+        if self.space && width > indent {
+            line.push(' ');
+            *width += 1;
+        }
+        line.push_str(&self.pending);
+        line.push_str(word);
+        *width += len;
+        self.pending.clear();
+        self.space = false;
+    }
+
+    /// Lays out text word by word
+    fn words(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) { self.space = true }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 { self.space = true }
+            self.word(word);
+        }
+        if text.ends_with(char::is_whitespace) { self.space = true }
+    }
+
+    fn codes(&self) -> String {
+        match self.styled {
+            true => self.styles.iter().map(|x| x.code()).collect(),
+            false => String::new(),
+        }
+    }
+
+    fn push_style(&mut self, style: Style) {
+        self.styles.push(style);
+        if self.styled { self.pending.push_str(style.code()) }
+    }
+
+    fn pop_style(&mut self) {
+        self.styles.pop();
+        if !self.styled { return }
+        self.pending = self.codes();
+        if let Some((line, _, _)) = &mut self.line { line.push_str(RESET) }
+    }
+
+    fn styled(&mut self, style: Style, text: &str) {
+        self.push_style(style);
+        self.words(text);
+        self.pop_style();
+    }
+
+    fn heading(&mut self, style: Style, text: &str) {
+        self.block();
+        self.styled(style, text);
+        self.end_line();
+    }
+
+    fn rule(&mut self) {
+        self.block();
+        self.styled(Style::Dim, &"─".repeat(self.width));
+        self.end_line();
+    }
+
+    /// Lays out a list of markdown notes as bullet points
+    fn notes(&mut self, notes: &[String]) {
+        let list = notes.iter().map(|x| format!("- {}", x.replace('\n', "\n  "))).collect::<Vec<_>>().join("\n");
+        self.markdown(&list);
+    }
+
+    fn markdown(&mut self, markdown: &str) {
+        for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_TABLES) {
+            self.event(event);
+        }
+        self.end_line();
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.in_code => {
+                for line in text.lines() {
+                    self.start_line();
+                    self.word(&format!("    {line}"));
+                    self.end_line();
+                }
+            },
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) | Event::InlineMath(text) | Event::DisplayMath(text) => self.words(&text),
+            Event::Code(code) if self.styled => self.styled(Style::Code, &code),
+            Event::Code(code) => self.words(&format!("`{code}`")),
+            Event::SoftBreak => self.space = true,
+            Event::HardBreak => self.end_line(),
+            Event::Rule => self.rule(),
+            Event::TaskListMarker(done) => self.word(if done { "[x]" } else { "[ ]" }),
+            Event::FootnoteReference(x) => self.word(&format!("[^{x}]")),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::Table(_) => self.block(),
+            Tag::Heading { .. } => { self.block(); self.push_style(Style::Heading) },
+            Tag::BlockQuote(_) => { self.block(); self.indents.push(if self.styled { "│ " } else { "> " }.into()) },
+            Tag::CodeBlock(_) => { self.block(); self.push_style(Style::Code); self.in_code = true },
+            Tag::List(start) => { if self.lists.is_empty() { self.block() } else { self.end_line() }; self.lists.push(start) },
+            Tag::Item => {
+                self.end_line();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => { *n += 1; format!("{}. ", *n - 1) },
+                    _ => "• ".into(),
+                };
+                self.indents.push(" ".repeat(marker.chars().count()));
+                self.marker = Some(marker);
+            },
+            Tag::TableRow | Tag::TableHead => self.end_line(),
+            Tag::TableCell if self.line.is_some() => self.word("|"),
+            Tag::Emphasis => self.push_style(Style::Italic),
+            Tag::Strong => self.push_style(Style::Bold),
+            Tag::Strikethrough => self.push_style(Style::Strike),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => { self.links.push(dest_url.to_string()); self.push_style(Style::Link) },
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Table => self.end_line(),
+            TagEnd::Heading(_) => { self.pop_style(); self.end_line() },
+            TagEnd::BlockQuote(_) => { self.end_line(); self.indents.pop(); },
+            TagEnd::CodeBlock => { self.in_code = false; self.pop_style() },
+            TagEnd::List(_) => { self.end_line(); self.lists.pop(); },
+            TagEnd::Item => { self.end_line(); self.indents.pop(); self.marker = None },
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => self.pop_style(),
+            TagEnd::Link | TagEnd::Image => {
+                self.pop_style();
+                let url = self.links.pop().unwrap_or_default();
+                if !url.is_empty() {
+                    self.space = true;
+                    self.styled(Style::Dim, &format!("({url})"));
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.end_line();
+        self.text
+    }
+}
+
+/// Renders a whole entry (its sections and all) as a page of text `width` wide, styled with ansi escape codes if `styled`
+pub fn render_entry(entry: &mut Entry, width: usize, styled: bool) -> Result<String> {
+    let mut page = Page::new(width, styled);
+    page.heading(Style::Title, entry.title()?);
+    let (date, private) = (entry.date()?.format(DATE_FORMAT).to_string(), *entry.private()?);
+    page.styled(Style::Dim, &meta(Some(date), entry.tags()?, private));
+    page.end_line();
+
+    let description = entry.description()?.clone();
+    if !description.is_empty() { page.markdown(&description) }
+    if !entry.notes()?.is_empty() {
+        page.heading(Style::Heading, "Notes");
+        page.notes(entry.notes()?);
+    }
+
+    for section in entry.sections()?.iter_mut() {
+        page.rule();
+        let title = section.title()?.clone();
+        page.block();
+        page.styled(Style::Heading, &title);
+        if *section.private()? { page.space = true; page.styled(Style::Dim, "(private)") }
+        page.end_line();
+        page.markdown(section.content()?);
+        if !section.notes()?.is_empty() {
+            page.heading(Style::Bold, "Notes");
+            page.notes(section.notes()?);
+        }
+        section.clear_cache();
+    }
+
+    entry.clear_cache();
+    Ok(page.finish())
+}
+
+/// Renders a moc as a page of text `width` wide with its collections resolved to the titles of what they link to
+pub fn render_moc(moc: &mut MOC, archive: &Archive, width: usize, styled: bool, logger: impl Logger) -> Result<String> {
+    let mut page = Page::new(width, styled);
+    page.heading(Style::Title, moc.title()?);
+    let private = *moc.private()?;
+    page.styled(Style::Dim, &meta(None, moc.tags()?, private));
+    page.end_line();
+
+    let description = moc.description()?.clone();
+    if !description.is_empty() { page.markdown(&description) }
+    if !moc.notes()?.is_empty() {
+        page.heading(Style::Heading, "Notes");
+        page.notes(moc.notes()?);
+    }
+
+    for collection in moc.collections()?.iter_mut() {
+        page.rule();
+        page.heading(Style::Heading, &collection.title()?.clone());
+        if !collection.notes()?.is_empty() { page.notes(collection.notes()?) }
+
+        let linked = linked(collection, archive, Privacy::Show, logger.hollow())?;
+        page.block();
+        if let Some((diary, _)) = &linked.diary {
+            page.styled(Style::Dim, &format!("From diary '{diary}'"));
+            page.end_line();
+        }
+        if linked.is_empty() {
+            page.styled(Style::Dim, "Nothing is in this collection yet");
+            page.end_line();
+        }
+
+        let mut listings = Vec::with_capacity(linked.mocs.len() + linked.entries.len());
+        for uid in linked.mocs.iter() {
+            let mut moc = archive.get_moc(uid.clone())?;
+            listings.push((moc.title()?.clone(), format!("moc `{uid}`")));
+        }
+        let source = linked.diary.as_ref().map_or(archive, |(_, x)| x);
+        for uid in linked.entries.iter() {
+            let mut entry = source.get_entry(uid.clone())?;
+            listings.push((entry.title()?.clone(), format!("{} `{uid}`", entry.date()?.format(DATE_FORMAT))));
+        }
+        for (title, about) in listings {
+            page.indents.push("  ".into());
+            page.marker = Some("• ".into());
+            page.words(&title);
+            page.space = true;
+            page.styled(Style::Dim, &format!("— {about}"));
+            page.end_line();
+            page.indents.pop();
+        }
+        collection.clear_cache();
+    }
+
+    moc.clear_cache();
+    Ok(page.finish())
+}
+
+/// The line under a title: its date, tags and whether it's private
+fn meta(date: Option<String>, tags: &[String], private: bool) -> String {
+    let mut meta = date.into_iter().collect::<Vec<_>>();
+    if !tags.is_empty() { meta.push(tags.iter().map(|x| format!("#{x}")).collect::<Vec<_>>().join(" ")) }
+    if private { meta.push("private".into()) }
+    meta.join(" · ")
+}
+
+/// Shows an entry or moc in the terminal (through `$PAGER`), or as plain text when piped
+pub fn show_command(uid: String, is_moc: bool, home: &Home, logger: impl Logger) -> Result<()> {
+    let archive = Archive::load_shared(home, logger.hollow())?;
+    let terminal = io::stdout().is_terminal();
+    let width = match terminal {
+        true => terminal_size::terminal_size().map_or(PLAIN_WIDTH, |(width, _)| width.0 as usize),
+        false => PLAIN_WIDTH,
+    };
+
+    let page = match is_moc {
+        true => render_moc(&mut archive.get_moc(uid)?, &archive, width, terminal, logger.hollow())?,
+        false => render_entry(&mut archive.get_entry(uid)?, width, terminal)?,
+    };
+    drop(archive); // so the archive isn't locked while the pager is open
+
+    match terminal {
+        true => page_out(&page),
+        false => io::stdout().write_all(page.as_bytes()).context("While writing to stdout"),
+    }
+}
+
+/// Shows text through `$PAGER` (falling back to `less -R`), or prints it if there's no pager
+fn page_out(text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").ok().filter(|x| !x.is_empty()).unwrap_or("less -R".into());
+    // Pagers are often given arguments, eg `less -R`
+    let mut args = pager.split_whitespace();
+    let program = args.next().unwrap_or("less");
+    let child = Command::new(program)
+        .args(args)
+        .env("LESS", std::env::var("LESS").unwrap_or("FRX".into()))
+        .stdin(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        return io::stdout().write_all(text.as_bytes()).context("While writing to stdout");
+    };
+
+    // The pager may be quit before it's read everything
+    if let Some(mut stdin) = child.stdin.take() { let _ = stdin.write_all(text.as_bytes()); }
+    child.wait().with_context(|| format!("While waiting for pager '{pager}'"))?;
+    Ok(())
+}
@@ -1,44 +1,22 @@
 use soulog::*;
 use lazy_db::*;
-use crate::{list, archive::Archive};
+use chrono::NaiveDate;
+use crate::{list, archive::Archive, Context};
 
-pub fn younger(this: &[u16; 3], other: &[u16; 3]) -> bool {
-    let this_date = this[2] as u32 * 10000 + this[1] as u32 * 100 + this[0] as u32;
-    let other_date = other[2] as u32 * 10000 + other[1] as u32 * 100 + other[0] as u32;
-    this_date > other_date
+pub fn younger(this: &NaiveDate, other: &NaiveDate) -> bool {
+    this > other
 }
 
-pub fn sort(mut logger: impl Logger) {
-    // load archive
-    let archive = Archive::load(logger.hollow());
-
-    let unsorted = list::read(
-        |x| x.collect_string(),
-        &if_err!((logger) [Sort, err => ("While reading unsorted stack length: {err:?}")] retry search_database!((archive.database()) /order/unsorted)),
-        logger.hollow(),
-    );
-
-    if unsorted.len() == 0 {
-        log!((logger.verbose) Sort("No unsorted items on unsorted stack; doing nothing") as Inconvenience);
-        return;
-    }
-
-    let mut sorted = list::read(
-        |x| x.collect_string(),
-        &if_err!((logger) [Sort, err => ("While reading sorted list length: {err:?}")] retry search_database!((archive.database()) /order/sorted)),
-        logger.hollow(),
-    ).into_vec();
-
-    log!((logger) Sort("Sorting unsorted entries..."));
-    // main sorting code
-    for usort_uid in unsorted.into_vec() {
-        let usort_date = *archive.get_entry(usort_uid.clone(), logger.hollow()).unwrap().date(logger.hollow());
+/// Inserts the unsorted entries into the sorted ones by date
+fn merge(archive: &Archive, mut sorted: Vec<String>, unsorted: Vec<String>) -> crate::Result<Vec<String>> {
+    for usort_uid in unsorted {
+        let usort_date = *archive.get_entry(usort_uid.clone())?.date()?;
         
         sorted.retain(|item| item != &usort_uid); // remove duplicates
 
         let mut is_oldest = true;
         for i in (0..sorted.len()).rev() {
-            let sort_date = *archive.get_entry(sorted[i].clone(), logger.hollow()).unwrap().date(logger.hollow());
+            let sort_date = *archive.get_entry(sorted[i].clone())?.date()?;
 
             if younger(&usort_date, &sort_date) {
                 sorted.insert(i + 1, usort_uid.clone()); // replace the one before
@@ -49,41 +27,62 @@ pub fn sort(mut logger: impl Logger) {
             sorted.insert(0, usort_uid);
         }
     }
+    Ok(sorted)
+}
+
+fn read_unsorted(archive: &Archive) -> crate::Result<Box<[String]>> {
+    list::read_strings(
+        &search_database!((archive.database()) /order/unsorted).context("While reading unsorted stack")?,
+    )
+}
+
+pub fn sort(archive: &Archive, mut logger: impl Logger) -> crate::Result<()> {
+    let unsorted = read_unsorted(archive)?;
+
+    if unsorted.is_empty() {
+        log!((logger.verbose) Sort("No unsorted items on unsorted stack; doing nothing") as Inconvenience);
+        return Ok(());
+    }
+
+    log!((logger) Sort("Sorting unsorted entries..."));
+    let sorted = merge(archive, read_sorted(archive)?.into_vec(), unsorted.into_vec())?;
 
     // Store updates
     log!((logger) Sort("Sorted list length: {}", sorted.len()));
-    list::write( // store newly sorted list
+    archive.touch("order")?;
+    list::write_strings( // store newly sorted list
         sorted.as_ref(),
-        |file, x| LazyData::new_string(file, x),
-        &if_err!((logger) [Sort, err => ("While initing sorted list: {err:?}")] retry search_database!((archive.database()) /order/sorted)),
-        logger.hollow(),
-    );
+        &search_database!((archive.database()) /order/sorted).context("While initing sorted list")?,
+    )?;
 
-    list::write(
+    list::write_strings::<String>(
         &[],
-        |file, x: &String| LazyData::new_string(file, x),
-        &if_err!((logger) [Sort, err => ("While initing unsorted list: {err:?}")] retry search_database!((archive.database()) /order/unsorted)),
-        logger.hollow()
-    );
+        &search_database!((archive.database()) /order/unsorted).context("While initing unsorted list")?,
+    )?;
 
     log!((logger.vital) Sort("Successfully sorted entries") as Log);
+    Ok(())
 }
 
-pub fn sort_uids(uids: &[String], logger: impl Logger) -> Box<[String]> {
-    // load archive & sort if sorting is needed
-    let archive = Archive::load(logger.hollow());
-    sort(logger.hollow());
+/// All the entries sorted by date without storing the order, for when the archive is only being read
+pub fn sorted(archive: &Archive) -> crate::Result<Box<[String]>> {
+    let unsorted = read_unsorted(archive)?;
+    let sorted = read_sorted(archive)?;
+    if unsorted.is_empty() {
+        return Ok(sorted);
+    }
+    Ok(merge(archive, sorted.into_vec(), unsorted.into_vec())?.into_boxed_slice())
+}
 
-    let sorted = read_sorted(&archive, logger.hollow()).into_vec();
+pub fn sort_uids(archive: &Archive, uids: &[String]) -> crate::Result<Box<[String]>> {
+    let sorted = sorted(archive)?.into_vec();
     
     // remove unspecified
-    sorted.into_iter().filter(|x| uids.contains(x)).collect()
+    Ok(sorted.into_iter().filter(|x| uids.contains(x)).collect())
 }
 
-pub fn read_sorted(archive: &Archive, mut logger: impl Logger) -> Box<[String]> {
-    list::read(
-        |x| x.collect_string(),
-        &if_err!((logger) [Sort, err => ("While reading sorted list: {err:?}")] retry search_database!((archive.database()) /order/sorted)),
-        logger.hollow(),
+pub fn read_sorted(archive: &Archive) -> crate::Result<Box<[String]>> {
+    list::read_strings(
+        &search_database!((archive.database()) /order/sorted).context("While reading sorted list")?,
     )
-}
\ No newline at end of file
+}
@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+#[cfg(feature = "failpoints")]
+use std::cell::Cell;
+#[cfg(debug_assertions)]
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use soulog::*;
+use crate::{archive::Archive, Context, Error, Home, Result};
+
+#[cfg(feature = "failpoints")]
+thread_local! {
+    static FAILPOINT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Makes the `n`th (starting at 1) storage or io step on this thread fail, for testing that failures never leave the archive half-written
+#[cfg(feature = "failpoints")]
+#[doc(hidden)]
+pub fn fail_at(n: Option<usize>) {
+    FAILPOINT.with(|x| x.set(n));
+}
+
+/// How many more storage or io steps until the failure set with `fail_at` happens (`None` if it already has or none was set)
+#[cfg(feature = "failpoints")]
+#[doc(hidden)]
+pub fn steps_left() -> Option<usize> {
+    FAILPOINT.with(|x| x.get())
+}
+
+/// Counts a storage or io step, returning the injected failure if it is the step set with `fail_at`
+#[cfg(feature = "failpoints")]
+pub(crate) fn failpoint(context: impl FnOnce() -> String) -> Result<()> {
+    FAILPOINT.with(|x| match x.get() {
+        Some(n) if n <= 1 => {
+            x.set(None);
+            Err(Error::Io { context: context(), source: io::Error::other("injected failure") })
+        },
+        Some(n) => { x.set(Some(n - 1)); Ok(()) },
+        None => Ok(()),
+    })
+}
+
+/// Errors that happen within a transaction leave the archive as it was
+fn revert(err: Error) -> Error {
+    if err.may_corrupt() { Error::Reverted(Box::new(err)) } else { err }
+}
+
+/// Mirrors a file or directory with hard links, which keep a file's old contents as lazy-db replaces files rather than writing into them
+fn link(from: &Path, to: &Path) -> Result<()> {
+    if !from.is_dir() {
+        // Falls back to copying on filesystems without hard links
+        return fs::hard_link(from, to)
+            .or_else(|_| fs::copy(from, to).map(|_| ()))
+            .with_context(|| format!("While linking '{}'", from.to_string_lossy()));
+    }
+
+    fs::create_dir_all(to).with_context(|| format!("While creating directory '{}'", to.to_string_lossy()))?;
+    for x in fs::read_dir(from).with_context(|| format!("While reading directory '{}'", from.to_string_lossy()))? {
+        let x = x.with_context(|| format!("While reading directory '{}'", from.to_string_lossy()))?;
+        link(&x.path(), &to.join(x.file_name()))?;
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match path.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+/// An undo journal of the paths a transaction changes in the archive
+///
+/// Before a path is first changed, what it was is linked into the journal (or noted as missing) and listed in its manifest, so undoing the transaction only puts back what it touched
+pub struct Journal {
+    archive: PathBuf,
+    path: PathBuf,
+    /// The paths recorded so far (relative to the archive)
+    recorded: RefCell<Vec<PathBuf>>,
+}
+
+impl Journal {
+    fn start(home: &Home) -> Result<Self> {
+        let path = home.journal();
+        if path.exists() {
+            fs::remove_dir_all(&path).context("While removing a stale journal")?;
+        }
+        fs::create_dir_all(&path).context("While creating the transaction's journal")?;
+        File::create(path.join("manifest")).context("While creating the transaction's journal")?;
+        Ok(Self { archive: home.archive(), path, recorded: RefCell::new(Vec::new()) })
+    }
+
+    /// Records what a path (relative to the archive) is before it's changed, unless it (or a folder it's in) already was
+    pub fn record(&self, path: &Path) -> Result<()> {
+        let mut recorded = self.recorded.borrow_mut();
+        if recorded.iter().any(|x| path.starts_with(x)) { return Ok(()) }
+
+        // New paths are recorded from their first missing folder, so undoing them removes the folders made for them too
+        let mut target = PathBuf::new();
+        for x in path.components() {
+            target.push(x);
+            if !self.archive.join(&target).exists() { break }
+        }
+
+        let n = recorded.len();
+        let existed = self.archive.join(&target).exists();
+        if existed {
+            link(&self.archive.join(&target), &self.path.join(n.to_string()))?;
+        }
+        let mut manifest = OpenOptions::new().append(true).open(self.path.join("manifest")).context("While opening the transaction's journal")?;
+        writeln!(manifest, "{n} {} {}", if existed { '+' } else { '-' }, target.to_string_lossy())
+            .with_context(|| format!("While journaling '{}'", target.to_string_lossy()))?;
+        recorded.push(target);
+        Ok(())
+    }
+
+    /// Puts back everything the journal recorded (newest first) and removes it
+    fn undo(archive: &Path, journal: &Path) -> Result<()> {
+        let manifest = match fs::read_to_string(journal.join("manifest")) {
+            Ok(x) => x,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(), // interrupted before anything was recorded
+            Err(err) => return Err(Error::Io { context: "While reading the transaction's journal".into(), source: err }),
+        };
+
+        for line in manifest.lines().rev() {
+            let mut parts = line.splitn(3, ' ');
+            let (Some(n), Some(existed), Some(target)) = (parts.next(), parts.next(), parts.next()) else { continue }; // a half-written last line
+            let path = archive.join(target);
+            if path.exists() {
+                remove(&path).with_context(|| format!("While undoing changes to '{}'", path.to_string_lossy()))?;
+            }
+            if existed == "+" {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).with_context(|| format!("While undoing changes to '{}'", path.to_string_lossy()))?;
+                }
+                fs::rename(journal.join(n), &path).with_context(|| format!("While undoing changes to '{}'", path.to_string_lossy()))?;
+            }
+        }
+
+        match fs::remove_dir_all(journal) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::Io { context: "While removing the transaction's journal".into(), source: err }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Panics if anything in the archive changed since its snapshot without being recorded, which would be left behind if the transaction were undone
+    #[cfg(debug_assertions)]
+    fn assert_covers(&self, before: &Snapshot) {
+        let after = Snapshot::take(&self.archive);
+        let recorded = self.recorded.borrow();
+        let unrecorded = before.0.keys().chain(after.0.keys())
+            .filter(|x| before.0.get(*x) != after.0.get(*x))
+            .filter(|x| !recorded.iter().any(|y| x.starts_with(y)))
+            .collect::<Vec<_>>();
+        assert!(unrecorded.is_empty(), "Changed {unrecorded:?} in the archive within a transaction without recording them with `Archive::touch`");
+    }
+
+    /// Keeps the transaction's changes; once the manifest is gone there's nothing left to undo
+    fn finish(&self) -> Result<()> {
+        let manifest = self.path.join("manifest");
+        if let Err(err) = fs::remove_file(&manifest).context("While finishing the transaction's journal") {
+            if manifest.exists() { return Err(err) } // gone regardless of the error means it's finished
+        }
+        let _ = fs::remove_dir_all(&self.path); // otherwise cleaned up by `recover`
+        Ok(())
+    }
+}
+
+/// Every path in the archive (relative to it) along with the contents of its files, for checking that transactions journal everything they change
+#[cfg(debug_assertions)]
+struct Snapshot(BTreeMap<PathBuf, Option<Vec<u8>>>);
+
+#[cfg(debug_assertions)]
+impl Snapshot {
+    fn take(archive: &Path) -> Self {
+        fn walk(root: &Path, path: &Path, snapshot: &mut BTreeMap<PathBuf, Option<Vec<u8>>>) {
+            let Ok(dir) = fs::read_dir(root.join(path)) else { return };
+            for x in dir.filter_map(|x| x.ok()) {
+                let path = path.join(x.file_name());
+                match x.path().is_dir() {
+                    true => { snapshot.insert(path.clone(), None); walk(root, &path, snapshot) },
+                    false => { snapshot.insert(path, fs::read(x.path()).ok()); },
+                }
+            }
+        }
+
+        let mut snapshot = BTreeMap::new();
+        walk(archive, Path::new(""), &mut snapshot);
+        Self(snapshot)
+    }
+}
+
+/// Runs `op` on the archive, journaling the paths it changes, and undoes them if it fails, so the archive is never left half-written
+///
+/// Writers record the paths they're about to change with `Archive::touch`; `recover` undoes a transaction that was interrupted (eg by a crash)
+pub fn run<T>(archive: &Archive, mut logger: impl Logger, op: impl FnOnce(&Archive) -> Result<T>) -> Result<T> {
+    let home = archive.home();
+    log!((logger) Transaction("Journaling changes to the archive..."));
+    let result = Journal::start(home).map(Rc::new).and_then(|journal| {
+        #[cfg(debug_assertions)]
+        let before = Snapshot::take(&journal.archive);
+        let x = op(&archive.journaled(journal.clone(), logger.hollow())?)?;
+        #[cfg(debug_assertions)]
+        journal.assert_covers(&before);
+        journal.finish()?;
+        Ok(x)
+    });
+
+    match result {
+        Ok(x) => Ok(x),
+        Err(err) => {
+            log!((logger) Transaction("Undoing the transaction's changes..."));
+            match Journal::undo(&home.archive(), &home.journal()) {
+                Ok(()) => Err(revert(err)),
+                Err(undo_err) => {
+                    log!((logger.error) Transaction("Failed to undo the transaction ({undo_err}); it will be undone the next time the archive is modified") as Failure);
+                    Err(err)
+                },
+            }
+        },
+    }
+}
+
+/// Like `run`, but for operations that may change anything in the archive (like migrations), so all of it is journaled up front
+pub fn run_all<T>(archive: &Archive, logger: impl Logger, op: impl FnOnce(&Archive) -> Result<T>) -> Result<T> {
+    run(archive, logger, |archive| {
+        archive.touch("")?;
+        op(archive)
+    })
+}
+
+/// Runs `op` on a staged copy of the archive and then throws the copy away, for showing what an operation would do without doing it
+pub fn preview<T>(archive: &Archive, mut logger: impl Logger, op: impl FnOnce(&Archive) -> Result<T>) -> Result<T> {
+    let home = archive.home();
+    let staging = home.staging();
+
+    log!((logger) Transaction("Staging a copy of the archive to preview changes on..."));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).context("While removing a stale staged archive")?;
+    }
+    let result = link(&home.archive(), &staging)
+        .and_then(|_| Archive::load_dir(home, staging.clone(), logger.hollow()))
+        .and_then(|staged| op(&staged));
+    let _ = fs::remove_dir_all(&staging); // otherwise cleaned up by `recover`
+    result
+}
+
+/// Cleans up after a transaction that was interrupted (eg by a crash), undoing whatever it had changed
+pub fn recover(home: &Home, mut logger: impl Logger) -> Result<()> {
+    let (path, staging, journal) = (home.archive(), home.staging(), home.journal());
+
+    if journal.exists() {
+        if journal.join("manifest").is_file() {
+            log!((logger.vital) Transaction("Restoring archive '{}' as it was before an interrupted transaction", path.to_string_lossy()) as Inconvenience);
+        }
+        Journal::undo(&path, &journal)?;
+    }
+
+    if staging.exists() {
+        log!((logger) Transaction("Removing the staged copy of an interrupted preview..."));
+        fs::remove_dir_all(&staging).context("While removing a staged archive left by an interrupted preview")?;
+    }
+
+    Ok(())
+}
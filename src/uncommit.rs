@@ -1,10 +1,10 @@
 use std::fs;
 use lazy_db::*;
 use soulog::*;
-use crate::{archive::Archive, home_dir, list, sort};
+use crate::{archive::Archive, backups, history, Home, index, list, sort, transaction, Context, Error};
 
-pub fn uncommmit(uid: String, is_moc: bool, mut logger: impl Logger) {
-    let archive = Archive::load(logger.hollow());
+pub fn uncommmit(uid: String, is_moc: bool, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
     
     let path = if is_moc {
         archive.database().path().join("mocs").join(&uid)
@@ -14,46 +14,71 @@ pub fn uncommmit(uid: String, is_moc: bool, mut logger: impl Logger) {
     
     // Check if path exists
     if !path.is_dir() {
-        println!("{path:?}");
-        if is_moc {
-            log!((logger.error) Remove("moc of uid '{uid}' doesn't exist") as Fatal);
+        return Err(Error::NotFound(if is_moc {
+            format!("moc of uid '{uid}' doesn't exist")
         } else {
-            log!((logger.error) Remove("entry of uid '{uid}' doesn't exist") as Fatal);
-        } return logger.crash();
+            format!("entry of uid '{uid}' doesn't exist")
+        }));
     }
 
     // Confirm with the user about the action
     let expected = "mhm, yep, I do wanna remove this entry/moc permanently";
-    log!((logger.vital) Remove("To confirm with removing an entry/moc of uid '{uid}' PERMANENTLY enter the phrase below (without quotes):") as Log);
-    if_err!((logger) [Remove, err => ("Entered phrase incorrect, please retry")] retry {
+    log!((logger.vital) Remove("To confirm with removing an entry/moc of uid '{uid}' (along with its revisions) PERMANENTLY enter the phrase below (without quotes):") as Log);
+    let mut confirmed = false;
+    for _ in 0..3 {
         log!((logger.vital) Remove("\"{expected}\"") as Log);
         let input = logger.ask("Remove", "Enter the phrase");
-        if &input[0..input.len() - 1] != expected { Err(()) }
-        else { Ok(()) }
-    });
+        if input.trim_end_matches(['\n', '\r']) == expected {
+            confirmed = true;
+            break;
+        }
+        log!((logger.vital) Remove("Entered phrase incorrect, please retry") as Warning);
+    } if !confirmed {
+        return Err(Error::Aborted(format!("Entered phrase incorrect; not removing entry/moc of uid '{uid}'")));
+    }
     
     // Backup archive before modification
     log!((logger) Remove("Backing up archive before removal, if you want to revert back, run `diary-cli rollback -f`"));
-    let _ = std::fs::remove_file(home_dir().join("backup.ldb")); // Clean up
-    Archive::backup(home_dir().join("backup.ldb"), logger.hollow());
+    backups::create(&archive, &format!("remove '{uid}'"), logger.hollow())?;
+
+    transaction::run(&archive, logger.hollow(), |archive| remove(archive, &uid, is_moc, logger.hollow()))?;
+    log!((logger.vital) Remove("Successfully removed entry/moc of uid '{uid}'") as Log);
+    Ok(())
+}
 
+/// Removes an entry or moc from the archive without asking for confirmation, backing it up or journaling it
+pub fn remove(archive: &Archive, uid: &str, is_moc: bool, mut logger: impl Logger) -> crate::Result<()> {
+    let path = if is_moc {
+        archive.database().path().join("mocs").join(uid)
+    } else {
+        archive.database().path().join("entries").join(uid)
+    };
     log!((logger) Remove("Removing entry/moc of uid '{uid}'..."));
 
     // Remove the entry/moc
-    sort::sort(logger.hollow());
-    if_err!((logger) [Remove, err => ("While removing entry/moc from archive: {err:?}")] retry fs::remove_dir_all(&path));
+    sort::sort(archive, logger.hollow())?;
+    if is_moc {
+        index::remove(archive, &mut archive.get_moc(uid.to_string())?, true, logger.hollow())?;
+    } else {
+        index::remove(archive, &mut archive.get_entry(uid.to_string())?, false, logger.hollow())?;
+    }
+    archive.touch(format!("{}/{uid}", if is_moc { "mocs" } else { "entries" }))?;
+    fs::remove_dir_all(&path).context("While removing entry/moc from archive")?;
+    history::forget(archive, uid, is_moc)?;
 
     // Update order lists
-    if is_moc { return; }
+    if is_moc { return Ok(()); }
 
-    let sorted_container = if_err!((logger) [Remove, err => ("While loading sorted list: {err:?}")] retry search_database!((archive.database()) /order/sorted));
-    let sorted: Box<[String]> = sort::read_sorted(&archive, logger.hollow()).into_vec().into_iter().filter(|x| *x != uid).collect();
+    archive.touch("order/sorted")?;
+    let sorted_container = search_database!((archive.database()) /order/sorted).context("While loading sorted list")?;
+    let sorted: Box<[String]> = sort::read_sorted(archive)?.into_vec().into_iter().filter(|x| x != uid).collect();
 
-    list::write(&sorted, |f, x| LazyData::new_string(f, x), &sorted_container, logger.hollow());
+    list::write_strings(&sorted, &sorted_container)?;
 
     // Update itver
     log!((logger) Commit("Updating archive itver..."));
-    if_err!((logger) [Commit, err => ("While update archive itver: {err:?}")] retry write_database!((archive.database()) itver = new_u16(archive.itver + 1)));
-
-    log!((logger.vital) Remove("Successfully removed entry/moc of uid '{uid}'") as Log)
-}
\ No newline at end of file
+    let itver = archive.next_itver()?;
+    archive.touch("itver")?;
+    write_database!((archive.database()) itver = new_u32(itver)).context("While updating archive itver")?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::{collections::{hash_map::DefaultHasher, BTreeSet, HashMap}, fs, hash::{Hash, Hasher}, path::Path, thread, time::Duration};
+use soulog::*;
+use crate::{archive::Archive, check, commit, Error, Home};
+
+/// How often the watched folder is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What the config files of a folder (and the section files they reference) contained when it was last checked
+#[derive(Debug, Default)]
+struct Snapshot {
+    /// The hash of each config's contents
+    configs: HashMap<String, u64>,
+    /// The hash of each referenced section file's contents (`None` if it doesn't exist)
+    sections: HashMap<String, Option<u64>>,
+    /// The configs that reference each section file
+    references: HashMap<String, BTreeSet<String>>,
+}
+
+impl Snapshot {
+    fn take(dir: &Path) -> crate::Result<Self> {
+        let mut paths = Vec::new();
+        commit::find_configs(dir, &mut paths)?;
+
+        let mut this = Self::default();
+        for path in paths {
+            let config = path.to_string_lossy().to_string();
+            // Configs can be removed (or be mid-save) while being read
+            let Ok(contents) = fs::read_to_string(&path) else { continue };
+            for section in section_paths(&contents) {
+                this.sections.entry(section.clone()).or_insert_with(|| hash_file(&section));
+                this.references.entry(section).or_default().insert(config.clone());
+            }
+            this.configs.insert(config, hash(contents.as_bytes()));
+        }
+        Ok(this)
+    }
+
+    /// The configs that were added or changed (or whose section files were) since an older snapshot
+    fn changed_since(&self, old: &Self) -> BTreeSet<String> {
+        let mut changed = self.configs.iter()
+            .filter(|(path, hash)| old.configs.get(*path) != Some(hash))
+            .map(|(path, _)| path.clone())
+            .collect::<BTreeSet<_>>();
+        for (section, hash) in self.sections.iter() {
+            if old.sections.get(section).is_some_and(|x| x != hash) {
+                changed.extend(self.references[section].iter().cloned());
+            }
+        }
+        changed
+    }
+}
+
+/// The section `path`s an entry config references (relative to where `diary-cli` is run, like when committing it)
+fn section_paths(contents: &str) -> Vec<String> {
+    let Ok(config) = contents.parse::<toml::Table>() else { return Vec::new() };
+    config.get("section")
+        .and_then(|x| x.as_array())
+        .map(|sections| sections.iter().filter_map(|x| x.get("path")?.as_str()).map(|x| x.to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_file(path: &str) -> Option<u64> {
+    fs::read(path).ok().map(|x| hash(&x))
+}
+
+/// Watches a folder of entry and moc configs for changes, reporting the configs that changed (or whose section files did)
+pub struct Watcher {
+    dir: String,
+    snapshot: Snapshot,
+}
+
+impl Watcher {
+    /// Starts watching a folder (leaving out hidden folders like `.git`) from how it is now
+    pub fn new(dir: impl Into<String>) -> crate::Result<Self> {
+        let dir = dir.into();
+        if !Path::new(&dir).is_dir() {
+            return Err(Error::NotFound(format!("Folder '{dir}' doesn't exist")));
+        }
+        let snapshot = Snapshot::take(Path::new(&dir))?;
+        Ok(Self { dir, snapshot })
+    }
+
+    /// How many config files are being watched
+    pub fn len(&self) -> usize {
+        self.snapshot.configs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshot.configs.is_empty()
+    }
+
+    /// Returns the configs that changed since it was last polled (in order)
+    pub fn poll(&mut self) -> crate::Result<Vec<String>> {
+        let snapshot = Snapshot::take(Path::new(&self.dir))?;
+        let changed = snapshot.changed_since(&self.snapshot);
+        self.snapshot = snapshot;
+        Ok(changed.into_iter().collect())
+    }
+}
+
+/// Re-checks and re-commits changed configs, leaving out (and reporting) the invalid ones until they're saved again
+pub fn commit_changed(changed: &[String], home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    let archive = Archive::load(home, logger.hollow())?;
+    let problems = check::check(changed, Some(&archive), logger.hollow())?;
+
+    let mut valid = Vec::with_capacity(changed.len());
+    for path in changed {
+        let mut invalid = false;
+        for x in problems.iter().filter(|x| &x.path == path) {
+            if x.warning {
+                log!((logger.vital) Watch("{x}") as Warning);
+            } else {
+                log!((logger.error) Watch("{x}") as Failure);
+                invalid = true;
+            }
+        }
+        match invalid {
+            true => log!((logger.vital) Watch("Skipped '{path}' as it is invalid; it'll be committed once it's fixed and saved") as Warning),
+            false => valid.push(path.clone()),
+        }
+    }
+
+    if valid.is_empty() { return Ok(()) }
+    commit::commit_all(&archive, &valid, logger)
+}
+
+/// Watches a folder of configs, re-committing the ones that change once they've stopped changing for `debounce`
+pub fn watch_command(dir: String, debounce: Duration, home: &Home, mut logger: impl Logger) -> crate::Result<()> {
+    // Checks if the archive exists, as loading a missing one initialises it
+    if !home.archive().is_dir() {
+        return Err(Error::NotFound(format!("Archive '{}' doesn't exist! Run `diary-cli init` before you can commit", home.archive().to_string_lossy())));
+    }
+
+    let mut watcher = Watcher::new(dir.clone())?;
+    log!((logger.vital) Watch("Watching {} config file(s) in '{dir}' (press Ctrl+C to stop)...", watcher.len()) as Log);
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut changed = BTreeSet::from_iter(watcher.poll()?);
+        if changed.is_empty() { continue }
+
+        // Wait for rapid saves to settle
+        loop {
+            thread::sleep(debounce);
+            let more = watcher.poll()?;
+            if more.is_empty() { break }
+            changed.extend(more);
+        }
+
+        let changed = changed.into_iter().collect::<Vec<_>>();
+        log!((logger.vital) Watch("{} config file(s) changed", changed.len()) as Log);
+        // A failed commit (eg while another command has the archive locked) shouldn't stop the watch
+        if let Err(err) = commit_changed(&changed, home, logger.hollow()) {
+            log!((logger.error) Watch("Failed to commit changes: {err}") as Failure);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{backups, Error, Home, Quiet};
+
+fn commit(home: &Home, tmp: &TmpPath, uid: &str) {
+    let path = tmp.get_path().join(format!("{uid}.toml"));
+    std::fs::write(&path, entry(uid).title(format!("Title of {uid}")).to_string()).unwrap();
+    Archive::load(home, Quiet::new()).unwrap().commit(path, Quiet::new()).unwrap();
+}
+
+#[test]
+fn backups_history() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap();
+    for uid in ["a", "b", "c"] { commit(&home, &tmp, uid) }
+
+    // One backup per commit, made before the archive's itver is updated
+    let history = backups::list(&home).unwrap();
+    assert_eq!(history.iter().map(|x| x.itver).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert!(history[1].operation.starts_with("commit '"));
+    assert!(history.iter().all(|x| x.path(&home).is_file()));
+    assert_eq!(backups::find(&home, "1").unwrap(), history[1]);
+    assert_eq!(backups::find(&home, &history[2].id).unwrap(), history[2]);
+    assert!(matches!(backups::find(&home, "7"), Err(Error::NotFound(_))));
+
+    // Rolling back further than one step (backing up the current archive first)
+    Archive::rollback(&home, Some("1".into()), false, Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.itver, 1);
+    assert!(archive.get_entry("a".into()).is_ok());
+    assert!(archive.get_entry("b".into()).is_err());
+    let history = backups::list(&home).unwrap();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[3].itver, 3);
+
+    assert_eq!(backups::prune(&home, 2, Quiet::new()).unwrap(), 2);
+    assert_eq!(backups::list(&home).unwrap().iter().map(|x| x.itver).collect::<Vec<_>>(), vec![2, 3]);
+}
@@ -0,0 +1,46 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::check;
+use diary_cli::{Home, Quiet};
+
+#[test]
+fn check_configs() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let write = |name: &str, contents: &str| {
+        let path = tmp.get_path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+
+    let valid = write("valid.toml", "[entry]\nuid = 'trip'\ntitle = 'Trip'\ndescription = ''\ntags = [ 'travel' ]\ndate = 2023-08-21\n[[section]]\ntitle = 'Day one'\ncontents = 'a'\n");
+    let invalid = write("invalid.toml", "[entry]\nuid = 'trip'\ntitle = 5\ndescription = \"\"\"\nnot = a key\n\"\"\"\ntags = []\ndate = 2023-02-30\n\n[[section]]\ntitle = 'Day one'\npath = 'nowhere.md'\n\n[[section]]\ncontents = 'b'\n");
+    let broken = write("broken.toml", "[entry]\nuid = 'x'\ntags = [\n");
+    let moc = write("moc.toml", "is-moc = true\n[moc]\nuid = 'm'\ntitle = 'M'\ndescription = ''\ntags = []\n[[collection]]\ntitle = 'Trips'\ninclude = [ 'travel' ]\n[[collection]]\ntitle = 'Nothing'\ninclude = [ 'nothing' ]\n");
+
+    assert!(check::check(&[valid.clone(), moc.clone()], None, Quiet::new()).unwrap().iter().all(|x| x.warning));
+
+    let problems = check::check(&[valid.clone(), invalid.clone(), broken.clone(), moc.clone()], None, Quiet::new()).unwrap();
+    let found = problems.iter().map(|x| (x.path.as_str(), x.location, x.warning)).collect::<Vec<_>>();
+    assert_eq!(found, vec![
+        (invalid.as_str(), Some((3, 1)), false), // title's type
+        (invalid.as_str(), Some((8, 1)), false), // invalid date
+        (invalid.as_str(), Some((12, 1)), false), // missing path
+        (invalid.as_str(), Some((14, 1)), false), // missing title
+        (broken.as_str(), Some((4, 1)), false), // toml syntax
+        (invalid.as_str(), Some((2, 1)), false), // duplicate uid
+        (moc.as_str(), Some((12, 1)), true), // includes nothing
+    ]);
+    assert!(problems[5].message.contains(&format!("{valid}:2:1")));
+
+    // Tags in the archive count for includes, and the archive is left untouched
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    let entry = "[entry]\nuid = 'n'\ntitle = 'N'\ndescription = ''\ntags = [ 'nothing' ]\ndate = 2023-08-21\n[[section]]\ntitle = ''\ncontents = ''\n";
+    archive.commit_table(entry.parse().unwrap(), "nothing", "commit", Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert!(check::check(&[valid, moc], Some(&archive), Quiet::new()).unwrap().is_empty());
+    assert_eq!(Archive::load(&home, Quiet::new()).unwrap().itver, archive.itver);
+}
@@ -0,0 +1,53 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{backups, commit, sort, Error, Home, Quiet};
+
+#[test]
+fn bulk_commit() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let configs = tmp.get_path().join("configs");
+    let write = |name: &str, contents: &str| {
+        let path = configs.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+    let entry = |uid: &str, date: &str| format!("[entry]\nuid = '{uid}'\ntitle = '{uid}'\ndescription = ''\ntags = [ 'travel' ]\ndate = {date}\n[[section]]\ntitle = 'Day one'\ncontents = 'a'\n");
+
+    write("b.toml", &entry("b", "2023-08-22"));
+    write("2023/a.toml", &entry("a", "2023-08-21"));
+    write("moc.toml", "is-moc = true\n[moc]\nuid = 'm'\ntitle = 'M'\ndescription = ''\ntags = []\n[[collection]]\ntitle = 'Trips'\ninclude = [ 'travel' ]\n");
+    write(".git/hidden.toml", &entry("hidden", "2023-08-23"));
+    write("notes.md", "not a config");
+    let folder = configs.to_string_lossy().to_string();
+
+    // Folders need `--recursive`, and globs and folders are expanded without duplicates
+    assert!(matches!(commit::resolve(std::slice::from_ref(&folder), false), Err(Error::Validation(_))));
+    assert!(matches!(commit::resolve(&[format!("{folder}/nothing*.toml")], false), Err(Error::NotFound(_))));
+    let found = commit::resolve(&[format!("{folder}/*.toml"), folder.clone()], true).unwrap();
+    let names = found.iter().map(|x| x.rsplit_once("configs/").unwrap().1).collect::<Vec<_>>();
+    assert_eq!(names, vec!["b.toml", "moc.toml", "2023/a.toml"]);
+
+    // Nothing is committed (or backed up) if any config is invalid
+    Archive::init(&home, Quiet::new()).unwrap();
+    let invalid = write("invalid/c.toml", "[entry]\nuid = 'c'\ntitle = 5\n");
+    let result = commit::commit_command(vec![folder.clone()], true, &home, Quiet::new());
+    assert!(matches!(result, Err(Error::Validation(_))));
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert!(!archive.database_exists("entries/b"));
+    drop(archive);
+    assert!(backups::list(&home).unwrap().is_empty());
+
+    // Otherwise everything is committed with one backup, then sorted
+    std::fs::remove_file(invalid).unwrap();
+    commit::commit_command(vec![folder], true, &home, Quiet::new()).unwrap();
+    assert_eq!(backups::list(&home).unwrap().len(), 1);
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert!(archive.database_exists("mocs/m"));
+    assert!(!archive.database_exists("entries/hidden"));
+    assert_eq!(sort::read_sorted(&archive).unwrap().to_vec(), vec!["a".to_string(), "b".to_string()]);
+}
@@ -0,0 +1,142 @@
+mod isol;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use lazy_db::*;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::crypt::{self, NEW_PASSPHRASE_VAR, PASSPHRASE_VAR};
+use diary_cli::index::{self, Group};
+use diary_cli::{backups, fsck, Error, Home, Quiet};
+
+fn cli(home: &Home, passphrase: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_diary-cli"))
+        .arg("--archive").arg(home.root()).args(args)
+        .env(PASSPHRASE_VAR, passphrase)
+        .stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .output().unwrap()
+}
+
+/// Returns if a needle is in any of the names or contents of the files within a directory
+fn leaks(path: &Path, needle: &str) -> bool {
+    fs::read_dir(path).unwrap().map(|x| x.unwrap().path()).any(|x| {
+        x.file_name().unwrap().to_string_lossy().contains(needle)
+            || if x.is_dir() { leaks(&x, needle) } else { fs::read(&x).unwrap().windows(needle.len()).any(|x| x == needle.as_bytes()) }
+    })
+}
+
+/// Decompiles the newest backup, returning where it was decompiled to
+fn newest_backup(home: &Home) -> std::path::PathBuf {
+    let path = home.root().join("decompiled");
+    let _ = fs::remove_dir_all(&path);
+    LazyDB::decompile(backups::list(home).unwrap().pop().unwrap().path(home), &path).unwrap();
+    path
+}
+
+#[test]
+fn encrypted_archive() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    std::env::set_var(PASSPHRASE_VAR, "correct horse");
+    crypt::init_command(&home, Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Zanzibar'
+        description = ''
+        tags = [ 'secretive' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        contents = 'We flew to zanzibar'
+    ".parse().unwrap(), "trip", "commit 'private/trip.toml'", Quiet::new()).unwrap();
+
+    // Neither the archive nor its backups have anything in the clear, but it reads as normal
+    let hex = index::encode_key("secretive");
+    for needle in ["anzibar", "secretive", &hex] {
+        assert!(!leaks(&home.archive(), needle), "{needle}");
+    }
+
+    // Except for uids and the operations backups were made for, which are documented as left in the clear
+    assert!(home.archive().join("entries/trip").is_dir() && home.archive().join("revisions/entries/trip").is_dir());
+    assert!(leaks(&home.backups(), "private/trip.toml"));
+    archive.commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = [ 'secretive' ]
+        date = 2023-08-01
+        [[section]]
+        title = 'Day one'
+        contents = 'b'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    assert!(!leaks(&newest_backup(&home), "anzibar"));
+    assert_eq!(archive.get_entry("trip".into()).unwrap().title().unwrap(), "Zanzibar");
+    assert_eq!(index::search(&archive, Group::Tags, &["secretive".into()], true, false, Quiet::new()).unwrap(), ["hike", "trip"]);
+    assert_eq!(index::terms(&archive, Group::Words, false, Quiet::new()).unwrap(), ["b", "day", "flew", "hike", "one", "to", "we", "zanzibar"]);
+    assert!(fsck::scan(&archive).unwrap().is_empty());
+    drop(archive);
+
+    // Other processes need the passphrase
+    let wrong = cli(&home, "wrong", &["list"]);
+    let output = format!("{}{}", String::from_utf8_lossy(&wrong.stdout), String::from_utf8_lossy(&wrong.stderr));
+    assert!(!wrong.status.success());
+    assert!(output.contains("Incorrect passphrase"), "{output}");
+    assert!(cli(&home, "correct horse", &["list"]).status.success());
+
+    // A backup that can't be re-encrypted leaves everything under the old passphrase
+    std::env::set_var(NEW_PASSPHRASE_VAR, "battery staple");
+    let broken = backups::list(&home).unwrap()[0].path(&home);
+    let contents = fs::read(&broken).unwrap();
+    fs::write(&broken, b"not a backup").unwrap();
+    let result = crypt::change_passphrase_command(&home, Quiet::new());
+    assert!(matches!(&result, Err(Error::Encryption(x)) if x.contains(&*broken.file_name().unwrap().to_string_lossy())), "{result:?}");
+    assert!(cli(&home, "correct horse", &["list"]).status.success());
+    let salt = fs::read(home.archive().join("crypt/salt")).unwrap();
+    for backup in backups::list(&home).unwrap().iter().skip(1) {
+        let path = home.root().join("decompiled");
+        let _ = fs::remove_dir_all(&path);
+        LazyDB::decompile(backup.path(&home), &path).unwrap();
+        assert_eq!(fs::read(path.join("crypt/salt")).unwrap(), salt);
+        assert!(!backup.path(&home).with_extension("ldb.new").exists());
+    }
+    fs::write(&broken, contents).unwrap();
+
+    // Changing the passphrase re-encrypts its backups too
+    crypt::change_passphrase_command(&home, Quiet::new()).unwrap();
+    assert!(!cli(&home, "correct horse", &["list"]).status.success());
+    assert!(cli(&home, "battery staple", &["list"]).status.success());
+    let salt = fs::read(home.archive().join("crypt/salt")).unwrap();
+    for backup in backups::list(&home).unwrap() {
+        let path = home.root().join("decompiled");
+        let _ = fs::remove_dir_all(&path);
+        LazyDB::decompile(backup.path(&home), &path).unwrap();
+        assert_eq!(fs::read(path.join("crypt/salt")).unwrap(), salt);
+    }
+    assert!(cli(&home, "battery staple", &["rollback", "--to", &backups::list(&home).unwrap()[1].id]).status.success()); // from before `hike`
+    assert!(matches!(crypt::encrypt_command(&home, Quiet::new()), Err(Error::Validation(_))));
+
+    // Decrypting leaves it (and its backups) readable without one
+    crypt::decrypt_command(&home, Quiet::new()).unwrap();
+    assert!(!home.archive().join("crypt").exists());
+    assert!(leaks(&home.archive(), "anzibar") && leaks(&home.archive(), &hex));
+    assert!(leaks(&newest_backup(&home), "anzibar"));
+    assert!(cli(&home, "", &["list"]).status.success());
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(index::search(&archive, Group::Tags, &["secretive".into()], true, false, Quiet::new()).unwrap(), ["trip"]);
+    assert!(fsck::scan(&archive).unwrap().is_empty());
+    drop(archive);
+    assert!(matches!(crypt::decrypt_command(&home, Quiet::new()), Err(Error::Validation(_))));
+
+    // And it can be encrypted again
+    std::env::set_var(PASSPHRASE_VAR, "battery staple");
+    crypt::encrypt_command(&home, Quiet::new()).unwrap();
+    assert!(!leaks(&home.archive(), "anzibar") && !leaks(&home.archive(), &hex));
+    assert!(!leaks(&newest_backup(&home), "anzibar"));
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.get_entry("trip".into()).unwrap().sections().unwrap()[0].content().unwrap(), "We flew to zanzibar");
+}
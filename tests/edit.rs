@@ -0,0 +1,50 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{edit, history, Error, Home, Quiet};
+use toml::Table;
+
+#[test]
+fn edit_entries() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    let entry: Table = "
+        [entry]
+        uid = 'trip'
+        title = 'Trip'
+        description = 'Old'
+        tags = [ 'travel' ]
+        date = 2023-08-21
+
+        [[section]]
+        title = 'Day one'
+        contents = 'a'
+    ".parse().unwrap();
+    archive.commit_table(entry.clone(), "first", "commit", Quiet::new()).unwrap();
+
+    // Invalid configs are rejected without touching the archive
+    let mut invalid = entry.clone();
+    invalid["entry"].as_table_mut().unwrap().remove("title");
+    assert!(matches!(Archive::validate(invalid, "invalid", Quiet::new()), Err(Error::Validation(_))));
+    assert!(!Archive::validate(entry, "valid", Quiet::new()).unwrap());
+
+    // An 'editor' that changes the description, after reading the archive (which isn't locked while it's open)
+    drop(archive);
+    let editor = tmp.get_path().join("editor.sh");
+    let show = format!("'{}' --archive '{}' show trip > /dev/null || exit 1", env!("CARGO_BIN_EXE_diary-cli"), home.root().to_string_lossy());
+    std::fs::write(&editor, format!("#!/bin/sh\n{show}\nsed -i 's/^description = .*/description = \"New\"/' \"$1\"\n")).unwrap();
+    std::process::Command::new("chmod").arg("+x").arg(&editor).status().unwrap();
+    std::env::remove_var("VISUAL");
+    std::env::set_var("EDITOR", &editor);
+
+    edit::edit_command("trip".into(), false, &home, Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.get_entry("trip".into()).unwrap().description().unwrap(), "New");
+
+    let revisions = history::list(&archive, "trip", false).unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[1].item["entry"]["description"].as_str(), Some("New"));
+}
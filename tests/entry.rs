@@ -4,6 +4,7 @@ use soulog::*;
 use lazy_db::*;
 use isol::*;
 use diary_cli::entry::Entry;
+use diary_cli::Error;
 use std::fs;
 use toml::Table;
 
@@ -17,9 +18,10 @@ fn isol_entry() {
     fs::write(&example_path, "example content of a file").unwrap();
     let toml = format!("
         [entry]
+        uid = 'example-entry'
         title = 'Example Entry Title'
         description = 'Example Entry Description'
-        groups = [ '2023', 'entry', 'term1' ]
+        tags = [ '2023', 'entry', 'term1' ]
         notes = [ 'entry-note1', 'entry-note2', 'entry-note3', 'entry-note4' ]
         date = 2023-08-21
 
@@ -40,10 +42,42 @@ fn isol_entry() {
         "example-entry.toml",
         container,
         logger.hollow(),
-    );
+    ).unwrap();
 
     // Load
     entry.clear_cache();
-    entry.fill_cache(logger.hollow());
-    entry.sections(logger.hollow())[0].fill_cache(logger);
+    entry.fill_cache().unwrap();
+    entry.sections().unwrap()[0].fill_cache().unwrap();
+}
+
+#[test]
+fn entry_invalid() {
+    let tmp = new_env();
+    let logger = sbl::PanicLogger::new();
+    let container = LazyContainer::init(tmp.get_path().join("Entry")).unwrap();
+    let toml = "
+        [entry]
+        uid = 'example-entry'
+        description = 'Example Entry Description'
+        tags = [ 'entry' ]
+        date = 2023-08-21
+
+        [[section]]
+        title = 'Example Section Title'
+        contents = 'example content'
+    ";
+
+    // Missing the title
+    let result = Entry::new(
+        toml.parse::<Table>().unwrap(),
+        "example-entry.toml",
+        container,
+        logger.hollow(),
+    );
+
+    match result {
+        Err(Error::Validation(message)) => assert!(message.contains("'title'")),
+        Err(err) => panic!("expected a validation error, got '{err}'"),
+        Ok(_) => panic!("expected entry to be invalid"),
+    }
 }
\ No newline at end of file
@@ -0,0 +1,334 @@
+mod isol;
+
+use std::fs;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::export::{self, html, json, Format, Privacy, REDACTED};
+use diary_cli::import::{self, Collision, DiaryJson};
+use diary_cli::{check, diaries, pull, Error, Home, Quiet};
+
+#[test]
+fn private_exports() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Trip'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        contents = 'We flew out'
+        [[section]]
+        title = 'Day two'
+        contents = 'We argued'
+        private = true
+    ".parse().unwrap(), "trip", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2023-08-01
+        private = true
+        [[section]]
+        title = 'Day one'
+        contents = 'We got lost'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        is-moc = true
+        [moc]
+        uid = 'trips'
+        title = 'Trips'
+        description = ''
+        tags = []
+        [[collection]]
+        title = 'Travel'
+        include = [ 'travel' ]
+    ".parse().unwrap(), "trips", "commit", Quiet::new()).unwrap();
+    drop(archive);
+
+    let export = |privacy: Privacy| {
+        let path = tmp.get_path().join(format!("{privacy:?}"));
+        fs::create_dir_all(&path).unwrap();
+        export::export_md(false, None, None, privacy, path.to_string_lossy().to_string(), &home, Quiet::new()).unwrap();
+        let read = |uid: &str| fs::read_to_string(path.join(uid).with_extension("md")).ok();
+        (read("trip").unwrap(), read("hike").or_else(|| read("redacted-1")), read("trips").unwrap())
+    };
+
+    // Shown as they are
+    let (trip, hike, trips) = export(Privacy::Show);
+    assert!(trip.contains("We argued"));
+    assert!(hike.unwrap().contains("We got lost"));
+    assert!(trips.contains("(hike)"));
+
+    // Replaced with a placeholder, under an opaque name that collections link to
+    let (trip, hike, trips) = export(Privacy::Redact);
+    assert!(trip.contains("We flew out") && !trip.contains("We argued") && !trip.contains("Day two") && trip.contains(REDACTED));
+    let hike = hike.unwrap();
+    assert!(!hike.contains("We got lost") && !hike.contains("Hike") && hike.contains(REDACTED));
+    assert!(!tmp.get_path().join("Redact/hike.md").exists());
+    assert!(trips.contains("(trip)") && trips.contains("[Redacted](redacted-1)") && !trips.contains("hike") && !trips.contains("Hike"));
+
+    // Left out entirely
+    let (trip, hike, trips) = export(Privacy::Omit);
+    assert!(trip.contains("We flew out") && !trip.contains("We argued") && !trip.contains(REDACTED));
+    assert!(hike.is_none());
+    assert!(trips.contains("(trip)") && !trips.contains("(hike)"));
+
+    // Pulling keeps them private
+    let pulled = tmp.get_path().join("pulled");
+    pull::pull(pulled.clone(), "hike.toml".into(), false, "hike".into(), true, &home, Quiet::new()).unwrap();
+    pull::pull(pulled.clone(), "trip.toml".into(), false, "trip".into(), true, &home, Quiet::new()).unwrap();
+    let hike: toml::Table = fs::read_to_string(pulled.join("hike.toml")).unwrap().parse().unwrap();
+    let trip: toml::Table = fs::read_to_string(pulled.join("trip.toml")).unwrap().parse().unwrap();
+    assert_eq!(hike["entry"]["private"].as_bool(), Some(true));
+    assert_eq!(trip["section"][1]["private"].as_bool(), Some(true));
+    assert!(trip["entry"].get("private").is_none() && trip["section"][0].get("private").is_none());
+
+    // Only booleans are accepted
+    let invalid = tmp.get_path().join("invalid.toml");
+    fs::write(&invalid, "[entry]\nuid = 'x'\ntitle = 'X'\ndescription = ''\ntags = []\ndate = 2023-08-21\nprivate = 'yes'\n[[section]]\ntitle = ''\ncontents = ''\n").unwrap();
+    let problems = check::check(&[invalid.to_string_lossy().to_string()], None, Quiet::new()).unwrap();
+    assert_eq!(problems.iter().map(|x| (x.location, x.warning)).collect::<Vec<_>>(), [(Some((7, 1)), false)]);
+}
+
+#[test]
+fn website() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Trip <abroad>'
+        description = 'Off we go'
+        tags = [ 'travel', 'far away' ]
+        notes = [ 'a note' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        contents = 'We **flew** out <script>alert(1)</script>'
+        [[section]]
+        title = 'Day two'
+        contents = 'We argued'
+        private = true
+    ".parse().unwrap(), "trip", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'secret'
+        title = 'Secret'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2023-09-01
+        private = true
+        [[section]]
+        title = ''
+        contents = 'Nobody knows'
+    ".parse().unwrap(), "secret", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2022-03-01
+        [[section]]
+        title = 'Day one'
+        contents = 'We got lost'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        is-moc = true
+        [moc]
+        uid = 'trips'
+        title = 'Trips'
+        description = ''
+        tags = []
+        [[collection]]
+        title = 'Travel'
+        include = [ 'travel' ]
+        [[collection]]
+        title = 'Work'
+        include = [ 'work' ]
+        diary = 'work'
+    ".parse().unwrap(), "trips", "commit", Quiet::new()).unwrap();
+    drop(archive);
+    diaries::create(&home, "work", Quiet::new()).unwrap();
+    let work = Archive::load(&home.with_diary(Some("work")), Quiet::new()).unwrap();
+    work.commit_table("
+        [entry]
+        uid = 'meeting'
+        title = 'Meeting'
+        description = ''
+        tags = [ 'work' ]
+        date = 2023-01-01
+        [[section]]
+        title = ''
+        contents = 'Long'
+    ".parse().unwrap(), "meeting", "commit", Quiet::new()).unwrap();
+    drop(work);
+
+    let path = tmp.get_path().join("site");
+    export::export(Format::Html, false, None, None, Privacy::Redact, path.to_string_lossy().to_string(), &home, Quiet::new()).unwrap();
+    let read = |x: &str| fs::read_to_string(path.join(x)).unwrap();
+    assert!(path.join("style.css").is_file());
+
+    // Entries have their sections rendered from markdown, and raw html is kept as text
+    let trip = read("entries/trip.html");
+    assert!(trip.contains("<title>Trip &lt;abroad&gt;</title>") && trip.contains("href=\"../style.css\""));
+    assert!(trip.contains("<strong>flew</strong>") && trip.contains("&lt;script&gt;") && !trip.contains("<script>"));
+    assert!(trip.contains("<li>a note</li>") && trip.contains("href=\"../tags/far~20away.html\""));
+    assert!(!trip.contains("We argued") && trip.contains("redacted"));
+
+    // Private entries are redacted under an opaque name, and left off the index and tag pages
+    assert!(!path.join("entries/secret.html").exists() && read("entries/redacted-1.html").contains("Redacted"));
+
+    // The index is chronological, grouped by year
+    let index = read("index.html");
+    let position = |x: &str| index.find(x).unwrap();
+    assert!(position("<h2>2022</h2>") < position("entries/hike.html"));
+    assert!(position("entries/hike.html") < position("<h2>2023</h2>"));
+    assert!(position("<h2>2023</h2>") < position("entries/trip.html"));
+    assert!(index.contains("mocs/trips.html") && !index.contains("meeting") && !index.contains("Secret"));
+
+    // Tags each have a page
+    assert!(read("tags.html").contains("href=\"tags/travel.html\">travel</a> (2)"));
+    let travel = read("tags/travel.html");
+    assert!(travel.find("../entries/hike.html").unwrap() < travel.find("../entries/trip.html").unwrap());
+    assert!(read("tags/far~20away.html").contains("../entries/trip.html"));
+
+    // Mocs list their collections, including those of sibling diaries
+    let trips = read("mocs/trips.html");
+    assert!(trips.contains("<h2>Travel</h2>") && trips.contains("href=\"../entries/trip.html\""));
+    assert!(trips.contains("href=\"../entries/redacted-1.html\">Redacted</a>") && !trips.contains("secret") && !trips.contains("Secret"));
+    assert!(trips.contains("href=\"../diaries/work/meeting.html\""));
+    assert!(read("diaries/work/meeting.html").contains("href=\"../../style.css\""));
+    assert_eq!(html::file_name("a/b ~c"), "a~2fb~20~7ec");
+}
+
+#[test]
+fn json_round_trip() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path().join("a"));
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Trip'
+        description = 'Off we go'
+        tags = [ 'travel' ]
+        notes = [ 'a note' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        notes = [ 'flew' ]
+        contents = '''We flew out
+and \"landed\"'''
+        [[section]]
+        title = 'Day two'
+        contents = 'We argued'
+        private = true
+    ".parse().unwrap(), "trip", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = []
+        date = 2022-03-01
+        [[section]]
+        title = ''
+        contents = 'We got lost'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        is-moc = true
+        [moc]
+        uid = 'trips'
+        title = 'Trips'
+        description = ''
+        tags = []
+        [[collection]]
+        title = 'Travel'
+        include = [ 'travel' ]
+        query = 'date>=2023-01-01'
+    ".parse().unwrap(), "trips", "commit", Quiet::new()).unwrap();
+    drop(archive);
+
+    let export = |home: &Home, format: Format, privacy: Privacy, name: &str| {
+        let path = tmp.get_path().join(name);
+        export::export(format, false, None, None, privacy, path.to_string_lossy().to_string(), home, Quiet::new()).unwrap();
+        (path.clone(), fs::read_to_string(path).unwrap())
+    };
+
+    // The schema is versioned and entries are in chronological order
+    let (json_path, json) = export(&home, Format::Json, Privacy::Show, "a.json");
+    let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!((document["format"].as_str(), document["version"].as_u64()), (Some("diary-cli"), Some(json::SCHEMA_VERSION as u64)));
+    assert_eq!(document["entries"][0]["uid"], "hike");
+    assert_eq!(document["entries"][1]["sections"][1]["private"], true);
+    assert_eq!(document["mocs"][0]["collections"][0]["query"], "date>=2023-01-01");
+    assert_eq!(document["mocs"][0]["collections"][0]["diary"], serde_json::Value::Null);
+
+    // Ndjson has a header, then an item per line
+    let (ndjson_path, ndjson) = export(&home, Format::Ndjson, Privacy::Show, "a.ndjson");
+    let kinds = ndjson.lines().map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap()["kind"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    assert_eq!(kinds, ["header", "entry", "entry", "moc"]);
+
+    // Importing either recreates the archive
+    for (i, path) in [json_path, ndjson_path].iter().enumerate() {
+        let other = Home::new(tmp.get_path().join(format!("b{i}")));
+        Archive::init(&other, Quiet::new()).unwrap();
+        import::import(&DiaryJson, path.to_string_lossy().to_string(), false, Collision::Overwrite, &other, Quiet::new()).unwrap();
+        assert_eq!(export(&other, Format::Json, Privacy::Show, &format!("b{i}.json")).1, json);
+    }
+
+    // Private sections can be left out
+    let (_, omitted) = export(&home, Format::Json, Privacy::Omit, "omitted.json");
+    assert!(!omitted.contains("We argued") && omitted.contains("We got lost"));
+
+    // Redacted and omitted exports are marked as such, and importing them over the archive they came from leaves its private material alone
+    Archive::load(&home, Quiet::new()).unwrap().commit_table("
+        [entry]
+        uid = 'secret'
+        title = 'Secret'
+        description = ''
+        tags = []
+        date = 2023-09-01
+        private = true
+        [[section]]
+        title = ''
+        contents = 'Nobody knows'
+    ".parse().unwrap(), "secret", "commit", Quiet::new()).unwrap();
+    let before = export(&home, Format::Json, Privacy::Show, "before.json").1;
+    for (privacy, name) in [(Privacy::Redact, "redacted.json"), (Privacy::Omit, "omitted-again.json"), (Privacy::Redact, "redacted.ndjson")] {
+        let format = if name.ends_with(".ndjson") { Format::Ndjson } else { Format::Json };
+        let (path, contents) = export(&home, format, privacy, name);
+        if format == Format::Json {
+            let document: serde_json::Value = serde_json::from_str(&contents).unwrap();
+            assert_eq!(document["privacy"], if privacy == Privacy::Redact { "redacted" } else { "omitted" });
+            assert_eq!(document["entries"][0]["redacted"], false);
+            assert_eq!(document["entries"][1]["redacted"], true);
+        }
+        import::import(&DiaryJson, path.to_string_lossy().to_string(), false, Collision::Overwrite, &home, Quiet::new()).unwrap();
+        assert_eq!(export(&home, Format::Json, Privacy::Show, "after.json").1, before);
+    }
+
+    // Newer schemas and broken items are refused without touching the archive
+    let other = Home::new(tmp.get_path().join("c"));
+    Archive::init(&other, Quiet::new()).unwrap();
+    let write = |name: &str, contents: &str| {
+        let path = tmp.get_path().join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+    let newer = write("newer.json", &json.replace(&format!("\"version\": {}", json::SCHEMA_VERSION), "\"version\": 999"));
+    assert!(matches!(import::import(&DiaryJson, newer, false, Collision::Overwrite, &other, Quiet::new()), Err(Error::Validation(_))));
+    let broken = write("broken.ndjson", &ndjson.replace("\"date\":\"2022-03-01\"", "\"date\":\"yesterday\""));
+    assert!(matches!(import::import(&DiaryJson, broken, false, Collision::Overwrite, &other, Quiet::new()), Err(Error::Validation(_))));
+    assert!(Archive::load(&other, Quiet::new()).unwrap().list_entries(Quiet::new()).unwrap().is_empty());
+}
@@ -0,0 +1,77 @@
+mod isol;
+
+use soulog::*;
+use lazy_db::*;
+use isol::*;
+use diary_cli::entry::Entry;
+use diary_cli::search::Searchable;
+use diary_cli::find::{self, Field};
+use toml::Table;
+
+#[test]
+fn find_in_entry() {
+    let tmp = new_env();
+    let logger = sbl::PanicLogger::new();
+    let container = LazyContainer::init(tmp.get_path().join("Entry")).unwrap();
+    let toml = "
+        [entry]
+        uid = 'example-entry'
+        title = 'A trip to the coast'
+        description = 'Example Entry Description'
+        tags = [ '2023', 'entry' ]
+        notes = [ 'met Alice at the station' ]
+        date = 2023-08-21
+
+        [[section]]
+        title = 'Morning'
+        contents = 'Nothing much happened'
+
+        [[section]]
+        title = 'Afternoon'
+        notes = [ 'long walk' ]
+        contents = 'Walked along the beach with alice and bob'
+    ";
+
+    let mut entry = Entry::new(
+        toml.parse::<Table>().unwrap(),
+        "example-entry.toml",
+        container,
+        logger,
+    ).unwrap();
+
+    // Case sensitive plain text
+    let pattern = find::pattern("Alice", false, false, false).unwrap();
+    let matches = entry.find(&pattern).unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].field, Field::Note(0));
+    assert_eq!(matches[0].before, "met ");
+    assert_eq!(matches[0].after, " at the station");
+
+    // Case insensitive plain text
+    let pattern = find::pattern("alice", false, false, true).unwrap();
+    let matches = entry.find(&pattern).unwrap();
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[1].field, Field::SectionContent(1));
+    assert_eq!(matches[1].field.section(), Some(1));
+
+    // Regex
+    let pattern = find::pattern("trip|walk", true, true, false).unwrap();
+    let fields: Vec<Field> = entry.find(&pattern).unwrap().into_iter().map(|x| x.field).collect();
+    assert_eq!(fields, vec![Field::Title, Field::SectionNote(1, 0)]);
+
+    // Plain text isn't treated as a regex
+    let pattern = find::pattern("a.e", false, false, false).unwrap();
+    assert!(entry.find(&pattern).unwrap().is_empty());
+}
+
+#[test]
+fn find_snippet_context() {
+    let pattern = find::pattern("needle", false, false, false).unwrap();
+    let text = format!("{}needle\n{}", "a".repeat(40), "b".repeat(40));
+    let mut matches = Vec::new();
+    find::find_in("uid", Field::Description, &text, &pattern, &mut matches);
+
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].before, format!("...{}", "a".repeat(find::SNIPPET_CONTEXT)));
+    assert_eq!(matches[0].after, format!(" {}...", "b".repeat(find::SNIPPET_CONTEXT - 1)));
+}
@@ -0,0 +1,79 @@
+mod isol;
+
+use std::fs;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::fsck::{self, Fix};
+use diary_cli::{sort, Error, Home, Quiet};
+
+#[test]
+fn fsck_repair() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let trip = |uid: &str| entry(uid).title(format!("Trip {uid}")).tags(&["travel", "beach"]).notes(&["one", "two"]).section("Day two", "b").build();
+    Archive::init(&home, Quiet::new()).unwrap();
+    for uid in ["a", "b", "c", "d"] {
+        Archive::load(&home, Quiet::new()).unwrap().commit_table(trip(uid), uid, "commit", Quiet::new()).unwrap();
+    }
+    Archive::load(&home, Quiet::new()).unwrap().commit_table("
+        is-moc = true
+        [moc]
+        uid = 'trips'
+        title = 'Trips'
+        description = ''
+        tags = []
+        [[collection]]
+        title = 'Trips'
+        include = [ 'travel' ]
+    ".parse().unwrap(), "trips", "commit", Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    sort::sort(&archive, Quiet::new()).unwrap();
+    assert!(fsck::scan(&archive).unwrap().is_empty());
+
+    // Re-committed entries are in both order lists until it's next sorted, but not twice in either
+    archive.commit_table(trip("b"), "b", "commit", Quiet::new()).unwrap();
+    assert!(fsck::scan(&archive).unwrap().is_empty());
+    archive.commit_table(trip("b"), "b", "commit", Quiet::new()).unwrap();
+    let issues = fsck::scan(&archive).unwrap();
+    assert_eq!(issues.iter().map(|x| (x.path.as_str(), x.fix.clone())).collect::<Vec<_>>(), vec![("order/unsorted", Fix::Order)]);
+    sort::sort(&archive, Quiet::new()).unwrap();
+    assert!(fsck::scan(&archive).unwrap().is_empty());
+
+    // Break it in every way
+    let root = home.archive();
+    fs::remove_file(root.join("entries/a/title")).unwrap(); // restored from its revision
+    fs::remove_file(root.join("entries/b/notes/1")).unwrap(); // list length
+    fs::rename(root.join("entries/c/sections/1"), root.join("entries/c/sections/4")).unwrap(); // container numbering
+    fs::remove_dir_all(root.join("revisions/entries/d")).unwrap();
+    fs::remove_file(root.join("entries/d/date")).unwrap(); // can't be repaired
+    fs::remove_dir_all(root.join("mocs/trips/collections/0/include")).unwrap();
+    fs::remove_file(root.join("order/sorted/0")).unwrap(); // missing from the order
+    fs::write(root.join("order/unsorted/0"), []).unwrap(); // an unreadable element past its length
+
+    let issues = fsck::scan(&archive).unwrap();
+    let mut found = issues.iter().map(|x| (x.path.as_str(), x.fix.clone())).collect::<Vec<_>>();
+    found.sort_by(|a, b| a.0.cmp(b.0));
+    assert_eq!(found, vec![
+        ("entries/a", Fix::Restore { uid: "a".into(), is_moc: false }),
+        ("entries/b/notes", Fix::List("entries/b/notes".into())),
+        ("entries/c/sections", Fix::Containers("entries/c/sections".into())),
+        ("entries/d", Fix::Manual),
+        ("mocs/trips/collections/0/include", Fix::Restore { uid: "trips".into(), is_moc: true }),
+        ("order", Fix::Order),
+        ("order/sorted", Fix::List("order/sorted".into())),
+        ("order/unsorted", Fix::List("order/unsorted".into())),
+    ]);
+
+    // Only the one without revisions is left
+    let result = fsck::fsck_command(true, &home, Quiet::new());
+    assert!(matches!(result, Err(Error::Validation(_))));
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    let issues = fsck::scan(&archive).unwrap();
+    assert_eq!(issues.iter().map(|x| (x.path.as_str(), x.fix.clone())).collect::<Vec<_>>(), vec![("entries/d", Fix::Manual)]);
+
+    assert_eq!(archive.get_entry("a".into()).unwrap().title().unwrap(), "Trip a");
+    assert_eq!(archive.get_entry("b".into()).unwrap().notes().unwrap().as_ref(), ["one".to_string()]);
+    assert_eq!(archive.get_entry("c".into()).unwrap().sections().unwrap().len(), 2);
+    assert_eq!(archive.get_moc("trips".into()).unwrap().collections().unwrap()[0].include().unwrap().as_ref(), ["travel".to_string()]);
+}
@@ -0,0 +1,50 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::history::{self, Change};
+use diary_cli::{transaction, uncommit, Error, Home, Quiet};
+use toml::Value;
+
+#[test]
+fn history_revisions() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table(entry("trip").description("Old").contents("a\nb\nc").build(), "first", "commit", Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    archive.commit_table(entry("trip").description("New").tags(&["travel", "beach"]).contents("a\nB\nc").build(), "second", "commit", Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+
+    let revisions = history::list(&archive, "trip", false).unwrap();
+    assert_eq!(revisions.iter().map(|x| (x.number, x.itver)).collect::<Vec<_>>(), vec![(1, 1), (2, 2)]);
+    assert_eq!(revisions[0].title(), "Trip");
+    assert!(matches!(history::get(&archive, "trip", false, 3), Err(Error::NotFound(_))));
+    assert!(matches!(history::get(&archive, "trip", true, 1), Err(Error::NotFound(_))));
+
+    let changes = history::diff(&revisions[0].item, &revisions[1].item);
+    assert_eq!(changes, vec![
+        Change::Changed("description".into(), Value::from("Old"), Value::from("New")),
+        Change::Added("tags".into(), Value::from("beach")),
+        Change::Changed("section 1.contents".into(), Value::from("a\nb\nc"), Value::from("a\nB\nc")),
+    ]);
+    assert_eq!(history::line_diff("a\nb\nc", "a\nB\nc"), vec![(' ', "a"), ('-', "b"), ('+', "B"), (' ', "c")]);
+
+    // Restoring re-commits the old revision as the newest one
+    history::restore_command("trip".into(), false, 1, &home, Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.get_entry("trip".into()).unwrap().description().unwrap(), "Old");
+    let revisions = history::list(&archive, "trip", false).unwrap();
+    assert_eq!(revisions.len(), 3);
+    assert_eq!(history::count(&archive, "trip", false).unwrap(), 3);
+    assert_eq!(revisions[2].item, revisions[0].item);
+
+    // Removing it removes its revisions, so committing it again starts afresh
+    transaction::run(&archive, Quiet::new(), |x| uncommit::remove(x, "trip", false, Quiet::new())).unwrap();
+    assert!(history::list(&archive, "trip", false).unwrap().is_empty());
+    assert!(!archive.database_exists("revisions/entries/trip"));
+    assert_eq!(history::count(&archive, "trip", false).unwrap(), 0);
+    archive.commit_table(entry("trip").description("Newer").build(), "third", "commit", Quiet::new()).unwrap();
+    assert_eq!(history::list(&archive, "trip", false).unwrap().len(), 1);
+}
@@ -0,0 +1,52 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{diaries, Error, Home, Quiet};
+
+#[test]
+fn home_flag_wins() {
+    let home = Home::from_env(Some("/tmp/elsewhere".into()));
+    assert_eq!(home.path(), std::path::Path::new("/tmp/elsewhere"));
+    assert_eq!(home.archive(), std::path::Path::new("/tmp/elsewhere/archive"));
+    assert_eq!(home.backup(), std::path::Path::new("/tmp/elsewhere/backup.ldb"));
+}
+
+#[test]
+fn home_isolated_archives() {
+    let tmp = new_env();
+    let first = Home::new(tmp.get_path().join("first"));
+    let second = Home::new(tmp.get_path().join("second"));
+
+    Archive::init(&first, Quiet::new()).unwrap();
+    assert!(first.archive().exists());
+    assert!(!second.archive().exists());
+
+    let archive = Archive::load(&first, sbl::PanicLogger::new()).unwrap();
+    assert_eq!(archive.home(), &first);
+}
+
+#[test]
+fn home_named_diaries() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let work = home.with_diary(Some("work"));
+    assert_eq!(work.archive(), tmp.get_path().join("diaries/work/archive"));
+    assert_eq!(home.with_diary(Some("default")), home);
+
+    // Named diaries must be created before they're used
+    assert!(matches!(Archive::load(&work, Quiet::new()), Err(Error::NotFound(_))));
+    diaries::create(&home, "work", Quiet::new()).unwrap();
+    assert!(matches!(diaries::create(&home, "work", Quiet::new()), Err(Error::AlreadyExists(_))));
+    assert!(matches!(diaries::create(&home, "default", Quiet::new()), Err(Error::AlreadyExists(_))));
+    assert!(matches!(diaries::create(&home, "../oops", Quiet::new()), Err(Error::Validation(_))));
+    Archive::load(&work, Quiet::new()).unwrap();
+    assert_eq!(diaries::list(&home).unwrap(), vec!["default", "work"]);
+
+    diaries::rename(&home, "work", "job", Quiet::new()).unwrap();
+    assert_eq!(diaries::list(&home).unwrap(), vec!["default", "job"]);
+    assert!(home.with_diary(Some("job")).archive().is_dir());
+    assert!(matches!(diaries::rename(&home, "work", "job", Quiet::new()), Err(Error::NotFound(_))));
+    assert!(matches!(diaries::rename(&home, "default", "other", Quiet::new()), Err(Error::Conflict(_))));
+}
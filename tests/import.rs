@@ -0,0 +1,207 @@
+mod isol;
+
+use std::fs;
+use std::path::Path;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::export::{self, Privacy};
+use diary_cli::import::{self, day_one::DayOne, jrnl::Jrnl, markdown::Markdown, obsidian::Obsidian, Collision};
+use diary_cli::{diaries, Home, Quiet};
+
+/// Every entry in an archive, as pulled
+fn pulled(home: &Home) -> Vec<toml::Table> {
+    let archive = Archive::load(home, Quiet::new()).unwrap();
+    let mut entries = archive.list_entries(Quiet::new()).unwrap();
+    entries.sort_by(|a, b| a.uid.cmp(&b.uid));
+    entries.iter_mut().map(|x| x.pull(Path::new("."), true).unwrap()).collect()
+}
+
+#[test]
+fn obsidian_round_trip() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path().join("a"));
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Trip'
+        description = '''Off we go
+on a trip'''
+        tags = [ 'travel', 'summer' ]
+        notes = [ 'a note', 'another' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        notes = [ 'flew' ]
+        contents = '''We flew out
+
+### and this isn't a heading
+> nor is this a quote'''
+        [[section]]
+        title = 'Day two'
+        contents = 'We argued'
+        private = true
+        [[section]]
+        title = 'Day three'
+        notes = [ 'home' ]
+        contents = 'We came home'
+    ".parse().unwrap(), "trip", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = []
+        date = 2022-03-01
+        private = true
+        [[section]]
+        title = ''
+        contents = 'We got lost'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    archive.commit_table("
+        is-moc = true
+        [moc]
+        uid = 'trips'
+        title = 'Trips'
+        description = 'Where we went'
+        tags = [ 'travel' ]
+        private = true
+        [[collection]]
+        title = 'Travel'
+        notes = [ 'so far' ]
+        include = [ 'travel' ]
+        query = 'date>=2023-01-01'
+        [[collection]]
+        title = 'Work'
+        include = [ 'work' ]
+        diary = 'work'
+    ".parse().unwrap(), "trips", "commit", Quiet::new()).unwrap();
+    drop(archive);
+    diaries::create(&home, "work", Quiet::new()).unwrap();
+    Archive::load(&home.with_diary(Some("work")), Quiet::new()).unwrap().commit_table("
+        [entry]
+        uid = 'meeting'
+        title = 'Meeting'
+        description = ''
+        tags = [ 'work' ]
+        date = 2023-01-01
+        [[section]]
+        title = ''
+        contents = 'Long'
+    ".parse().unwrap(), "meeting", "commit", Quiet::new()).unwrap();
+
+    // Exporting then importing reproduces the entries and mocs, leaving the sibling diary's entries in its folder alone
+    let vault = tmp.get_path().join("vault");
+    fs::create_dir_all(&vault).unwrap();
+    export::export_md(false, None, None, Privacy::Show, vault.to_string_lossy().to_string(), &home, Quiet::new()).unwrap();
+    assert!(vault.join("work/meeting.md").is_file());
+    let other = Home::new(tmp.get_path().join("b"));
+    Archive::init(&other, Quiet::new()).unwrap();
+    import::import(&Obsidian, vault.to_string_lossy().to_string(), false, Collision::Overwrite, &other, Quiet::new()).unwrap();
+    assert_eq!(pulled(&other), pulled(&home));
+    let mocs = |home: &Home| Archive::load(home, Quiet::new()).unwrap().list_mocs(Quiet::new()).unwrap().iter_mut().map(|x| x.pull().unwrap()).collect::<Vec<_>>();
+    assert_eq!(mocs(&other), mocs(&home));
+
+    // Redacted notes are skipped rather than imported as placeholders
+    let redacted = tmp.get_path().join("redacted");
+    fs::create_dir_all(&redacted).unwrap();
+    export::export_md(false, None, None, Privacy::Redact, redacted.to_string_lossy().to_string(), &home, Quiet::new()).unwrap();
+    import::import(&Obsidian, redacted.to_string_lossy().to_string(), false, Collision::Overwrite, &other, Quiet::new()).unwrap();
+    assert_eq!(pulled(&other), pulled(&home));
+    assert_eq!(mocs(&other), mocs(&home));
+}
+
+#[test]
+fn obsidian_notes() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap();
+    let vault = tmp.get_path().join("vault");
+    fs::create_dir_all(vault.join(".obsidian")).unwrap();
+    fs::create_dir_all(vault.join("2023")).unwrap();
+    fs::write(vault.join(".obsidian/hidden.md"), "---\ndate: 2023-01-01\n---\nhidden").unwrap();
+    fs::write(vault.join("undated.md"), "# Undated\nno date here").unwrap();
+    fs::write(vault.join("2023/Beach day.md"), "---\ntags: [summer, \"#beach\"]\ndate: 2023-01-02T10:30\n---\n# At the beach\nIt was hot\n## Morning\n> Swam\n### Noon\nAte\n").unwrap();
+
+    import::import(&Obsidian, vault.to_string_lossy().to_string(), false, Collision::Overwrite, &home, Quiet::new()).unwrap();
+    let entries = pulled(&home);
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry["entry"]["uid"].as_str(), Some("Beach day"));
+    assert_eq!(entry["entry"]["title"].as_str(), Some("At the beach"));
+    assert_eq!(entry["entry"]["date"].as_datetime().unwrap().to_string(), "2023-01-02");
+    assert_eq!(entry["entry"]["tags"], toml::Value::from(vec!["summer", "beach"]));
+    let sections = entry["section"].as_array().unwrap().iter().map(|x| (x["title"].as_str().unwrap(), x["contents"].as_str().unwrap())).collect::<Vec<_>>();
+    assert_eq!(sections, [("", "It was hot"), ("Morning", "Swam"), ("Noon", "Ate")]);
+}
+
+#[test]
+fn other_tools() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap();
+    let write = |name: &str, contents: &str| {
+        let path = tmp.get_path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+    let day_one = |path: &str, dry_run, collision| import::import(&DayOne, path.into(), dry_run, collision, &home, Quiet::new()).unwrap();
+    let jrnl = |path: &str| import::import(&Jrnl, path.into(), false, Collision::Overwrite, &home, Quiet::new()).unwrap();
+    let markdown = |path: &str| import::import(&Markdown, path.into(), false, Collision::Overwrite, &home, Quiet::new()).unwrap();
+
+    // Day One
+    let journal = write("dayone/Journal.json", r##"{ "metadata": { "version": "1.0" }, "entries": [
+        { "uuid": "1A2B3C4D5E6F", "creationDate": "2023-08-21T09:12:03Z", "text": "# Beach\n\nIt was hot\n## Evening\nSunset", "tags": ["summer"], "starred": true },
+        { "uuid": "99990000AAAA", "creationDate": "2023-08-22T09:00:00Z", "text": "Just a line" },
+        { "uuid": "BROKEN", "text": "no date" }
+    ] }"##);
+    day_one(&journal, true, Collision::Overwrite);
+    assert!(pulled(&home).is_empty());
+    day_one(&journal, false, Collision::Overwrite);
+    let entries = pulled(&home);
+    assert_eq!(entries.len(), 2);
+    let beach = &entries[0];
+    assert_eq!(beach["entry"]["uid"].as_str(), Some("2023-08-21-1a2b3c4d"));
+    assert_eq!(beach["entry"]["title"].as_str(), Some("Beach"));
+    assert_eq!(beach["entry"]["tags"], toml::Value::from(vec!["summer", "starred"]));
+    assert_eq!(beach["section"][1]["title"].as_str(), Some("Evening"));
+    assert_eq!(entries[1]["entry"]["title"].as_str(), Some("Just a line"));
+
+    // jrnl, as plain text and as json
+    let text = write("journal.txt", "[2023-08-21 10:00] Went hiking. It was @outdoors fun\nand long\n\n[2023-08-21 18:00] Dinner with @family\n\n[2023-13-01 09:00] Bad date\n");
+    jrnl(&text);
+    let entries = pulled(&home);
+    let hike = entries.iter().find(|x| x["entry"]["uid"].as_str() == Some("2023-08-21-1")).unwrap();
+    assert_eq!(hike["entry"]["title"].as_str(), Some("Went hiking."));
+    assert_eq!(hike["entry"]["tags"], toml::Value::from(vec!["outdoors"]));
+    assert_eq!(hike["section"][0]["contents"].as_str(), Some("It was @outdoors fun\nand long"));
+    assert!(entries.iter().any(|x| x["entry"]["uid"].as_str() == Some("2023-08-21-2") && x["entry"]["tags"] == toml::Value::from(vec!["family"])));
+    let json = write("journal.json", r#"{ "entries": [ { "title": "Json", "body": "Body", "date": "2023-09-01", "time": "10:00", "tags": ["@work"], "starred": false } ] }"#);
+    jrnl(&json);
+    assert!(pulled(&home).iter().any(|x| x["entry"]["uid"].as_str() == Some("2023-09-01-1") && x["entry"]["tags"] == toml::Value::from(vec!["work"])));
+
+    // A folder of dated markdown notes
+    write("notes/2023-10-01 Harvest.md", "---\ntags: [autumn]\n---\nApples\n");
+    write("notes/2023-10-02.md", "# Rain\nWet\n");
+    write("notes/shopping.md", "milk");
+    markdown(&tmp.get_path().join("notes").to_string_lossy());
+    let entries = pulled(&home);
+    let harvest = entries.iter().find(|x| x["entry"]["uid"].as_str() == Some("2023-10-01 Harvest")).unwrap();
+    assert_eq!((harvest["entry"]["title"].as_str(), harvest["entry"]["tags"].clone()), (Some("Harvest"), toml::Value::from(vec!["autumn"])));
+    assert!(entries.iter().any(|x| x["entry"]["uid"].as_str() == Some("2023-10-02") && x["entry"]["title"].as_str() == Some("Rain")));
+    assert!(!entries.iter().any(|x| x["entry"]["uid"].as_str() == Some("shopping")));
+
+    // Collisions are skipped, overwritten or suffixed
+    let count = || pulled(&home).len();
+    let before = count();
+    let changed = write("dayone2/Journal.json", r##"{ "entries": [ { "uuid": "1A2B3C4D5E6F", "creationDate": "2023-08-21T09:12:03Z", "text": "# Changed" } ] }"##);
+    let title = |uid: &str| pulled(&home).into_iter().find(|x| x["entry"]["uid"].as_str() == Some(uid)).map(|x| x["entry"]["title"].as_str().unwrap().to_string());
+    day_one(&changed, false, Collision::Skip);
+    assert_eq!((count(), title("2023-08-21-1a2b3c4d").as_deref()), (before, Some("Beach")));
+    day_one(&changed, false, Collision::Suffix);
+    assert_eq!((count(), title("2023-08-21-1a2b3c4d-2").as_deref()), (before + 1, Some("Changed")));
+    day_one(&changed, false, Collision::Overwrite);
+    assert_eq!((count(), title("2023-08-21-1a2b3c4d").as_deref()), (before + 1, Some("Changed")));
+}
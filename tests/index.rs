@@ -0,0 +1,74 @@
+mod isol;
+
+use soulog::*;
+use lazy_db::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{Home, Quiet};
+use diary_cli::entry::Entry;
+use diary_cli::index::{self, Group};
+
+#[test]
+fn index_keys() {
+    for term in ["travel", "a/b c", "ünïcödé", ""] {
+        assert_eq!(index::decode_key(&index::encode_key(term)).unwrap(), term);
+    }
+    assert_eq!(index::decode_key("abc"), None);
+
+    let words: Vec<String> = index::tokenize("Met Alice, at the café!").collect();
+    assert_eq!(words, vec!["met", "alice", "at", "the", "café"]);
+}
+
+#[test]
+fn index_insert_and_remove() {
+    let tmp = new_env();
+    let logger = sbl::PanicLogger::new();
+    let archive = Archive::init(&Home::new(tmp.get_path()), Quiet::new()).unwrap();
+    let container = || search_database!((archive.database()) /entries/).unwrap();
+
+    let mut first = Entry::new(entry("first").tags(&["travel", "work"]).contents("Went to Paris").build(), "first", container(), logger.hollow()).unwrap();
+    let mut second = Entry::new(entry("second").contents("Stayed in paris and rome").build(), "second", container(), logger.hollow()).unwrap();
+    index::insert(&archive, &mut first, false, logger.hollow()).unwrap();
+    index::insert(&archive, &mut second, false, logger.hollow()).unwrap();
+
+    let tags = |x: &[&str], strict| index::search(&archive, Group::Tags, &x.iter().map(|x| x.to_string()).collect::<Vec<_>>(), strict, false, sbl::PanicLogger::new()).unwrap();
+    assert_eq!(tags(&["travel"], false), vec!["first", "second"]);
+    assert_eq!(tags(&["travel", "work"], true), vec!["first"]);
+    assert_eq!(tags(&["work", "nothing"], false), vec!["first"]);
+    assert!(tags(&["nothing"], false).is_empty());
+
+    assert_eq!(index::lookup(&archive, Group::Words, "paris", false, logger.hollow()).unwrap().to_vec(), vec!["first", "second"]);
+    assert_eq!(index::lookup(&archive, Group::Words, "rome", false, logger.hollow()).unwrap().to_vec(), vec!["second"]);
+    assert_eq!(index::terms(&archive, Group::Tags, false, logger.hollow()).unwrap(), vec!["travel", "work"]);
+
+    // Removing an item drops it (and any terms left empty) from the index
+    index::remove(&archive, &mut first, false, logger.hollow()).unwrap();
+    assert_eq!(tags(&["travel"], false), vec!["second"]);
+    assert_eq!(index::terms(&archive, Group::Tags, false, logger.hollow()).unwrap(), vec!["travel"]);
+
+    // Rebuilding from scratch gives the same result
+    index::rebuild(&archive, logger.hollow()).unwrap();
+    assert_eq!(tags(&["travel"], false), vec!["first", "second"]);
+    assert_eq!(index::lookup(&archive, Group::Words, "went", false, logger).unwrap().to_vec(), vec!["first"]);
+}
+
+#[test]
+fn index_missing() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    archive.commit_table(entry("trip").tags(&["travel"]).build(), "trip", "commit", Quiet::new()).unwrap();
+    drop(archive);
+    std::fs::remove_dir_all(home.archive().join("index")).unwrap();
+
+    // Reading an archive without an index never writes to it
+    let archive = Archive::load_dir(&home, home.archive(), Quiet::new()).unwrap();
+    assert!(matches!(index::lookup(&archive, Group::Tags, "travel", false, Quiet::new()), Err(diary_cli::Error::NotFound(_))));
+    assert!(!home.archive().join("index").exists());
+    drop(archive);
+
+    // Loading it to read falls back to loading it to modify it, which builds the index
+    let archive = Archive::load_shared(&home, Quiet::new()).unwrap();
+    assert_eq!(index::lookup(&archive, Group::Tags, "travel", false, Quiet::new()).unwrap().to_vec(), vec!["trip"]);
+    assert!(!home.journal().exists());
+}
@@ -1,9 +1,12 @@
+#![allow(dead_code)] // each test uses only some of these
+
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::fmt::Display;
 use std::fmt;
 use std::collections::hash_map::RandomState;
 use std::hash::{BuildHasher, Hasher};
+use toml::{Table, Value};
 
 pub fn new_env() -> TmpPath {
     // creates 'test_tmp' folder if it doesn't exist
@@ -43,4 +46,98 @@ impl Drop for TmpPath {
     fn drop(&mut self) {
         fs::remove_dir_all(&self.0).unwrap();
     }
-}
\ No newline at end of file
+}
+
+/// Starts an entry config with a title, a tag, a date and a section, which can each be changed
+pub fn entry(uid: &str) -> EntryConfig {
+    EntryConfig {
+        uid: uid.to_string(),
+        title: "Trip".into(),
+        description: String::new(),
+        tags: vec!["travel".into()],
+        notes: Vec::new(),
+        date: "2023-08-21".into(),
+        sections: vec![("Day one".into(), "a".into())],
+    }
+}
+
+pub struct EntryConfig {
+    uid: String,
+    title: String,
+    description: String,
+    tags: Vec<String>,
+    notes: Vec<String>,
+    date: String,
+    sections: Vec<(String, String)>,
+}
+
+impl EntryConfig {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn notes(mut self, notes: &[&str]) -> Self {
+        self.notes = notes.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    /// Sets the date, like `2023-08-21`
+    pub fn date(mut self, date: &str) -> Self {
+        self.date = date.to_string();
+        self
+    }
+
+    /// Replaces the contents of the first section
+    pub fn contents(mut self, contents: impl Into<String>) -> Self {
+        self.sections[0].1 = contents.into();
+        self
+    }
+
+    /// Adds another section
+    pub fn section(mut self, title: &str, contents: &str) -> Self {
+        self.sections.push((title.to_string(), contents.to_string()));
+        self
+    }
+
+    pub fn build(&self) -> Table {
+        let strings = |x: &[String]| Value::Array(x.iter().map(|x| Value::from(x.as_str())).collect());
+        let mut entry = Table::new();
+        entry.insert("uid".into(), self.uid.as_str().into());
+        entry.insert("title".into(), self.title.as_str().into());
+        entry.insert("description".into(), self.description.as_str().into());
+        entry.insert("tags".into(), strings(&self.tags));
+        if !self.notes.is_empty() {
+            entry.insert("notes".into(), strings(&self.notes));
+        }
+        entry.insert("date".into(), Value::Datetime(self.date.parse().unwrap()));
+
+        let sections = self.sections.iter().map(|(title, contents)| {
+            let mut section = Table::new();
+            section.insert("title".into(), title.as_str().into());
+            section.insert("contents".into(), contents.as_str().into());
+            Value::Table(section)
+        }).collect();
+
+        let mut config = Table::new();
+        config.insert("entry".into(), Value::Table(entry));
+        config.insert("section".into(), Value::Array(sections));
+        config
+    }
+}
+
+impl Display for EntryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", toml::to_string(&self.build()).map_err(|_| fmt::Error)?)
+    }
+}
@@ -0,0 +1,76 @@
+mod isol;
+
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{search, sort, Home, Quiet};
+
+fn cli(home: &Home, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_diary-cli"));
+    command.arg("--archive").arg(home.root()).args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    command
+}
+
+fn output(x: &Output) -> String {
+    format!("{}{}", String::from_utf8_lossy(&x.stdout), String::from_utf8_lossy(&x.stderr))
+}
+
+#[test]
+fn archive_locking() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap().commit_table("
+        [entry]
+        uid = 'trip'
+        title = 'Trip'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2023-08-21
+        [[section]]
+        title = 'Day one'
+        contents = 'a'
+    ".parse().unwrap(), "trip", "commit", Quiet::new()).unwrap();
+
+    // Loads within the same process never wait on each other
+    let reader = Archive::load_shared(&home, Quiet::new()).unwrap();
+    let writer = Archive::load(&home, Quiet::new()).unwrap();
+    drop(Archive::load_shared(&home, Quiet::new()).unwrap());
+
+    // Other processes can't read or modify it while it's being modified, and are told who has it
+    let listed = cli(&home, &["list"]).output().unwrap();
+    assert!(!listed.status.success());
+    assert!(output(&listed).contains(&format!("in use by process {}", std::process::id())), "{}", output(&listed));
+    drop(writer);
+
+    // But they can read it alongside other readers, and only wait to modify it when asked to
+    assert!(cli(&home, &["list"]).output().unwrap().status.success());
+    let sorted = cli(&home, &["sort"]).output().unwrap();
+    assert!(!sorted.status.success());
+    assert!(output(&sorted).contains("in use by another process reading it"), "{}", output(&sorted));
+
+    let mut waiting = cli(&home, &["--wait", "sort"]).spawn().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert!(waiting.try_wait().unwrap().is_none());
+    drop(reader);
+    assert!(waiting.wait().unwrap().success());
+
+    // Reading commands don't store the order they sort entries in
+    Archive::load(&home, Quiet::new()).unwrap().commit_table("
+        [entry]
+        uid = 'hike'
+        title = 'Hike'
+        description = ''
+        tags = [ 'travel' ]
+        date = 2023-08-01
+        [[section]]
+        title = 'Day one'
+        contents = 'b'
+    ".parse().unwrap(), "hike", "commit", Quiet::new()).unwrap();
+    let archive = Archive::load_shared(&home, Quiet::new()).unwrap();
+    search::list_command(false, false, true, None, None, &home, Quiet::new()).unwrap();
+    assert_eq!(sort::read_sorted(&archive).unwrap().as_ref(), ["trip".to_string()]);
+    assert_eq!(sort::sorted(&archive).unwrap().as_ref(), ["hike".to_string(), "trip".to_string()]);
+}
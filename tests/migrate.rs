@@ -0,0 +1,89 @@
+mod isol;
+
+use std::fs;
+use chrono::NaiveDate;
+use lazy_db::*;
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::migrate::{self, FORMAT_VERSION};
+use diary_cli::{backups, fsck, Error, Home, Quiet};
+
+/// Puts the list lengths and itver back the way archives before format version 2 stored them
+fn narrow(path: &std::path::Path) {
+    for x in fs::read_dir(path).unwrap() {
+        let x = x.unwrap().path();
+        if x.is_dir() {
+            narrow(&x);
+        } else if x.ends_with("length") || x.ends_with("archive/itver") {
+            let value = LazyData::load(&x).unwrap().collect_u32().unwrap();
+            let container = LazyContainer::load(path).unwrap();
+            LazyData::new_u16(container.data_writer(x.file_name().unwrap().to_str().unwrap()).unwrap(), value as u16).unwrap();
+        }
+    }
+}
+
+/// Puts an entry's date back the way archives of format version 0 stored it
+fn old_date(home: &Home, uid: &str, date: [u16; 3]) {
+    let container = LazyContainer::load(home.archive().join("entries").join(uid)).unwrap();
+    LazyData::new_u16_array(container.data_writer("date").unwrap(), &date).unwrap();
+}
+
+#[test]
+fn migrate_archive() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let archive = Archive::init(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.format_version, FORMAT_VERSION);
+    archive.commit_table(entry("trip").date("2023-08-21").build(), "trip", "commit", Quiet::new()).unwrap();
+    archive.commit_table(entry("hike").date("2023-08-01").build(), "hike", "commit", Quiet::new()).unwrap();
+    drop(archive);
+
+    // An archive from before format versions
+    fs::remove_file(home.archive().join("format_version")).unwrap();
+    narrow(&home.archive());
+    old_date(&home, "trip", [21, 8, 2023]);
+    old_date(&home, "hike", [30, 2, 2023]);
+
+    // A dry run leaves it as it was
+    migrate::migrate_command(true, &home, Quiet::new()).unwrap();
+    assert!(!home.archive().join("format_version").exists());
+    assert!(!home.staging().exists());
+    let backups = backups::list(&home).unwrap().len();
+
+    // Loading it migrates it (after backing it up)
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.format_version, FORMAT_VERSION);
+    assert_eq!(archive.itver, 2);
+    assert_eq!(archive.get_entry("trip".into()).unwrap().sections().unwrap().len(), 1);
+    assert_eq!(*archive.get_entry("trip".into()).unwrap().date().unwrap(), NaiveDate::from_ymd_opt(2023, 8, 21).unwrap());
+    let history = backups::list(&home).unwrap();
+    assert_eq!(history.len(), backups + 1);
+    assert_eq!(history.last().unwrap().operation, format!("migrate to format version {FORMAT_VERSION}"));
+
+    // Invalid dates don't stop it, but are left for fsck
+    assert!(matches!(archive.get_entry("hike".into()).unwrap().date(), Err(Error::Validation(_))));
+    let issues = fsck::scan(&archive).unwrap();
+    assert_eq!(issues.iter().map(|x| x.path.as_str()).collect::<Vec<_>>(), vec!["entries/hike"]);
+    drop(archive);
+
+    // Nothing left to do
+    migrate::migrate_command(false, &home, Quiet::new()).unwrap();
+    assert_eq!(backups::list(&home).unwrap().len(), backups + 1);
+
+    // Running out of itvers is an error rather than wrapping around
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    LazyData::new_u32(archive.database().as_container().unwrap().data_writer("itver").unwrap(), u32::MAX).unwrap();
+    drop(archive);
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert!(matches!(archive.commit_table(entry("walk").date("2023-09-01").build(), "walk", "commit", Quiet::new()), Err(Error::Overflow(_))));
+    assert!(!archive.database_exists("entries/walk"));
+    drop(archive);
+
+    // Archives from newer versions are refused
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    LazyData::new_u16(archive.database().as_container().unwrap().data_writer("format_version").unwrap(), FORMAT_VERSION + 1).unwrap();
+    drop(archive);
+    assert!(matches!(Archive::load(&home, Quiet::new()), Err(Error::Conflict(_))));
+    assert!(matches!(Archive::load_shared(&home, Quiet::new()), Err(Error::Conflict(_))));
+}
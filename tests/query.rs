@@ -0,0 +1,95 @@
+mod isol;
+
+use soulog::*;
+use lazy_db::*;
+use chrono::NaiveDate;
+use isol::*;
+use diary_cli::entry::Entry;
+use diary_cli::query::{self, Query, Op, TextField};
+use diary_cli::Error;
+use toml::Table;
+
+#[test]
+fn query_parse() {
+    let tag = |x: &str| Box::new(Query::Text(TextField::Tag, Op::Eq, x.to_string()));
+
+    assert_eq!(Query::parse("travel").unwrap(), *tag("travel"));
+    assert_eq!(
+        Query::parse("a or b and not c").unwrap(),
+        Query::Or(tag("a"), Box::new(Query::And(tag("b"), Box::new(Query::Not(tag("c")))))),
+    );
+    assert_eq!(
+        Query::parse("(a OR b) and date>=2023-01-02").unwrap(),
+        Query::And(Box::new(Query::Or(tag("a"), tag("b"))), Box::new(Query::Date(Op::Ge, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()))),
+    );
+    assert_eq!(
+        Query::parse("title~\"a \\\"trip\\\"\"").unwrap(),
+        Query::Text(TextField::Title, Op::Contains, "a \"trip\"".to_string()),
+    );
+}
+
+#[test]
+fn query_parse_errors() {
+    let column = |x: &str| Query::parse(x).unwrap_err().column;
+
+    assert_eq!(column("(travel or work"), 16);
+    assert_eq!(column("travel and"), 11);
+    assert_eq!(column("travel work"), 8);
+    assert_eq!(column("date>=2023-13-01"), 7);
+    assert_eq!(column("date~2023-01-01"), 5);
+    assert_eq!(column("colour=red"), 1);
+    assert_eq!(column("title<x"), 6);
+    assert_eq!(column("a and \"unterminated"), 7);
+    assert_eq!(column("a ! b"), 3);
+
+    // The query is kept alongside the error so it can be highlighted
+    match query::parse("travel and") {
+        Err(Error::Query { query, error }) => {
+            assert_eq!(query, "travel and");
+            assert_eq!(error.column, 11);
+        },
+        _ => panic!("expected a query error"),
+    }
+}
+
+#[test]
+fn query_matches_entry() {
+    let tmp = new_env();
+    let logger = sbl::PanicLogger::new();
+    let container = LazyContainer::init(tmp.get_path().join("Entry")).unwrap();
+    let toml = "
+        [entry]
+        uid = 'paris-trip'
+        title = 'A Trip to Paris'
+        description = 'Example Entry Description'
+        tags = [ 'travel', 'france' ]
+        notes = [ 'ate a croissant' ]
+        date = 2023-08-21
+
+        [[section]]
+        title = 'Morning'
+        contents = 'Saw the Eiffel Tower'
+    ";
+
+    let mut entry = Entry::new(
+        toml.parse::<Table>().unwrap(),
+        "example-entry.toml",
+        container,
+        logger,
+    ).unwrap();
+
+    let mut matches = |x: &str| Query::parse(x).unwrap().matches(&mut entry).unwrap();
+
+    assert!(matches("travel"));
+    assert!(matches("(travel or work) and not draft"));
+    assert!(!matches("travel and draft"));
+    assert!(matches("date>=2023-01-01 and date<2023-08-22"));
+    assert!(!matches("date>2023-08-21"));
+    assert!(matches("title~\"trip\""));
+    assert!(!matches("title=trip"));
+    assert!(matches("uid=paris-trip"));
+    assert!(matches("note~croissant and content~eiffel"));
+    assert!(!matches("content~croissant"));
+    assert!(matches("text~croissant"));
+    assert!(matches("tag~fra and tag!=draft"));
+}
@@ -0,0 +1,53 @@
+mod isol;
+
+use isol::*;
+use chrono::NaiveDate;
+use diary_cli::scaffold::{self, DEFAULT_PATTERN};
+use diary_cli::{Error, Home};
+use toml::Table;
+
+#[test]
+fn scaffold_uids() {
+    let date = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+    assert_eq!(scaffold::slugify("Trip to the Beach!"), "trip-to-the-beach");
+    assert_eq!(scaffold::slugify("  Ünï -- cödé  "), "ünï-cödé");
+    assert_eq!(scaffold::expand(DEFAULT_PATTERN, "Trip", date), "2023-08-01-trip");
+    assert_eq!(scaffold::expand("{title} ({dd}/{mm})", "Trip", date), "Trip (01/08)");
+}
+
+#[test]
+fn scaffold_templates() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let date = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+
+    // The built-in template
+    let (uid, table) = scaffold::scaffold(scaffold::load_template(&home, None).unwrap(), Some("Trip"), None, date).unwrap();
+    assert_eq!(uid, "2023-08-01-trip");
+    assert_eq!(table["entry"]["date"].as_datetime().unwrap().to_string(), "2023-08-01");
+    assert_eq!(table["section"].as_array().unwrap().len(), 1);
+    assert!(matches!(scaffold::load_template(&home, Some("daily")), Err(Error::NotFound(_))));
+
+    std::fs::create_dir_all(home.templates()).unwrap();
+    std::fs::write(home.templates().join("daily.toml"), "
+        uid-pattern = 'daily-{yyyy}{mm}{dd}'
+        [entry]
+        title = 'Daily {dd}/{mm}'
+        tags = [ 'daily' ]
+        [[section]]
+        title = 'Morning'
+        contents = 'Notes for {title}'
+    ").unwrap();
+
+    let (uid, table) = scaffold::scaffold(scaffold::load_template(&home, Some("daily")).unwrap(), None, None, date).unwrap();
+    assert_eq!(uid, "daily-20230801");
+    assert!(table.get("uid-pattern").is_none());
+    assert_eq!(table["entry"]["title"].as_str(), Some("Daily 01/08"));
+    assert_eq!(table["entry"]["tags"].as_array().unwrap().len(), 1);
+    assert_eq!(table["section"][0]["contents"].as_str(), Some("Notes for Daily 01/08"));
+
+    // `--pattern` overrides the template's
+    let (uid, _) = scaffold::scaffold(Table::new(), Some("x"), Some("{slug}"), date).unwrap();
+    assert_eq!(uid, "x");
+    assert!(matches!(scaffold::scaffold(Table::new(), Some("!!"), Some("{slug}"), date), Err(Error::Validation(_))));
+}
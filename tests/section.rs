@@ -28,14 +28,14 @@ fn isol_section() {
         container,
         "example-entry.toml",
         0,
-        logger.hollow(),
-    );
+        logger,
+    ).unwrap();
 
     // Loading the stuff
     section.clear_cache();
-    let title = section.title(logger.hollow()).clone();
-    let content = section.content(logger.hollow()).clone();
-    let notes = section.notes(logger.hollow());
+    let title = section.title().unwrap().clone();
+    let content = section.content().unwrap().clone();
+    let notes = section.notes().unwrap();
 
     assert_eq!(title, "Example Title");
     assert_eq!(content, "example content of a file");
@@ -0,0 +1,69 @@
+mod isol;
+
+use std::process::{Command, Stdio};
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{commit, show, Home, Quiet};
+
+#[test]
+fn show_pages() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let write = |name: &str, contents: &str| {
+        let path = tmp.get_path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+
+    let trip = write("trip.toml", r#"[entry]
+uid = 'trip'
+title = 'Trip'
+description = 'We went **hiking** up the trail, which was a lot further than any of us thought it would be'
+tags = [ 'travel' ]
+notes = [ 'Bring `water`' ]
+date = 2023-08-21
+[[section]]
+title = 'Day one'
+private = true
+contents = """
+It was a long way up.
+
+- first
+- second
+"""
+"#);
+    let moc = write("moc.toml", "is-moc = true\n[moc]\nuid = 'm'\ntitle = 'Travel'\ndescription = ''\ntags = []\n[[collection]]\ntitle = 'Trips'\ninclude = [ 'travel' ]\n[[collection]]\ntitle = 'Nothing'\ninclude = [ 'nothing' ]\n");
+    Archive::init(&home, Quiet::new()).unwrap();
+    commit::commit_command(vec![trip, moc], false, &home, Quiet::new()).unwrap();
+    let archive = Archive::load_shared(&home, Quiet::new()).unwrap();
+
+    // Plain pages are wrapped to their width, with sections (private or not) and their contents
+    let page = show::render_entry(&mut archive.get_entry("trip".into()).unwrap(), 40, false).unwrap();
+    assert!(!page.contains('\x1b'));
+    assert!(page.lines().all(|x| x.chars().count() <= 40));
+    assert!(page.starts_with("Trip\n2023-08-21 · #travel\n\nWe went hiking up the trail, which was a\nlot further"));
+    for x in ["• Bring `water`", "Day one (private)", "It was a long way up.", "• first\n• second"] {
+        assert!(page.contains(x), "{x:?} isn't in {page}");
+    }
+
+    // Styled pages have markdown styling
+    let page = show::render_entry(&mut archive.get_entry("trip".into()).unwrap(), 40, true).unwrap();
+    assert!(page.contains("\x1b[1mhiking\x1b[0m"));
+
+    // Mocs have their collections resolved to titles
+    let page = show::render_moc(&mut archive.get_moc("m".into()).unwrap(), &archive, 80, false, Quiet::new()).unwrap();
+    assert!(page.contains("Trips\n\n• Trip — 2023-08-21 `trip`"));
+    assert!(page.contains("Nothing\n\nNothing is in this collection yet"));
+    drop(archive);
+
+    // Plain text is shown when piped (rather than through the pager)
+    let output = Command::new(env!("CARGO_BIN_EXE_diary-cli"))
+        .arg("--archive").arg(home.root()).args(["show", "trip"])
+        .env("PAGER", "false")
+        .stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Trip\n") && stdout.contains("It was a long way up.") && !stdout.contains('\x1b'));
+}
@@ -1,8 +1,9 @@
+use chrono::NaiveDate;
 use diary_cli::sort::younger;
 
 #[test]
 fn sort_is_younger() {
-    let date1 = [15, 8, 2023];
-    let date2 = [30, 8, 2023];
+    let date1 = NaiveDate::from_ymd_opt(2023, 8, 15).unwrap();
+    let date2 = NaiveDate::from_ymd_opt(2023, 8, 30).unwrap();
     assert!(younger(&date2, &date1))
 }
\ No newline at end of file
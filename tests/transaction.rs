@@ -0,0 +1,116 @@
+mod isol;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::{transaction, uncommit, Error, Home, Quiet};
+
+/// Every file in a directory along with its contents
+fn snapshot(path: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    for x in fs::read_dir(path).unwrap() {
+        let x = x.unwrap().path();
+        if x.is_dir() {
+            files.extend(snapshot(&x));
+        } else {
+            files.insert(x.clone(), fs::read(&x).unwrap());
+        }
+    } files
+}
+
+/// Makes each storage and io step of `op` fail in turn until it succeeds, checking the archive is left exactly as it was every time
+fn fail_each_step(home: &Home, op: impl Fn(&Archive) -> diary_cli::Result<()>) -> usize {
+    let before = snapshot(&home.archive());
+    for n in 1.. {
+        let archive = Archive::load(home, Quiet::new()).unwrap();
+        transaction::fail_at(Some(n));
+        let result = op(&archive);
+
+        // It either happens completely (once the failure is past the last step) or not at all
+        if result.is_ok() {
+            transaction::fail_at(None);
+            assert_ne!(snapshot(&home.archive()), before);
+            return n;
+        }
+        assert!(transaction::steps_left().is_none(), "step {n} failed without the failure happening: {result:?}");
+        assert!(matches!(result, Err(Error::Reverted(_))), "step {n} didn't fail: {result:?}");
+        assert_eq!(snapshot(&home.archive()), before, "step {n} changed the archive");
+        assert!(!home.journal().exists());
+    } unreachable!()
+}
+
+#[test]
+fn transaction_failures() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap().commit_table(entry("trip").description("Old").build(), "trip", "commit", Quiet::new()).unwrap();
+
+    // A new entry, a changed entry and a removal (backups are made before the transaction, so they're left out)
+    let steps = fail_each_step(&home, |x| transaction::run(x, Quiet::new(), |x| x.write_table(entry("hike").description("New").build(), "hike", Quiet::new())));
+    assert!(steps > 0);
+    fail_each_step(&home, |x| transaction::run(x, Quiet::new(), |x| x.write_table(entry("trip").description("Changed").build(), "trip", Quiet::new())));
+    fail_each_step(&home, |x| transaction::run(x, Quiet::new(), |x| uncommit::remove(x, "hike", false, Quiet::new())));
+
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.itver, 4);
+    assert_eq!(archive.get_entry("trip".into()).unwrap().description().unwrap(), "Changed");
+    assert!(!archive.database_exists("entries/hike"));
+
+    // Invalid configs fail part-way through parsing without writing anything
+    let before = snapshot(&home.archive());
+    let mut invalid = entry("broken").section("Day two", "b").build();
+    invalid["section"][1].as_table_mut().unwrap().remove("contents");
+    assert!(matches!(archive.commit_table(invalid, "broken", "commit", Quiet::new()), Err(Error::Validation(_))));
+    assert_eq!(snapshot(&home.archive()), before);
+}
+
+#[test]
+fn transaction_recovery() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    Archive::init(&home, Quiet::new()).unwrap().commit_table(entry("trip").description("Old").build(), "trip", "commit", Quiet::new()).unwrap();
+    let before = snapshot(&home.archive());
+
+    // Interrupted part-way through (like by a crash), leaving its journal behind
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    let crashed = panic::catch_unwind(AssertUnwindSafe(|| {
+        transaction::run(&archive, Quiet::new(), |x| -> diary_cli::Result<()> {
+            x.write_table(entry("hike").description("New").build(), "hike", Quiet::new())?;
+            x.write_table(entry("trip").description("Changed").build(), "trip", Quiet::new())?;
+            panic!("crashed");
+        })
+    }));
+    assert!(crashed.is_err());
+    drop(archive);
+    assert!(home.journal().exists());
+    assert_ne!(snapshot(&home.archive()), before);
+
+    // Which is undone the next time the archive is loaded to be modified
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert_eq!(archive.itver, 1);
+    assert!(!archive.database_exists("entries/hike"));
+    assert!(!home.journal().exists());
+    assert_eq!(snapshot(&home.archive()), before);
+    drop(archive);
+
+    // Whole-archive transactions are undone too
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    let result = transaction::run_all(&archive, Quiet::new(), |x| {
+        fs::remove_dir_all(x.database().path().join("entries")).unwrap();
+        Err::<(), _>(Error::Validation("failed".into()))
+    });
+    assert!(matches!(result, Err(Error::Validation(_))));
+    assert!(!home.journal().exists());
+    assert_eq!(snapshot(&home.archive()), before);
+    drop(archive);
+
+    // Interrupted while previewing
+    fs::create_dir_all(home.staging().join("entries")).unwrap();
+    Archive::load(&home, Quiet::new()).unwrap();
+    assert!(!home.staging().exists());
+    assert_eq!(snapshot(&home.archive()), before);
+}
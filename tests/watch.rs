@@ -0,0 +1,46 @@
+mod isol;
+
+use soulog::*;
+use isol::*;
+use diary_cli::archive::Archive;
+use diary_cli::watch::{self, Watcher};
+use diary_cli::{Home, Quiet};
+
+#[test]
+fn watch_changes() {
+    let tmp = new_env();
+    let home = Home::new(tmp.get_path());
+    let configs = tmp.get_path().join("configs");
+    let write = |name: &str, contents: &str| {
+        let path = configs.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    };
+
+    let section = write("sections/day-one.md", "Old");
+    let trip = write("trip.toml", &format!("[entry]\nuid = 'trip'\ntitle = 'Trip'\ndescription = ''\ntags = []\ndate = 2023-08-21\n[[section]]\ntitle = 'Day one'\npath = '{section}'\n"));
+    Archive::init(&home, Quiet::new()).unwrap();
+
+    let mut watcher = Watcher::new(configs.to_string_lossy()).unwrap();
+    assert_eq!(watcher.len(), 1);
+    assert!(watcher.poll().unwrap().is_empty());
+
+    // A changed section file maps back to the entry that references it
+    write("sections/day-one.md", "New");
+    assert_eq!(watcher.poll().unwrap(), vec![trip.clone()]);
+    assert!(watcher.poll().unwrap().is_empty());
+    watch::commit_changed(&[trip], &home, Quiet::new()).unwrap();
+    let mut entry = Archive::load(&home, Quiet::new()).unwrap().get_entry("trip".into()).unwrap();
+    assert_eq!(entry.sections().unwrap()[0].content().unwrap(), "New");
+
+    // Invalid configs are left out until they're fixed
+    let broken = write("broken.toml", "[entry]\nuid = 'broken'\ntitle = 5\n");
+    let hike = write("hike.toml", "[entry]\nuid = 'hike'\ntitle = 'Hike'\ndescription = ''\ntags = []\ndate = 2023-08-22\n[[section]]\ntitle = 'Up'\ncontents = 'a'\n");
+    let changed = watcher.poll().unwrap();
+    assert_eq!(changed, vec![broken, hike]);
+    watch::commit_changed(&changed, &home, Quiet::new()).unwrap();
+    let archive = Archive::load(&home, Quiet::new()).unwrap();
+    assert!(archive.database_exists("entries/hike"));
+    assert!(!archive.database_exists("entries/broken"));
+}